@@ -0,0 +1,6 @@
+fn main() {
+    // `cbindgen` is set by the bindings generator rather than enabled as
+    // a Cargo feature, so declare it to the compiler's cfg checking.
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(feature, values(\"cbindgen\"))");
+}
@@ -1,4 +1,4 @@
-use sharks::Sharks;
+use sharks::{InterpolationCtx, Randomized, Secret, Sharks};
 use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
@@ -112,6 +112,19 @@ impl fmt::Debug for Share {
 }
 
 impl Share {
+    /// The x coordinate of the underlying secret share, which is
+    /// sampled afresh each time a `Commune` is shared.
+    pub fn x(&self) -> sharks::Fp {
+        self.S.x
+    }
+
+    /// Checks that the share lies on the polynomial committed to by
+    /// `commitments`, as returned by `Commune::share_verifiable_rng`.
+    #[cfg(feature = "vss")]
+    pub fn verify(&self, commitments: &sharks::Commitments) -> bool {
+        self.A.threshold == commitments.threshold() && self.S.verify(commitments)
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut out: Vec<u8> = Vec::new();
 
@@ -183,7 +196,34 @@ impl Commune {
         }
     }
 
+    #[cfg(not(feature = "no-thread-rng"))]
     pub fn share(self) -> Share {
+        self.share_rng(&mut rand::thread_rng())
+    }
+
+    /// Shares the `Commune` as in `share`, sampling the share point
+    /// using the supplied random number generator.
+    pub fn share_rng<R: rand::RngCore + rand::CryptoRng>(self, rng: &mut R) -> Share {
+        self.deal(rng).0
+    }
+
+    /// Shares the `Commune` as in `share_rng`, and also returns Feldman
+    /// commitments to the polynomial the share lies on, which anyone can
+    /// check the share against with `Share::verify`. The polynomial is
+    /// derived from the `Commune`, so every share of the same `Commune`
+    /// comes with the same commitments.
+    #[cfg(feature = "vss")]
+    pub fn share_verifiable_rng<R: rand::RngCore + rand::CryptoRng>(
+        self,
+        rng: &mut R,
+    ) -> (Share, sharks::Commitments) {
+        let (share, polys) = self.deal(rng);
+        (share, polys.commitments())
+    }
+
+    // Shares the `Commune`, returning the share along with the
+    // evaluator of the polynomial it lies on.
+    fn deal<R: rand::RngCore + rand::CryptoRng>(self, rng: &mut R) -> (Share, sharks::Evaluator) {
         // H4κ = (A, M, R, T)
         let mut transcript = self
             .T
@@ -217,18 +257,17 @@ impl Commune {
         key.send_enc(&mut D, false);
 
         // Generate a random share
-        let mut K_vec: Vec<u8> = K.to_vec();
-        K_vec.extend(vec![0u8; 16]);
-        let polys = Sharks::from(self.A).dealer_rng(&K_vec, &mut L);
-        let S = polys.gen(&mut rand::thread_rng());
-        Share {
+        let polys = Sharks::from(self.A).dealer_rng(&Secret::from_bytes(&K), &mut L);
+        let S = polys.with_rng(rng);
+        let share = Share {
             A: self.A,
             S,
             C,
             D,
             J,
             T: (),
-        }
+        };
+        (share, polys)
     }
 
     pub fn get_message(&self) -> Vec<u8> {
@@ -250,8 +289,30 @@ impl Commune {
     }
 }
 
-#[allow(non_snake_case)]
+impl Randomized for Commune {
+    type Output = Share;
+
+    fn with_rng<R: rand::RngCore + rand::CryptoRng>(&self, rng: &mut R) -> Share {
+        self.clone().share_rng(rng)
+    }
+}
+
 pub fn recover<'a, T>(shares: T) -> Result<Commune, Box<dyn Error>>
+where
+    T: IntoIterator<Item = &'a Share>,
+    T::IntoIter: Iterator<Item = &'a Share>,
+{
+    recover_with_ctx(&mut InterpolationCtx::new(), shares)
+}
+
+/// Recovers the `Commune` as in `recover`, borrowing the interpolation
+/// scratch space from `ctx`, so that it can be reused across many
+/// recoveries.
+#[allow(non_snake_case)]
+pub fn recover_with_ctx<'a, T>(
+    ctx: &mut InterpolationCtx,
+    shares: T,
+) -> Result<Commune, Box<dyn Error>>
 where
     T: IntoIterator<Item = &'a Share>,
     T::IntoIter: Iterator<Item = &'a Share>,
@@ -259,8 +320,12 @@ where
     let mut shares = shares.into_iter().peekable();
     let s = &(*shares.peek().ok_or("no shares passed")?).clone();
     let shares: Vec<sharks::Share> = shares.cloned().map(|s| s.S).collect();
-    let key = Sharks::from(s.A).recover(&shares)?;
-    let K = key[..16].to_vec();
+    let key = Sharks::from(s.A).recover_with_ctx(ctx, &shares)?;
+    let K = key
+        .as_bytes()
+        .get(..16)
+        .ok_or("recovered key is too short")?
+        .to_vec();
 
     let mut key = Strobe::new(b"adss encrypt", SecParam::B128);
     key.key(&K, false);
@@ -286,6 +351,15 @@ where
     Ok(c)
 }
 
+// Checks that `Commune::share`, which implicitly uses `thread_rng`, is
+// absent with the `no-thread-rng` feature.
+///
+/// ```compile_fail
+/// adss_rs::Commune::new(2, vec![1; 32], vec![2; 32], None).share();
+/// ```
+#[cfg(feature = "no-thread-rng")]
+mod no_thread_rng {}
+
 #[cfg(test)]
 mod tests {
     use core::iter;
@@ -325,6 +399,7 @@ mod tests {
         }
     }
 
+    #[cfg(not(feature = "no-thread-rng"))]
     #[test]
     fn serialization_share() {
         let c = Commune {
@@ -340,6 +415,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_works_with_rng() {
+        use rand::SeedableRng;
+
+        let c = Commune {
+            A: AccessStructure { threshold: 5 },
+            M: vec![1, 2, 3, 4],
+            R: vec![5, 6, 7, 8],
+            T: None,
+        };
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let shares: Vec<Share> = iter::repeat_with(|| c.with_rng(&mut rng)).take(5).collect();
+
+        let recovered = recover(&shares).unwrap();
+
+        assert_eq!(c.M, recovered.M);
+    }
+
+    #[test]
+    fn it_works_with_ctx() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut ctx = InterpolationCtx::new();
+        for threshold in &[5, 2, 8] {
+            let c = Commune {
+                A: AccessStructure {
+                    threshold: *threshold,
+                },
+                M: vec![*threshold as u8; 4],
+                R: vec![5, 6, 7, 8],
+                T: None,
+            };
+            let shares: Vec<Share> = iter::repeat_with(|| c.with_rng(&mut rng))
+                .take(*threshold as usize)
+                .collect();
+
+            let recovered = recover_with_ctx(&mut ctx, &shares).unwrap();
+
+            assert_eq!(c.M, recovered.M);
+        }
+    }
+
+    #[cfg(not(feature = "no-thread-rng"))]
     #[test]
     fn it_works() {
         let c = Commune {
@@ -355,4 +475,31 @@ mod tests {
 
         assert_eq!(c.M, recovered.M);
     }
+
+    #[cfg(feature = "vss")]
+    #[test]
+    fn verifiable_shares() {
+        let c = Commune {
+            A: AccessStructure { threshold: 3 },
+            M: vec![1, 2, 3, 4],
+            R: vec![5, 6, 7, 8],
+            T: None,
+        };
+        let mut rng = rand::thread_rng();
+        let (share, commitments) = c.clone().share_verifiable_rng(&mut rng);
+        assert!(share.verify(&commitments));
+
+        // Every share of the `Commune` has the same commitments
+        let (other, same) = c.clone().share_verifiable_rng(&mut rng);
+        assert_eq!(commitments, same);
+        assert!(other.verify(&commitments));
+
+        // But not the shares of another `Commune`
+        let d = Commune {
+            M: vec![4, 3, 2, 1],
+            ..c
+        };
+        let (foreign, _) = d.share_verifiable_rng(&mut rng);
+        assert!(!foreign.verify(&commitments));
+    }
 }
@@ -108,14 +108,18 @@ fn benchmark_server(c: &mut Criterion) {
     c.bench_function("Server eval", |b| {
         b.iter(|| {
             let server = Server::new(&mds);
-            server.eval(&RistrettoPoint::random(&mut OsRng).compress(), 0, false);
+            server
+                .eval(&RistrettoPoint::random(&mut OsRng).compress(), 0, false)
+                .unwrap();
         })
     });
 
     c.bench_function("Server verifiable eval", |b| {
         b.iter(|| {
             let server = Server::new(&mds);
-            server.eval(&RistrettoPoint::random(&mut OsRng).compress(), 0, true);
+            server
+                .eval(&RistrettoPoint::random(&mut OsRng).compress(), 0, true)
+                .unwrap();
         })
     });
 }
@@ -136,7 +140,7 @@ fn benchmark_client(c: &mut Criterion) {
 
     c.bench_function("Client verify", |b| {
         let (blinded_point, _) = Client::blind(input.as_ref());
-        let eval = server.eval(&blinded_point, 0, true);
+        let eval = server.eval(&blinded_point, 0, true).unwrap();
         b.iter(|| {
             Client::verify(
                 &server.get_public_key(),
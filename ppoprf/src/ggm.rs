@@ -7,15 +7,14 @@ use std::fmt;
 
 use super::PPRF;
 use bitvec::prelude::*;
-use ring::{
-    hmac,
-    rand::{SecureRandom, SystemRandom},
-};
+use rand_core::{CryptoRng, RngCore};
+use ring::hmac;
 
 #[derive(Debug)]
-enum GGMError {
+pub(crate) enum GGMError {
     NoPrefixFound,
     AlreadyPunctured,
+    InvalidInputLength { expected: usize, found: usize },
 }
 
 #[derive(Clone, Eq, PartialEq)]
@@ -45,8 +44,8 @@ struct GGMPseudorandomGenerator {
 }
 
 impl GGMPseudorandomGenerator {
-    fn setup() -> Self {
-        let secret = sample_secret();
+    fn setup<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let secret = sample_secret(rng);
         let s_key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref());
         GGMPseudorandomGenerator { key: s_key }
     }
@@ -66,13 +65,13 @@ struct GGMPuncturableKey {
 
 // TODO: remove copies/clones
 impl GGMPuncturableKey {
-    fn new() -> Self {
-        let secret = sample_secret();
+    fn new<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let secret = sample_secret(rng);
         // Setup PRGs and initial tree
-        let prg0 = GGMPseudorandomGenerator::setup();
+        let prg0 = GGMPseudorandomGenerator::setup(rng);
         let mut out0 = vec![0u8; 32];
         prg0.eval(&secret, &mut out0);
-        let prg1 = GGMPseudorandomGenerator::setup();
+        let prg1 = GGMPseudorandomGenerator::setup(rng);
         let mut out1 = vec![0u8; 32];
         prg1.eval(&secret, &mut out1);
         GGMPuncturableKey {
@@ -124,20 +123,42 @@ pub struct GGM {
 }
 
 impl GGM {
+    // Sets up the PRF as in `PPRF::setup`, but samples the key from
+    // `rng`, so that a seeded `rng` always gives the same key.
+    pub fn setup_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        GGM {
+            inp_len: 1,
+            key: GGMPuncturableKey::new(rng),
+        }
+    }
+
     fn bit_eval(&self, bits: &BitVec, prg_inp: &[u8], output: &mut [u8]) {
         let mut eval = prg_inp.to_vec();
         for bit in bits {
-            let prg: &GGMPseudorandomGenerator;
-            if *bit {
-                prg = &self.key.prgs[1];
+            let prg = if *bit {
+                &self.key.prgs[1]
             } else {
-                prg = &self.key.prgs[0];
-            }
+                &self.key.prgs[0]
+            };
             prg.eval(&eval.clone(), &mut eval);
         }
         output.copy_from_slice(&eval);
     }
 
+    // Evaluates the PRF as in `PPRF::eval`, but returns an error rather
+    // than panicking if the input has been punctured or is not the
+    // input length.
+    pub(crate) fn try_eval(&self, input: &[u8], output: &mut [u8]) -> Result<(), GGMError> {
+        if input.len() != self.inp_len {
+            return Err(GGMError::InvalidInputLength {
+                expected: self.inp_len,
+                found: input.len(),
+            });
+        }
+        let mut input_bits = bvcast_u8_to_usize(&BitVec::<Lsb0, _>::from_slice(input).unwrap());
+        self.partial_eval(&mut input_bits, output)
+    }
+
     fn partial_eval(&self, input_bits: &mut BitVec, output: &mut [u8]) -> Result<(), GGMError> {
         let res = self.key.find_prefix(input_bits);
         if let Ok(pfx) = res {
@@ -152,22 +173,11 @@ impl GGM {
 
 impl PPRF for GGM {
     fn setup() -> Self {
-        GGM {
-            inp_len: 1,
-            key: GGMPuncturableKey::new(),
-        }
+        Self::setup_with_rng(&mut rand_core::OsRng)
     }
 
     fn eval(&self, input: &[u8], output: &mut [u8]) {
-        if input.len() != self.inp_len {
-            panic!(
-                "Input length ({}) does not match input param ({})",
-                input.len(),
-                self.inp_len
-            );
-        }
-        let mut input_bits = bvcast_u8_to_usize(&BitVec::<Lsb0, _>::from_slice(input).unwrap());
-        if let Err(e) = self.partial_eval(&mut input_bits, output) {
+        if let Err(e) = self.try_eval(input, output) {
             panic!("Error occurred for {:?}: {:?}", input, e);
         }
     }
@@ -218,12 +228,9 @@ impl PPRF for GGM {
     }
 }
 
-fn sample_secret() -> Vec<u8> {
-    let rng = SystemRandom::new();
+fn sample_secret<R: RngCore + CryptoRng>(rng: &mut R) -> Vec<u8> {
     let mut out = vec![0u8; 32];
-    if let Err(e) = rng.fill(&mut out) {
-        panic!("{}", e);
-    }
+    rng.fill_bytes(&mut out);
     out
 }
 
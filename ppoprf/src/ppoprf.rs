@@ -14,21 +14,176 @@
 extern crate rand;
 
 extern crate rand_core;
-use rand_core::RngCore;
+use rand_core::{CryptoRng, RngCore};
 use rand_core_ristretto::OsRng;
 
 use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
 use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use curve25519_dalek::scalar::Scalar;
 
+use ring::digest;
 use strobe_rng::StrobeRng;
 use strobe_rs::{SecParam, Strobe};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
 
-use crate::{ggm::GGM, PPRF};
+use std::fmt;
+
+use crate::{
+    ggm::{GGMError, GGM},
+    PPRF,
+};
+use randomized::Randomized;
 
 pub const COMPRESSED_POINT_LEN: usize = 32;
 pub const DIGEST_LEN: usize = 64;
 
+// Errors that can occur when the `Server` evaluates the PPOPRF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PPOPRFError {
+    // The metadata tag index is not one of the server's tags.
+    UnknownMetadata,
+    // The metadata tag has been punctured from the server secret key.
+    PuncturedMetadata,
+    // The client input is not a valid compressed Ristretto point.
+    InvalidPoint,
+    // The secret key for the metadata tag is degenerate, so no valid
+    // proof can be produced for a verifiable evaluation.
+    ProofGenerationFailed,
+    // The secret key for the metadata tag is degenerate, so no valid
+    // evaluation can be produced.
+    DegenerateKey,
+    // The metadata tag is not the length the punctured PRF takes.
+    InvalidMetadataLength { expected: usize, found: usize },
+    // The proof of a verifiable evaluation does not verify against the
+    // server's public key.
+    VerificationFailed,
+}
+
+impl fmt::Display for PPOPRFError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PPOPRFError::UnknownMetadata => write!(f, "Unknown metadata tag"),
+            PPOPRFError::PuncturedMetadata => write!(f, "Metadata tag has been punctured"),
+            PPOPRFError::InvalidPoint => write!(f, "Input is not a valid Ristretto point"),
+            PPOPRFError::ProofGenerationFailed => write!(f, "Failed to generate evaluation proof"),
+            PPOPRFError::DegenerateKey => write!(f, "Secret key for the metadata tag is zero"),
+            PPOPRFError::InvalidMetadataLength { expected, found } => write!(
+                f,
+                "Metadata tag is {} bytes long, but {} bytes were expected",
+                found, expected
+            ),
+            PPOPRFError::VerificationFailed => write!(f, "Evaluation proof failed to verify"),
+        }
+    }
+}
+
+impl std::error::Error for PPOPRFError {}
+
+// Maps an error evaluating the punctured PRF at a metadata tag.
+fn pprf_error(e: GGMError) -> PPOPRFError {
+    match e {
+        GGMError::InvalidInputLength { expected, found } => {
+            PPOPRFError::InvalidMetadataLength { expected, found }
+        }
+        GGMError::NoPrefixFound | GGMError::AlreadyPunctured => PPOPRFError::PuncturedMetadata,
+    }
+}
+
+// The hash function used by `Client::finalize_with` to map the
+// unblinded evaluation to the PPOPRF output. Deployments which select
+// different hashes produce unrelated outputs for the same input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalizationHash {
+    // STROBE-128 with the "ppoprf_finalize" label, as used by
+    // `Server::new` and `Client::finalize`.
+    Strobe,
+    // SHA-512.
+    Sha512,
+}
+
+impl FinalizationHash {
+    fn hash(&self, input: &[u8], out: &mut [u8]) {
+        match self {
+            FinalizationHash::Strobe => strobe_hash(input, "ppoprf_finalize", out),
+            FinalizationHash::Sha512 => {
+                out.copy_from_slice(digest::digest(&digest::SHA512, input).as_ref())
+            }
+        }
+    }
+}
+
+// The domain separation tag used by `HashToCurve::Sha512Xmd`.
+pub const HASH_TO_CURVE_DST: &[u8] = b"ppoprf-ristretto255_XMD:SHA-512_R255MAP_RO_";
+
+// The map from a client input to the curve point that is blinded and
+// sent to the server. `Server::eval` never hashes inputs itself: it
+// evaluates whichever point it is sent, so outputs only match across
+// implementations which hash inputs with the same variant.
+//
+// Both variants expand the input to 64 uniform bytes, and map them to
+// a point with the ristretto255 one-way map (the sum of two Elligator2
+// maps, as `RistrettoPoint::from_uniform_bytes`). They differ only in
+// the expansion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashToCurve {
+    // STROBE-128 with the "ppoprf_derive_client_input" label, keyed
+    // with the input, as used by `Client::blind`.
+    Strobe,
+    // `expand_message_xmd` with SHA-512 from RFC 9380, section 5.3.1,
+    // with `HASH_TO_CURVE_DST`. This is the `hash_to_ristretto255`
+    // suite of RFC 9380, appendix B, and is the variant to use when
+    // interoperating with other implementations.
+    Sha512Xmd,
+}
+
+impl HashToCurve {
+    // Maps `input` to a curve point.
+    pub fn hash_to_curve(&self, input: &[u8]) -> RistrettoPoint {
+        let mut uniform = [0u8; 64];
+        match self {
+            HashToCurve::Strobe => strobe_hash(input, "ppoprf_derive_client_input", &mut uniform),
+            HashToCurve::Sha512Xmd => expand_message_xmd(input, HASH_TO_CURVE_DST, &mut uniform),
+        }
+        RistrettoPoint::from_uniform_bytes(&uniform)
+    }
+}
+
+// `expand_message_xmd` from RFC 9380, section 5.3.1, with SHA-512.
+// `out` must be at most 255 * 64 bytes, and `dst` at most 255 bytes.
+fn expand_message_xmd(msg: &[u8], dst: &[u8], out: &mut [u8]) {
+    const B_IN_BYTES: usize = 64;
+    const S_IN_BYTES: usize = 128;
+    assert!(out.len() <= 255 * B_IN_BYTES && dst.len() <= 255);
+    let dst_len = [dst.len() as u8];
+
+    let mut ctx = digest::Context::new(&digest::SHA512);
+    ctx.update(&[0u8; S_IN_BYTES]);
+    ctx.update(msg);
+    ctx.update(&(out.len() as u16).to_be_bytes());
+    ctx.update(&[0]);
+    ctx.update(dst);
+    ctx.update(&dst_len);
+    let b_0 = ctx.finish();
+
+    let mut b_i = [0u8; B_IN_BYTES];
+    for (i, chunk) in out.chunks_mut(B_IN_BYTES).enumerate() {
+        let mut ctx = digest::Context::new(&digest::SHA512);
+        let xored: Vec<u8> = b_0.as_ref().iter().zip(&b_i).map(|(a, b)| a ^ b).collect();
+        ctx.update(&xored);
+        ctx.update(&[i as u8 + 1]);
+        ctx.update(dst);
+        ctx.update(&dst_len);
+        b_i.copy_from_slice(ctx.finish().as_ref());
+        chunk.copy_from_slice(&b_i[..chunk.len()]);
+    }
+}
+
+// Length of a serialized `ProofDLEQ`, which is the challenge and
+// response scalars.
+pub const PROOF_LEN: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProofDLEQ {
     c: Scalar,
     s: Scalar,
@@ -42,7 +197,19 @@ impl ProofDLEQ {
     ) -> Self {
         let mut csprng = OsRng;
         let t = Scalar::random(&mut csprng);
+        Self::new_with_nonce(key, public_value, p, q, &t)
+    }
 
+    // Creates the proof using the nonce `t`, rather than sampling it.
+    // Revealing `t`, or using it for two proofs under the same `key`,
+    // reveals the `key`.
+    fn new_with_nonce(
+        key: &Scalar,
+        public_value: &RistrettoPoint,
+        p: &RistrettoPoint,
+        q: &RistrettoPoint,
+        t: &Scalar,
+    ) -> Self {
         let tg = t * RISTRETTO_BASEPOINT_POINT;
         let tp = t * p;
         let chl = ProofDLEQ::hash(&[&RISTRETTO_BASEPOINT_POINT, public_value, p, q, &tg, &tp]);
@@ -62,6 +229,27 @@ impl ProofDLEQ {
         c_prime == self.c
     }
 
+    pub fn to_bytes(&self) -> [u8; PROOF_LEN] {
+        let mut out = [0u8; PROOF_LEN];
+        out[..32].copy_from_slice(self.c.as_bytes());
+        out[32..].copy_from_slice(self.s.as_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != PROOF_LEN {
+            return None;
+        }
+        let mut c_bytes = [0u8; 32];
+        c_bytes.copy_from_slice(&bytes[..32]);
+        let mut s_bytes = [0u8; 32];
+        s_bytes.copy_from_slice(&bytes[32..]);
+        Some(Self {
+            c: Scalar::from_canonical_bytes(c_bytes)?,
+            s: Scalar::from_canonical_bytes(s_bytes)?,
+        })
+    }
+
     fn hash(elements: &[&RistrettoPoint]) -> Scalar {
         if elements.len() != 6 {
             panic!("Incorrect number of points sent: {}", elements.len());
@@ -81,71 +269,319 @@ impl ProofDLEQ {
 pub type ServerPublicKey = Vec<RistrettoPoint>;
 
 // The wrapper for PPOPRF evaluations (similar to standard OPRFs)
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Evaluation {
     output: CompressedRistretto,
     proof: Option<ProofDLEQ>,
 }
 
+impl Evaluation {
+    // The evaluated (still blinded) point, as a compressed Ristretto
+    // point. This is all that a client which doesn't verify the
+    // evaluation needs to send or store.
+    pub fn output(&self) -> &[u8; COMPRESSED_POINT_LEN] {
+        self.output.as_bytes()
+    }
+
+    // The proof that the evaluation is correct, if it was requested to
+    // be verifiable.
+    pub fn proof(&self) -> Option<&ProofDLEQ> {
+        self.proof.as_ref()
+    }
+
+    // Serializes the output, followed by the proof if there is one.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(COMPRESSED_POINT_LEN + PROOF_LEN);
+        out.extend(self.output());
+        if let Some(proof) = &self.proof {
+            out.extend(proof.to_bytes());
+        }
+        out
+    }
+
+    // Parses an evaluation serialized with `to_bytes`. An output on its
+    // own gives an evaluation without a proof.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let proof = match bytes.len() {
+            COMPRESSED_POINT_LEN => None,
+            len if len == COMPRESSED_POINT_LEN + PROOF_LEN => {
+                Some(ProofDLEQ::from_bytes(&bytes[COMPRESSED_POINT_LEN..])?)
+            }
+            _ => return None,
+        };
+        let output = CompressedRistretto::from_slice(&bytes[..COMPRESSED_POINT_LEN]);
+        output.decompress()?;
+        Some(Self { output, proof })
+    }
+}
+
+// Compares the outputs of two evaluations in constant time, so that
+// comparing an output against a secret expected value doesn't leak how
+// much of it matched. This should be used rather than `==` whenever the
+// comparison gates an authentication decision, as the derived
+// `PartialEq` makes no timing guarantees. The proofs are not compared,
+// as they are randomized, and so differ between evaluations of the same
+// input.
+impl ConstantTimeEq for Evaluation {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.output.ct_eq(&other.output)
+    }
+}
+
 // The `Server` runs the server-side component of the PPOPRF protocol.
 #[derive(Clone)]
 pub struct Server {
     oprf_key: Scalar,
     public_key: ServerPublicKey,
     mds: Vec<Vec<u8>>,
+    max_mds: usize,
     pprf: GGM,
+    finalization_hash: FinalizationHash,
 }
 impl Server {
     pub fn new(mds: &[Vec<u8>]) -> Self {
-        let mut csprng = OsRng;
-        let oprf_key = Scalar::random(&mut csprng);
-        let mut public_key = Vec::with_capacity(mds.len() + 1);
+        Self::new_with_opts(mds, FinalizationHash::Strobe)
+    }
+
+    // Creates a `Server` as `new` does, but samples all of its secret
+    // key material, the OPRF key and the GGM key, from `rng` rather than
+    // the operating system. Servers created from identically seeded
+    // generators have the same keys, and so give the same outputs,
+    // which is useful for tests and for deriving a server's keys from a
+    // seed. The seed is then as sensitive as the keys themselves.
+    pub fn new_with_rng<R: RngCore + CryptoRng>(mds: &[Vec<u8>], rng: &mut R) -> Self {
+        Self::new_with_capacity(mds, mds.len().max(1), FinalizationHash::Strobe, rng)
+    }
+
+    // Creates a `Server` whose outputs are finalized by clients using
+    // `finalization_hash`.
+    pub fn new_with_opts(mds: &[Vec<u8>], finalization_hash: FinalizationHash) -> Self {
+        Self::new_with_capacity(
+            mds,
+            mds.len().max(1),
+            finalization_hash,
+            &mut rand_core::OsRng,
+        )
+    }
+
+    // Creates a `Server` which holds at most `max_mds` metadata tags at
+    // once, starting with `mds`. The tag table and public key are
+    // allocated for `max_mds` tags up front, and `rotate` never grows
+    // them: once the server holds `max_mds` tags, each new tag evicts
+    // the oldest one. A server created with `new` has a capacity of
+    // its initial tags.
+    //
+    // Panics if `max_mds` is zero, or smaller than the number of tags
+    // in `mds`.
+    pub fn with_capacity(mds: &[Vec<u8>], max_mds: usize) -> Self {
+        assert!(
+            max_mds > 0 && mds.len() <= max_mds,
+            "Capacity ({}) must be non-zero and hold all {} metadata tags",
+            max_mds,
+            mds.len()
+        );
+        Self::new_with_capacity(
+            mds,
+            max_mds,
+            FinalizationHash::Strobe,
+            &mut rand_core::OsRng,
+        )
+    }
+
+    fn new_with_capacity<R: RngCore + CryptoRng>(
+        mds: &[Vec<u8>],
+        max_mds: usize,
+        finalization_hash: FinalizationHash,
+        rng: &mut R,
+    ) -> Self {
+        let mut key_bytes = [0u8; 64];
+        rng.fill_bytes(&mut key_bytes);
+        let oprf_key = Scalar::from_bytes_mod_order_wide(&key_bytes);
+        let mut public_key = Vec::with_capacity(max_mds + 1);
         public_key.push(oprf_key * RISTRETTO_BASEPOINT_POINT);
-        let pprf = GGM::setup();
+        let pprf = GGM::setup_with_rng(rng);
         for md in mds {
             let mut tag = [0u8; 32];
             pprf.eval(md, &mut tag);
             let ts = Scalar::from_bytes_mod_order(tag);
             public_key.push(ts * RISTRETTO_BASEPOINT_POINT);
         }
+        let mut tags = Vec::with_capacity(max_mds);
+        tags.extend_from_slice(mds);
         Self {
             oprf_key,
             public_key,
-            mds: mds.to_vec(),
+            mds: tags,
+            max_mds,
             pprf,
+            finalization_hash,
         }
     }
 
-    pub fn eval(&self, p: &CompressedRistretto, md_idx: usize, verifiable: bool) -> Evaluation {
-        let point = p.decompress().unwrap();
+    // Evaluates the PPOPRF at the blinded point `p`, under the metadata
+    // tag at `md_idx`. The input is not hashed here: clients map their
+    // inputs to points with a `HashToCurve` variant, `Strobe` by
+    // default, before blinding them. This only decompresses `p`, and
+    // fails with `InvalidPoint` if it isn't a valid encoding; see
+    // `eval_point` for evaluating a point that is already decompressed.
+    pub fn eval(
+        &self,
+        p: &CompressedRistretto,
+        md_idx: usize,
+        verifiable: bool,
+    ) -> Result<Evaluation, PPOPRFError> {
+        let point = p.decompress().ok_or(PPOPRFError::InvalidPoint)?;
+        self.eval_point(&point, md_idx, verifiable)
+    }
+
+    // Evaluates the same blinded input for each of the metadata tags
+    // indicated by `md_indices`. The input point is only decompressed
+    // once, and is shared across all evaluations.
+    pub fn eval_multi(
+        &self,
+        p: &CompressedRistretto,
+        md_indices: &[usize],
+        verifiable: bool,
+    ) -> Result<Vec<Evaluation>, PPOPRFError> {
+        let point = p.decompress().ok_or(PPOPRFError::InvalidPoint)?;
+        md_indices
+            .iter()
+            .map(|&md_idx| self.eval_point(&point, md_idx, verifiable))
+            .collect()
+    }
+
+    // Evaluates as in `eval` with `verifiable` set, but uses `nonce` for
+    // the proof rather than sampling it, so that the proof is
+    // reproducible. This is only intended for generating test vectors,
+    // so it is only built for tests or with the `test-vectors` feature:
+    // reusing a nonce for two different evaluations, or using a nonce
+    // that isn't secret, reveals the server's secret key.
+    #[cfg(any(test, feature = "test-vectors"))]
+    pub fn eval_with_nonce(
+        &self,
+        p: &CompressedRistretto,
+        md_idx: usize,
+        nonce: &[u8; 32],
+    ) -> Result<Evaluation, PPOPRFError> {
+        let point = p.decompress().ok_or(PPOPRFError::InvalidPoint)?;
+        let nonce = Scalar::from_bytes_mod_order(*nonce);
+        self.eval_point_with_nonce(&point, md_idx, Some(&nonce))
+    }
+
+    // Returns the secret key for the metadata tag `md_idx`.
+    fn tagged_key(&self, md_idx: usize) -> Result<Scalar, PPOPRFError> {
         if md_idx >= self.mds.len() {
-            panic!("Specified tag index is out of bounds for stored tags, indicated index {} is not in [0..{})", md_idx, self.mds.len());
+            return Err(PPOPRFError::UnknownMetadata);
         }
         let mut tag = [0u8; 32];
-        self.pprf.eval(&self.mds[md_idx], &mut tag);
+        self.pprf
+            .try_eval(&self.mds[md_idx], &mut tag)
+            .map_err(pprf_error)?;
         let ts = Scalar::from_bytes_mod_order(tag);
         let tagged_key = self.oprf_key + ts;
+        if tagged_key == Scalar::zero() {
+            return Err(PPOPRFError::DegenerateKey);
+        }
+        Ok(tagged_key)
+    }
+
+    // Evaluates the PPOPRF at the blinded group element `point`, as
+    // `eval` does for its compressed encoding. Neither function hashes
+    // its input to the curve: both take the point that the client
+    // obtained by hashing its raw input with a `HashToCurve` and
+    // blinding it. A client evaluating the same input under several
+    // servers, as in a distributed OPRF, can hash and blind once and
+    // send the same point to each of them. Servers that receive points
+    // in their own format can skip the compressed encoding, but must
+    // only pass points that they have validated.
+    pub fn eval_point(
+        &self,
+        point: &RistrettoPoint,
+        md_idx: usize,
+        verifiable: bool,
+    ) -> Result<Evaluation, PPOPRFError> {
+        let nonce = if verifiable {
+            Some(Scalar::random(&mut OsRng))
+        } else {
+            None
+        };
+        self.eval_point_with_nonce(point, md_idx, nonce.as_ref())
+    }
+
+    // Evaluates as `eval_point` does, proving the evaluation with
+    // `nonce` if there is one.
+    fn eval_point_with_nonce(
+        &self,
+        point: &RistrettoPoint,
+        md_idx: usize,
+        nonce: Option<&Scalar>,
+    ) -> Result<Evaluation, PPOPRFError> {
+        let tagged_key = self.tagged_key(md_idx).map_err(|e| match e {
+            PPOPRFError::DegenerateKey if nonce.is_some() => PPOPRFError::ProofGenerationFailed,
+            e => e,
+        })?;
         let exponent = tagged_key.invert();
         let eval_point = exponent * point;
-        let mut proof = None;
-        if verifiable {
+        let proof = nonce.map(|t| {
             let public_value = self.public_key[0] + self.public_key[md_idx + 1];
-            proof = Some(ProofDLEQ::new(
-                &tagged_key,
-                &public_value,
-                &eval_point,
-                &point,
-            ));
-        }
-        Evaluation {
+            ProofDLEQ::new_with_nonce(&tagged_key, &public_value, &eval_point, point, t)
+        });
+        Ok(Evaluation {
             output: eval_point.compress(),
             proof,
-        }
+        })
+    }
+
+    // Replaces the secret OPRF key with a fresh one sampled from `rng`,
+    // keeping the metadata tags (and any punctured tags) unchanged.
+    // All prior evaluations are invalidated, and proofs from the
+    // rekeyed server only verify against the new public key, which must
+    // be redistributed to clients.
+    pub fn rekey<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
+        let mut key_bytes = [0u8; 64];
+        rng.fill_bytes(&mut key_bytes);
+        self.oprf_key = Scalar::from_bytes_mod_order_wide(&key_bytes);
+        self.public_key[0] = self.oprf_key * RISTRETTO_BASEPOINT_POINT;
     }
 
     pub fn puncture(&mut self, md: &[u8]) {
         self.pprf.puncture(md);
     }
 
+    // Adds the metadata tag `md`, as the start of a new epoch. If the
+    // server already holds its maximum number of tags, the oldest one
+    // is removed and punctured, so that it can never be evaluated
+    // again, and is returned. Adding a tag the server already holds
+    // does nothing, while adding one that has been punctured fails.
+    //
+    // Metadata indices are positions in `get_valid_metadata_tags`, so
+    // an eviction shifts the index of every remaining tag down by one,
+    // and clients need the new public key. The table doesn't grow, but
+    // the punctured key does, by at most one set of GGM prefixes per
+    // eviction; its size is bounded by the 256 possible tags.
+    pub fn rotate(&mut self, md: Vec<u8>) -> Result<Option<Vec<u8>>, PPOPRFError> {
+        if self.mds.contains(&md) {
+            return Ok(None);
+        }
+        let mut tag = [0u8; 32];
+        self.pprf.try_eval(&md, &mut tag).map_err(pprf_error)?;
+        let mut evicted = None;
+        if self.mds.len() == self.max_mds {
+            let oldest = self.mds.remove(0);
+            self.public_key.remove(1);
+            self.pprf.puncture(&oldest);
+            evicted = Some(oldest);
+        }
+        let ts = Scalar::from_bytes_mod_order(tag);
+        self.public_key.push(ts * RISTRETTO_BASEPOINT_POINT);
+        self.mds.push(md);
+        Ok(evicted)
+    }
+
+    pub fn get_finalization_hash(&self) -> FinalizationHash {
+        self.finalization_hash
+    }
+
     pub fn get_public_key(&self) -> ServerPublicKey {
         self.public_key.clone()
     }
@@ -155,17 +591,116 @@ impl Server {
     }
 }
 
+pub const BLINDING_TOKEN_LEN: usize = COMPRESSED_POINT_LEN + 32;
+
+// A `BlindingToken` holds the blinded point sent to the `Server`,
+// along with the blinding factor needed to unblind its evaluation. It
+// can be serialized so that a client can persist it, and retry a
+// failed server request with the same blinded point.
+//
+// A token MUST only be used for the input it was created from.
+// Reusing the same blinding factor for *different* inputs allows the
+// server to link the requests, and learn relations between the
+// inputs.
+//
+// The blinding factor is kept private, left out of the `Debug`
+// output, and zeroized when the token is dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BlindingToken {
+    pub blinded_point: CompressedRistretto,
+    r: Scalar,
+}
+impl BlindingToken {
+    pub fn new(input: &[u8]) -> Self {
+        Self::from(Client::blind(input))
+    }
+
+    pub fn unblind(&self, p: &CompressedRistretto) -> CompressedRistretto {
+        Client::unblind(p, &self.r)
+    }
+
+    pub fn to_bytes(&self) -> [u8; BLINDING_TOKEN_LEN] {
+        let mut out = [0u8; BLINDING_TOKEN_LEN];
+        out[..COMPRESSED_POINT_LEN].copy_from_slice(self.blinded_point.as_bytes());
+        out[COMPRESSED_POINT_LEN..].copy_from_slice(self.r.as_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != BLINDING_TOKEN_LEN {
+            return None;
+        }
+        let blinded_point = CompressedRistretto::from_slice(&bytes[..COMPRESSED_POINT_LEN]);
+        blinded_point.decompress()?;
+        let mut r_bytes = [0u8; 32];
+        r_bytes.copy_from_slice(&bytes[COMPRESSED_POINT_LEN..]);
+        let r = Scalar::from_canonical_bytes(r_bytes)?;
+        Some(Self { blinded_point, r })
+    }
+}
+impl From<(CompressedRistretto, Scalar)> for BlindingToken {
+    fn from((blinded_point, r): (CompressedRistretto, Scalar)) -> Self {
+        Self { blinded_point, r }
+    }
+}
+impl fmt::Debug for BlindingToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlindingToken")
+            .field("blinded_point", &self.blinded_point)
+            .field("r", &"<redacted>")
+            .finish()
+    }
+}
+impl Drop for BlindingToken {
+    fn drop(&mut self) {
+        self.r.zeroize();
+    }
+}
+
+// A pending blinding of a client `input`, created by
+// `Client::blinding`.
+pub struct Blinding<'a> {
+    input: &'a [u8],
+    hash: HashToCurve,
+}
+
+impl<'a> Randomized for Blinding<'a> {
+    type Output = (CompressedRistretto, Scalar);
+
+    fn with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Self::Output {
+        let point = self.hash.hash_to_curve(self.input);
+        let mut r_bytes = [0u8; 64];
+        rng.fill_bytes(&mut r_bytes);
+        let r = Scalar::from_bytes_mod_order_wide(&r_bytes);
+        ((r * point).compress(), r)
+    }
+}
+
 // The `Client` struct is essentially a collection of static functions
 // for computing client-side operations in the PPOPRF protocol.
 pub struct Client {}
 impl Client {
     pub fn blind(input: &[u8]) -> (CompressedRistretto, Scalar) {
-        let mut hashed_input = [0u8; 64];
-        strobe_hash(input, "ppoprf_derive_client_input", &mut hashed_input);
-        let point = RistrettoPoint::from_uniform_bytes(&hashed_input);
-        let mut csprng = OsRng;
-        let r = Scalar::random(&mut csprng);
-        ((r * point).compress(), r)
+        Self::blinding(input).with_rng(&mut rand_core::OsRng)
+    }
+
+    // Returns a `Blinding` of `input`, which samples the blinding
+    // factor from any random number generator via the `Randomized`
+    // trait.
+    pub fn blinding(input: &[u8]) -> Blinding<'_> {
+        Self::blinding_with(HashToCurve::Strobe, input)
+    }
+
+    // Blinds `input` as `blind` does, mapping it to a point with
+    // `hash`.
+    pub fn blind_with(hash: HashToCurve, input: &[u8]) -> (CompressedRistretto, Scalar) {
+        Self::blinding_with(hash, input).with_rng(&mut rand_core::OsRng)
+    }
+
+    // Returns a `Blinding` of `input` as `blinding` does, mapping it to
+    // a point with `hash`.
+    pub fn blinding_with(hash: HashToCurve, input: &[u8]) -> Blinding<'_> {
+        Blinding { input, hash }
     }
 
     pub fn verify(
@@ -189,6 +724,18 @@ impl Client {
     }
 
     pub fn finalize(input: &[u8], md: &[u8], unblinded: &CompressedRistretto, out: &mut [u8]) {
+        Client::finalize_with(FinalizationHash::Strobe, input, md, unblinded, out)
+    }
+
+    // Finalizes the output as in `finalize`, using `hash`, which must
+    // match the `FinalizationHash` of the server.
+    pub fn finalize_with(
+        hash: FinalizationHash,
+        input: &[u8],
+        md: &[u8],
+        unblinded: &CompressedRistretto,
+        out: &mut [u8],
+    ) {
         if out.len() != 32 {
             panic!("Wrong output length!!: {:?}", out.len());
         }
@@ -198,11 +745,38 @@ impl Client {
         hash_input.extend(md);
         hash_input.extend(&point_bytes);
         let mut untruncated = vec![0u8; 64];
-        strobe_hash(&hash_input, "ppoprf_finalize", &mut untruncated);
+        hash.hash(&hash_input, &mut untruncated);
         out.copy_from_slice(&untruncated[..32]);
     }
 }
 
+// The transcript of a verifiable evaluation of the blinded input
+// `blinded_point` under the metadata tag `md_idx`. This is what a client
+// needs to check, or to show others, that the server evaluated the
+// PPOPRF with its published key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvaluationProof {
+    pub blinded_point: CompressedRistretto,
+    pub evaluation: Evaluation,
+    pub md_idx: usize,
+}
+
+impl EvaluationProof {
+    // Checks the proof against the server's `public_key`. Returns false
+    // for evaluations without a proof, unknown metadata tags, and
+    // invalid points, rather than panicking.
+    pub fn verify(&self, public_key: &[RistrettoPoint]) -> bool {
+        let input = match self.blinded_point.decompress() {
+            Some(input) => input,
+            None => return false,
+        };
+        self.evaluation.proof.is_some()
+            && self.evaluation.output.decompress().is_some()
+            && self.md_idx + 1 < public_key.len()
+            && Client::verify(public_key, &input, &self.evaluation, self.md_idx)
+    }
+}
+
 // The `ènd_to_end_evaluation` helper function for performs a full
 // protocol evaluation for a given `Server`.
 pub fn end_to_end_evaluation(
@@ -211,21 +785,56 @@ pub fn end_to_end_evaluation(
     md_idx: usize,
     verify: bool,
     out: &mut [u8],
-) {
+) -> Result<(), PPOPRFError> {
+    if verify {
+        end_to_end_evaluation_with_proof(server, input, md_idx, out)?;
+        return Ok(());
+    }
     let (blinded_point, r) = Client::blind(input);
-    let evaluated = server.eval(&blinded_point, md_idx, verify);
-    if verify
-        && !Client::verify(
-            &server.public_key,
-            &blinded_point.decompress().unwrap(),
-            &evaluated,
-            md_idx,
-        )
-    {
-        panic!("Verification failed")
+    let evaluated = server.eval(&blinded_point, md_idx, false)?;
+    finalize_evaluation(server, input, md_idx, &evaluated, &r, out);
+    Ok(())
+}
+
+// As `end_to_end_evaluation` with verification, but also returns the
+// proof of the evaluation, so that it can be kept and checked again
+// later, or by others.
+pub fn end_to_end_evaluation_with_proof(
+    server: &Server,
+    input: &[u8],
+    md_idx: usize,
+    out: &mut [u8],
+) -> Result<EvaluationProof, PPOPRFError> {
+    let (blinded_point, r) = Client::blind(input);
+    let evaluation = server.eval(&blinded_point, md_idx, true)?;
+    let proof = EvaluationProof {
+        blinded_point,
+        evaluation,
+        md_idx,
+    };
+    if !proof.verify(&server.public_key) {
+        return Err(PPOPRFError::VerificationFailed);
     }
-    let unblinded = Client::unblind(&evaluated.output, &r);
-    Client::finalize(input, &server.mds[md_idx], &unblinded, out);
+    finalize_evaluation(server, input, md_idx, &proof.evaluation, &r, out);
+    Ok(proof)
+}
+
+fn finalize_evaluation(
+    server: &Server,
+    input: &[u8],
+    md_idx: usize,
+    evaluated: &Evaluation,
+    r: &Scalar,
+    out: &mut [u8],
+) {
+    let unblinded = Client::unblind(&evaluated.output, r);
+    Client::finalize_with(
+        server.finalization_hash,
+        input,
+        &server.mds[md_idx],
+        &unblinded,
+        out,
+    );
 }
 
 fn strobe_hash(input: &[u8], label: &str, out: &mut [u8]) {
@@ -252,16 +861,18 @@ mod tests {
         md_idx: usize,
     ) -> (CompressedRistretto, CompressedRistretto) {
         let (blinded_point, r) = Client::blind(c_input);
-        let evaluated = server.eval(&blinded_point, md_idx, false);
+        let evaluated = server.eval(&blinded_point, md_idx, false).unwrap();
         let unblinded = Client::unblind(&evaluated.output, &r);
 
         let mut chk_inp = [0u8; 64];
         strobe_hash(c_input, "ppoprf_derive_client_input", &mut chk_inp);
-        let chk_eval = server.eval(
-            &RistrettoPoint::from_uniform_bytes(&chk_inp).compress(),
-            md_idx,
-            false,
-        );
+        let chk_eval = server
+            .eval(
+                &RistrettoPoint::from_uniform_bytes(&chk_inp).compress(),
+                md_idx,
+                false,
+            )
+            .unwrap();
         (unblinded, chk_eval.output)
     }
 
@@ -271,7 +882,7 @@ mod tests {
         md_idx: usize,
     ) -> (CompressedRistretto, CompressedRistretto) {
         let (blinded_point, r) = Client::blind(c_input);
-        let evaluated = server.eval(&blinded_point, md_idx, true);
+        let evaluated = server.eval(&blinded_point, md_idx, true).unwrap();
         if !Client::verify(
             &server.public_key,
             &blinded_point.decompress().unwrap(),
@@ -284,11 +895,13 @@ mod tests {
 
         let mut chk_inp = [0u8; 64];
         strobe_hash(c_input, "ppoprf_derive_client_input", &mut chk_inp);
-        let chk_eval = server.eval(
-            &RistrettoPoint::from_uniform_bytes(&chk_inp).compress(),
-            md_idx,
-            false,
-        );
+        let chk_eval = server
+            .eval(
+                &RistrettoPoint::from_uniform_bytes(&chk_inp).compress(),
+                md_idx,
+                false,
+            )
+            .unwrap();
         (unblinded, chk_eval.output)
     }
 
@@ -337,7 +950,7 @@ mod tests {
 
     #[test]
     fn end_to_end_no_verify_multi_tag() {
-        let epochs = vec!["a", "e", "i", "o", "u"];
+        let epochs = ["a", "e", "i", "o", "u"];
         let mds: Vec<Vec<u8>> = epochs.iter().map(|t| t.as_bytes().to_vec()).collect();
         end_to_end_no_verify(&mds, 0);
         end_to_end_no_verify(&mds, 1);
@@ -348,7 +961,7 @@ mod tests {
 
     #[test]
     fn end_to_end_verify_multi_tag() {
-        let epochs = vec!["a", "e", "i", "o", "u"];
+        let epochs = ["a", "e", "i", "o", "u"];
         let mds: Vec<Vec<u8>> = epochs.iter().map(|t| t.as_bytes().to_vec()).collect();
         end_to_end_verify(&mds, 0);
         end_to_end_verify(&mds, 1);
@@ -358,7 +971,461 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "NoPrefixFound")]
+    fn eval_multi_matches_eval() {
+        let epochs = ["a", "e", "i", "o", "u"];
+        let mds: Vec<Vec<u8>> = epochs.iter().map(|t| t.as_bytes().to_vec()).collect();
+        let server = Server::new(&mds);
+        let (blinded_point, _) = Client::blind(b"some_test_input");
+        let md_indices = [4, 0, 2];
+        let evals = server
+            .eval_multi(&blinded_point, &md_indices, true)
+            .unwrap();
+        assert_eq!(evals.len(), md_indices.len());
+        for (eval, &md_idx) in evals.iter().zip(md_indices.iter()) {
+            let chk_eval = server.eval(&blinded_point, md_idx, false).unwrap();
+            assert_eq!(eval.output, chk_eval.output);
+            assert!(Client::verify(
+                &server.public_key,
+                &blinded_point.decompress().unwrap(),
+                eval,
+                md_idx,
+            ));
+        }
+    }
+
+    #[test]
+    fn eval_point_distributed() {
+        let mds = vec![b"t".to_vec()];
+        let servers: Vec<Server> = (0..3).map(|_| Server::new(&mds)).collect();
+        let input = b"some_test_input";
+
+        // Hash and blind once, then evaluate under every server
+        let point = HashToCurve::Strobe.hash_to_curve(input);
+        let r = Scalar::random(&mut OsRng);
+        let blinded = r * point;
+        for server in &servers {
+            let eval = server.eval_point(&blinded, 0, true).unwrap();
+            assert_eq!(
+                eval.output,
+                server.eval(&blinded.compress(), 0, false).unwrap().output
+            );
+            assert!(Client::verify(&server.public_key, &blinded, &eval, 0));
+
+            // Unblinds to the same output as a separate blinding
+            let (unblinded, chk_eval) = end_to_end_eval_check(server, input, 0);
+            assert_eq!(chk_eval, unblinded);
+            assert_eq!(Client::unblind(&eval.output, &r), unblinded);
+        }
+        assert!(servers[0].eval_point(&blinded, 1, false).is_err());
+    }
+
+    #[test]
+    fn evaluation_ct_eq() {
+        let mds = vec![b"t".to_vec()];
+        let server = Server::new(&mds);
+        let (blinded_point, _) = Client::blind(b"some_test_input");
+        let eval = server.eval(&blinded_point, 0, true).unwrap();
+
+        // Evaluations of the same input have different proofs, but equal
+        // outputs
+        let again = server.eval(&blinded_point, 0, true).unwrap();
+        assert_ne!(again.proof(), eval.proof());
+        assert!(bool::from(eval.ct_eq(&again)));
+        assert!(bool::from(eval.ct_eq(&eval)));
+
+        let (other_point, _) = Client::blind(b"other_test_input");
+        let other = server.eval(&other_point, 0, true).unwrap();
+        assert!(!bool::from(eval.ct_eq(&other)));
+        let unverifiable = server.eval(&blinded_point, 0, false).unwrap();
+        assert!(bool::from(eval.ct_eq(&unverifiable)));
+    }
+
+    #[test]
+    fn eval_with_nonce_reproducible() {
+        let mds = vec![b"t".to_vec()];
+        let server = Server::new(&mds);
+        let (blinded_point, _) = Client::blind(b"some_test_input");
+        let nonce = [7u8; 32];
+
+        let eval = server.eval_with_nonce(&blinded_point, 0, &nonce).unwrap();
+        assert_eq!(
+            server.eval_with_nonce(&blinded_point, 0, &nonce),
+            Ok(eval.clone())
+        );
+        assert_eq!(
+            eval.output,
+            server.eval(&blinded_point, 0, true).unwrap().output
+        );
+        assert!(Client::verify(
+            &server.public_key,
+            &blinded_point.decompress().unwrap(),
+            &eval,
+            0,
+        ));
+
+        let other = server
+            .eval_with_nonce(&blinded_point, 0, &[8u8; 32])
+            .unwrap();
+        assert_ne!(other.proof(), eval.proof());
+        assert_eq!(
+            server.eval_with_nonce(&blinded_point, 1, &nonce),
+            Err(PPOPRFError::UnknownMetadata)
+        );
+    }
+
+    #[test]
+    fn evaluation_accessors() {
+        let mds = vec![b"t".to_vec()];
+        let server = Server::new(&mds);
+        let (blinded_point, _) = Client::blind(b"some_test_input");
+
+        let eval = server.eval(&blinded_point, 0, false).unwrap();
+        assert!(eval.proof().is_none());
+        assert_eq!(eval.output(), eval.output.as_bytes());
+        assert_eq!(eval.to_bytes(), eval.output().to_vec());
+        assert_eq!(Evaluation::from_bytes(&eval.to_bytes()), Some(eval));
+
+        let eval = server.eval(&blinded_point, 0, true).unwrap();
+        let proof = eval.proof().unwrap();
+        assert_eq!(
+            ProofDLEQ::from_bytes(&proof.to_bytes()).as_ref(),
+            Some(proof)
+        );
+        let parsed = Evaluation::from_bytes(&eval.to_bytes()).unwrap();
+        assert!(Client::verify(
+            &server.public_key,
+            &blinded_point.decompress().unwrap(),
+            &parsed,
+            0,
+        ));
+        assert_eq!(parsed, eval);
+
+        let bytes = eval.to_bytes();
+        assert_eq!(Evaluation::from_bytes(&bytes[1..]), None);
+        let mut bad_proof = bytes;
+        bad_proof[COMPRESSED_POINT_LEN + PROOF_LEN - 1] = 0xff;
+        assert_eq!(Evaluation::from_bytes(&bad_proof), None);
+    }
+
+    #[test]
+    fn blinding_with_rng() {
+        let mds = vec![b"t".to_vec()];
+        let server = Server::new(&mds);
+        let input = b"some_test_input";
+        let mut rng: StrobeRng = Strobe::new(b"blinding_with_rng", SecParam::B128).into();
+        let (blinded_point, r) = Client::blinding(input).with_rng(&mut rng);
+        let evaluated = server.eval(&blinded_point, 0, false).unwrap();
+        let unblinded = Client::unblind(&evaluated.output, &r);
+
+        let mut chk_inp = [0u8; 64];
+        strobe_hash(input, "ppoprf_derive_client_input", &mut chk_inp);
+        let chk_eval = server
+            .eval(
+                &RistrettoPoint::from_uniform_bytes(&chk_inp).compress(),
+                0,
+                false,
+            )
+            .unwrap();
+        assert_eq!(chk_eval.output, unblinded);
+    }
+
+    #[test]
+    fn blinding_token_retry() {
+        let mds = vec![b"t".to_vec()];
+        let server = Server::new(&mds);
+        let input = b"some_test_input";
+        let token = BlindingToken::new(input);
+        let persisted = token.to_bytes();
+        // first request is sent, but the response is lost
+        server.eval(&token.blinded_point, 0, true).unwrap();
+
+        // retry the request with the persisted token
+        let token = BlindingToken::from_bytes(&persisted).unwrap();
+        let evaluated = server.eval(&token.blinded_point, 0, true).unwrap();
+        assert!(Client::verify(
+            &server.public_key,
+            &token.blinded_point.decompress().unwrap(),
+            &evaluated,
+            0,
+        ));
+        let unblinded = token.unblind(&evaluated.output);
+
+        let mut chk_inp = [0u8; 64];
+        strobe_hash(input, "ppoprf_derive_client_input", &mut chk_inp);
+        let chk_eval = server
+            .eval(
+                &RistrettoPoint::from_uniform_bytes(&chk_inp).compress(),
+                0,
+                false,
+            )
+            .unwrap();
+        assert_eq!(chk_eval.output, unblinded);
+    }
+
+    #[test]
+    fn blinding_token_bad_bytes() {
+        let token = BlindingToken::new(b"some_test_input");
+        let bytes = token.to_bytes();
+        assert_eq!(BlindingToken::from_bytes(&bytes), Some(token));
+        assert_eq!(BlindingToken::from_bytes(&bytes[1..]), None);
+        let mut bad_scalar = bytes;
+        bad_scalar[BLINDING_TOKEN_LEN - 1] = 0xff;
+        assert_eq!(BlindingToken::from_bytes(&bad_scalar), None);
+    }
+
+    #[test]
+    fn blinding_token_debug_redacts_r() {
+        let token = BlindingToken::new(b"some_test_input");
+        let debug = format!("{:?}", token);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains(&format!("{:?}", token.r)));
+    }
+
+    #[test]
+    fn eval_errors() {
+        let mds = vec![b"a".to_vec(), b"t".to_vec()];
+        let mut server = Server::new(&mds);
+        let (blinded_point, _) = Client::blind(b"some_test_input");
+        assert_eq!(
+            server.eval(&blinded_point, 2, false).err(),
+            Some(PPOPRFError::UnknownMetadata)
+        );
+        let invalid_point = CompressedRistretto::from_slice(&[0xff; 32]);
+        assert_eq!(
+            server.eval(&invalid_point, 0, false).err(),
+            Some(PPOPRFError::InvalidPoint)
+        );
+        server.puncture(b"t");
+        assert_eq!(
+            server.eval(&blinded_point, 1, true).err(),
+            Some(PPOPRFError::PuncturedMetadata)
+        );
+        assert_eq!(
+            server.eval_multi(&blinded_point, &[0, 1], false).err(),
+            Some(PPOPRFError::PuncturedMetadata)
+        );
+
+        // force the tagged key for the first tag to be zero
+        let mut tag = [0u8; 32];
+        server.pprf.eval(b"a", &mut tag);
+        server.oprf_key = -Scalar::from_bytes_mod_order(tag);
+        assert_eq!(
+            server.eval(&blinded_point, 0, true).err(),
+            Some(PPOPRFError::ProofGenerationFailed)
+        );
+        assert_eq!(
+            server.eval(&blinded_point, 0, false).err(),
+            Some(PPOPRFError::DegenerateKey)
+        );
+        let mut out = [0u8; 32];
+        assert_eq!(
+            end_to_end_evaluation(&server, b"some_test_input", 0, false, &mut out),
+            Err(PPOPRFError::DegenerateKey)
+        );
+
+        // a tag the punctured PRF can't take
+        assert_eq!(
+            server.rotate(b"ab".to_vec()),
+            Err(PPOPRFError::InvalidMetadataLength {
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            PPOPRFError::PuncturedMetadata.to_string(),
+            "Metadata tag has been punctured"
+        );
+    }
+
+    #[test]
+    fn new_with_rng_is_deterministic() {
+        let mds = vec![b"a".to_vec(), b"t".to_vec()];
+        let seeded = |seed: &[u8]| {
+            let mut strobe = Strobe::new(b"new_with_rng", SecParam::B128);
+            strobe.key(seed, false);
+            let mut rng: StrobeRng = strobe.into();
+            Server::new_with_rng(&mds, &mut rng)
+        };
+        let (first, second, other) = (seeded(b"seed"), seeded(b"seed"), seeded(b"other"));
+        assert_eq!(first.get_public_key(), second.get_public_key());
+        assert_ne!(first.get_public_key(), other.get_public_key());
+
+        let input = b"some_test_input";
+        for md_idx in 0..mds.len() {
+            let mut outputs = [[0u8; 32]; 3];
+            for (server, out) in [&first, &second, &other].iter().zip(outputs.iter_mut()) {
+                end_to_end_evaluation(server, input, md_idx, true, out).unwrap();
+            }
+            assert_eq!(outputs[0], outputs[1]);
+            assert_ne!(outputs[0], outputs[2]);
+        }
+
+        // Puncturing one of the seeded servers doesn't affect the other
+        let mut punctured = seeded(b"seed");
+        punctured.puncture(b"t");
+        let mut out = [0u8; 32];
+        assert!(end_to_end_evaluation(&punctured, input, 1, false, &mut out).is_err());
+        assert!(end_to_end_evaluation(&first, input, 1, false, &mut out).is_ok());
+    }
+
+    #[test]
+    fn rekey() {
+        let mds = vec![b"a".to_vec(), b"t".to_vec()];
+        let mut server = Server::new(&mds);
+        server.puncture(b"t");
+        let old_public_key = server.get_public_key();
+        let input = b"some_test_input";
+        let mut before = [0u8; 32];
+        end_to_end_evaluation(&server, input, 0, true, &mut before).unwrap();
+
+        server.rekey(&mut rand_core::OsRng);
+        let mut after = [0u8; 32];
+        end_to_end_evaluation(&server, input, 0, true, &mut after).unwrap();
+        assert_ne!(before, after);
+        assert_eq!(server.get_valid_metadata_tags(), mds);
+        assert_eq!(
+            server.eval(&Client::blind(input).0, 1, false).err(),
+            Some(PPOPRFError::PuncturedMetadata)
+        );
+
+        // proofs no longer verify against the old public key
+        let (blinded_point, _) = Client::blind(input);
+        let eval = server.eval(&blinded_point, 0, true).unwrap();
+        let point = blinded_point.decompress().unwrap();
+        assert!(Client::verify(&server.get_public_key(), &point, &eval, 0));
+        assert!(!Client::verify(&old_public_key, &point, &eval, 0));
+    }
+
+    #[test]
+    fn rotate_evicts_oldest() {
+        let max_mds = 3;
+        let mut server = Server::with_capacity(&[vec![0u8]], max_mds);
+        let input = b"some_test_input";
+        for epoch in 1..8u8 {
+            let evicted = server.rotate(vec![epoch]).unwrap();
+            if epoch < max_mds as u8 {
+                assert_eq!(evicted, None);
+            } else {
+                let oldest = epoch - max_mds as u8;
+                assert_eq!(evicted, Some(vec![oldest]));
+
+                // the evicted tag is punctured, and can't be restored
+                let mut tag = [0u8; 32];
+                assert!(server.pprf.try_eval(&[oldest], &mut tag).is_err());
+                assert_eq!(
+                    server.rotate(vec![oldest]),
+                    Err(PPOPRFError::PuncturedMetadata)
+                );
+            }
+
+            // the newest tag is always the last, and evaluates
+            let md_idx = server.get_valid_metadata_tags().len() - 1;
+            assert_eq!(server.get_valid_metadata_tags()[md_idx], vec![epoch]);
+            let (unblinded, chk_eval) = end_to_end_eval_check(&server, input, md_idx);
+            assert_eq!(chk_eval, unblinded);
+            assert_eq!(server.mds.capacity(), max_mds);
+            assert_eq!(server.public_key.capacity(), max_mds + 1);
+        }
+        assert_eq!(
+            server.get_valid_metadata_tags(),
+            vec![vec![5u8], vec![6], vec![7]]
+        );
+        assert_eq!(server.get_public_key().len(), max_mds + 1);
+        assert_eq!(
+            server.eval(&Client::blind(input).0, max_mds, false).err(),
+            Some(PPOPRFError::UnknownMetadata)
+        );
+        assert_eq!(server.rotate(vec![7]), Ok(None));
+    }
+
+    #[test]
+    fn finalization_hash() {
+        let mds = vec![b"t".to_vec()];
+        let server = Server::new(&mds);
+        assert_eq!(server.get_finalization_hash(), FinalizationHash::Strobe);
+        let input = b"some_test_input";
+        let (unblinded, _) = end_to_end_eval_check_no_proof(&server, input, 0);
+        let mut hash_input = input.to_vec();
+        hash_input.extend(&mds[0]);
+        hash_input.extend(unblinded.to_bytes());
+
+        // the default is unchanged
+        let mut out = [0u8; 32];
+        end_to_end_evaluation(&server, input, 0, false, &mut out).unwrap();
+        let mut expected = [0u8; 64];
+        strobe_hash(&hash_input, "ppoprf_finalize", &mut expected);
+        assert_eq!(out, expected[..32]);
+
+        let mut sha_server = server.clone();
+        sha_server.finalization_hash = FinalizationHash::Sha512;
+        let mut sha_out = [0u8; 32];
+        end_to_end_evaluation(&sha_server, input, 0, true, &mut sha_out).unwrap();
+        assert_ne!(sha_out, out);
+        let expected = digest::digest(&digest::SHA512, &hash_input);
+        assert_eq!(sha_out, expected.as_ref()[..32]);
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn expand_message_xmd_rfc_vector() {
+        // RFC 9380, appendix K.3
+        let mut out = [0u8; 32];
+        expand_message_xmd(b"", b"QUUX-V01-CS02-with-expander-SHA512-256", &mut out);
+        assert_eq!(
+            out[..],
+            hex("6b9a7312411d92f921c6f68ca0b6380730a1a4d982c507211a90964c394179ba")[..]
+        );
+    }
+
+    #[test]
+    fn hash_to_curve_vectors() {
+        // Fixed vectors for other implementations to check against: the
+        // point `input` is mapped to, and its evaluation with a combined
+        // key (the OPRF key plus the metadata tag scalar) of 12, which is
+        // `12^-1` times the point.
+        let input = b"ppoprf test vector";
+        let vectors = [
+            (
+                HashToCurve::Strobe,
+                "ca2eb796a48689d4836a25ac196ec07c8473992e044176db7837b33c7bb64169",
+                "a670e7421c35b52a744d06261fad30aa1363ba10366d7e60c44626cab6bc3d04",
+            ),
+            (
+                HashToCurve::Sha512Xmd,
+                "d61b0b404343f7dd6cd15be60124dc102bd40816fba0f4ff7ad5ecd49458f37f",
+                "7431f2f237e40a0702c650e35e08b7a3ba6a17301fe657633fb879a73d9f5831",
+            ),
+        ];
+
+        let mds = vec![b"t".to_vec()];
+        let mut server = Server::new(&mds);
+        let mut tag = [0u8; 32];
+        server.pprf.eval(&mds[0], &mut tag);
+        server.oprf_key = Scalar::from(12u64) - Scalar::from_bytes_mod_order(tag);
+        for (hash, point, output) in &vectors {
+            let p = hash.hash_to_curve(input).compress();
+            assert_eq!(p.as_bytes()[..], hex(point)[..]);
+            let eval = server.eval(&p, 0, false).unwrap();
+            assert_eq!(eval.output.as_bytes()[..], hex(output)[..]);
+
+            // Blinding with the same variant evaluates to the same point
+            let (blinded, r) = Client::blind_with(*hash, input);
+            let eval = server.eval(&blinded, 0, false).unwrap();
+            assert_eq!(
+                Client::unblind(&eval.output, &r).as_bytes()[..],
+                hex(output)[..]
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "PuncturedMetadata")]
     fn end_to_end_puncture() {
         let mds = vec![b"a".to_vec(), b"t".to_vec()];
         let mut server = Server::new(&mds);
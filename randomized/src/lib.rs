@@ -0,0 +1,19 @@
+//! The `Randomized` trait shared by the crates of the workspace, so
+//! that generic code can inject randomness into `sharks`, `adss` and
+//! `ppoprf` operations in the same way, without those crates depending
+//! on each other for it.
+#![no_std]
+
+use rand_core::{CryptoRng, RngCore};
+
+/// Common interface for operations which consume randomness.
+///
+/// Implementors take the random number generator as an argument, so
+/// generic code can inject its own source of randomness (e.g. a seeded
+/// generator in tests, or a STROBE-derived one) by calling `with_rng`
+/// uniformly.
+pub trait Randomized {
+    type Output;
+
+    fn with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Self::Output;
+}
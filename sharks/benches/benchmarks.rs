@@ -1,25 +1,297 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use std::convert::TryFrom;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand_chacha::rand_core::SeedableRng;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::{TryFrom, TryInto};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use sharks::{Share, Sharks};
+use ff::PrimeField;
+use sharks::{get_evaluator, random_polynomial, Fp, FIELD_ELEMENT_LEN};
+use sharks::{interpolate, interpolate_with_ctx, InterpolationCtx};
+use sharks::{Secret, Share, Sharks, SECRET_CHUNK_LEN};
+
+// Counts allocations, so that benchmarks can report them as well as
+// timings.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// Returns the number of allocations made by `f`.
+fn count_allocations<F: FnMut()>(mut f: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
 
 fn dealer(c: &mut Criterion) {
     let sharks = Sharks(255);
-    let mut dealer = sharks.dealer(&[1]);
+    let secret = Secret::from_bytes(&[1]);
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    let mut dealer = sharks.dealer_rng(&secret, &mut rng);
 
     c.bench_function("obtain_shares_dealer", |b| {
-        b.iter(|| sharks.dealer(black_box(&[1])))
+        b.iter(|| sharks.dealer_rng(black_box(&secret), &mut rng))
     });
     c.bench_function("step_shares_dealer", |b| b.iter(|| dealer.next()));
 }
 
+// Compares collecting the shares of each secret into a new `Vec` with
+// `dealer_into`, which reuses one buffer of shares across secrets.
+fn dealer_into(c: &mut Criterion) {
+    let sharks = Sharks(10);
+    let secret = Secret::from_bytes(&[1; 40]);
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    let mut out = Vec::new();
+    sharks.dealer_into(&secret, 100, &mut rng, &mut out);
+    let collected = count_allocations(|| {
+        black_box(
+            sharks
+                .dealer_rng(&secret, &mut rng)
+                .take(100)
+                .collect::<Vec<Share>>(),
+        );
+    });
+    let reused = count_allocations(|| {
+        sharks.dealer_into(&secret, 100, &mut rng, &mut out);
+    });
+    println!(
+        "allocations dealing 100 shares: collect {}, dealer_into {}",
+        collected, reused
+    );
+
+    let mut group = c.benchmark_group("deal_n100");
+    group.bench_function("collect", |b| {
+        b.iter(|| {
+            sharks
+                .dealer_rng(black_box(&secret), &mut rng)
+                .take(100)
+                .collect::<Vec<Share>>()
+        })
+    });
+    group.bench_function("dealer_into", |b| {
+        b.iter(|| sharks.dealer_into(black_box(&secret), 100, &mut rng, &mut out))
+    });
+    group.finish();
+}
+
+// Compares generating the shares for a committee of 2000 one at a time
+// with generating them on the `rayon` thread pool.
+fn gen_many(c: &mut Criterion) {
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    let evaluator = Sharks(100).dealer_rng(&Secret::from_bytes(&[1; 100]), &mut rng);
+    let xs: Vec<Fp> = (1..=2000).map(Fp::from).collect();
+
+    let mut group = c.benchmark_group("gen_n2000");
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            black_box(&xs)
+                .iter()
+                .map(|&x| evaluator.gen_at(x).unwrap())
+                .collect::<Vec<Share>>()
+        })
+    });
+    #[cfg(feature = "parallel")]
+    group.bench_function("parallel", |b| {
+        b.iter(|| evaluator.gen_many_parallel(black_box(&xs)).unwrap())
+    });
+    group.finish();
+}
+
 fn recover(c: &mut Criterion) {
     let sharks = Sharks(255);
-    let shares: Vec<Share> = sharks.dealer(&[1]).take(255).collect();
+    let shares: Vec<Share> = get_share_set(255, 1);
 
     c.bench_function("recover_secret", |b| {
         b.iter(|| sharks.recover(black_box(shares.as_slice())))
     });
+
+    // At large thresholds, recovery used to be dominated by cloning
+    // every share before interpolating.
+    let sharks = Sharks(1000);
+    let shares: Vec<Share> = get_share_set(1000, 1);
+    let cloned = count_allocations(|| {
+        black_box(shares.to_vec());
+    });
+    let recovered = count_allocations(|| {
+        black_box(sharks.recover(&shares).unwrap());
+    });
+    println!(
+        "allocations at t=1000: cloning shares {}, recover {}",
+        cloned, recovered
+    );
+    c.bench_function("recover_secret_t1000", |b| {
+        b.iter(|| sharks.recover(black_box(shares.as_slice())))
+    });
+}
+
+// Compares the single field element path with the generic one, on the
+// same secret and shares.
+fn single(c: &mut Criterion) {
+    let sharks = Sharks(100);
+    let secret = Fp::from(0xabcd);
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    let shares: Vec<Share> = sharks
+        .dealer_single_rng(secret, &mut rng.clone())
+        .take(100)
+        .collect();
+    assert_eq!(sharks.recover_single(&shares), Ok(secret));
+    assert_eq!(sharks.recover_fp(&shares), Ok(vec![secret]));
+
+    let mut group = c.benchmark_group("single_t100");
+    group.bench_function("deal_generic", |b| {
+        b.iter(|| {
+            get_evaluator(vec![random_polynomial(black_box(secret), 100, &mut rng)])
+                .take(100)
+                .count()
+        })
+    });
+    group.bench_function("deal_single", |b| {
+        b.iter(|| {
+            sharks
+                .dealer_single_rng(black_box(secret), &mut rng)
+                .take(100)
+                .count()
+        })
+    });
+    group.bench_function("recover_generic", |b| {
+        b.iter(|| sharks.recover_fp(black_box(shares.as_slice())))
+    });
+    group.bench_function("recover_single", |b| {
+        b.iter(|| sharks.recover_single(black_box(shares.as_slice())))
+    });
+    group.finish();
+}
+
+// Compares recovering from shares that arrive one at a time with
+// `Recovery`, which does O(t) work as each share is added, against
+// recovering from all of them at once with Lagrange interpolation once
+// the last one arrives.
+fn incremental(c: &mut Criterion) {
+    let sharks = Sharks(100);
+    let shares: Vec<Share> = get_share_set(100, 1);
+    let mut partial = sharks.recovery();
+    for share in &shares[..99] {
+        partial.add(share).unwrap();
+    }
+
+    let mut group = c.benchmark_group("incremental_t100");
+    group.bench_function("newton_add_last", |b| {
+        b.iter_batched(
+            || partial.clone(),
+            |mut recovery| {
+                recovery.add(black_box(&shares[99])).unwrap();
+                recovery.recover_fp()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("newton_add_all", |b| {
+        b.iter(|| {
+            let mut recovery = sharks.recovery();
+            for share in black_box(&shares) {
+                recovery.add(share).unwrap();
+            }
+            recovery.recover_fp()
+        })
+    });
+    group.bench_function("lagrange_recover", |b| {
+        b.iter(|| sharks.recover_fp(black_box(shares.as_slice())))
+    });
+    group.finish();
+}
+
+// Compares deduplicating the `x` coordinates of 1000 shares, as
+// `recover` does, with the AHash hasher of `hashbrown` and the SipHash
+// hasher of `std`.
+fn dedup(c: &mut Criterion) {
+    let shares: Vec<Share> = get_share_set(1000, 1);
+    let keys: Vec<[u8; FIELD_ELEMENT_LEN]> = shares
+        .iter()
+        .map(|s| s.x.to_repr().as_ref().try_into().unwrap())
+        .collect();
+
+    let mut group = c.benchmark_group("dedup_t1000");
+    group.bench_function("ahash", |b| {
+        b.iter(|| {
+            let mut set = hashbrown::HashSet::new();
+            black_box(&keys).iter().filter(|k| set.insert(**k)).count()
+        })
+    });
+    group.bench_function("siphash", |b| {
+        b.iter(|| {
+            let mut set = std::collections::HashSet::new();
+            black_box(&keys).iter().filter(|k| set.insert(**k)).count()
+        })
+    });
+    group.finish();
+}
+
+fn interpolation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interpolate");
+    for &threshold in &[2, 10, 50, 255] {
+        for &chunks in &[1, 4, 16] {
+            let shares = get_share_set(threshold, chunks);
+            group.bench_with_input(
+                BenchmarkId::from_parameter(format!("t={}, chunks={}", threshold, chunks)),
+                &shares,
+                |b, shares| b.iter(|| interpolate(black_box(shares)).unwrap()),
+            );
+        }
+    }
+    group.finish();
+}
+
+// Interpolates many groups of shares, as the aggregation server does,
+// either allocating afresh for each group or reusing one
+// `InterpolationCtx` for all of them.
+fn interpolation_ctx(c: &mut Criterion) {
+    let groups: Vec<Vec<Share>> = (0..100).map(|_| get_share_set(10, 4)).collect();
+    let mut ctx = InterpolationCtx::new();
+    let fresh = count_allocations(|| {
+        for shares in &groups {
+            black_box(interpolate(shares).unwrap());
+        }
+    });
+    let reused = count_allocations(|| {
+        for shares in &groups {
+            black_box(interpolate_with_ctx(&mut ctx, shares).unwrap());
+        }
+    });
+    println!(
+        "allocations per group: interpolate {}, interpolate_with_ctx {}",
+        fresh as f64 / groups.len() as f64,
+        reused as f64 / groups.len() as f64
+    );
+
+    let mut group = c.benchmark_group("interpolate_groups");
+    group.bench_function("interpolate", |b| {
+        b.iter(|| {
+            for shares in &groups {
+                black_box(interpolate(shares).unwrap());
+            }
+        })
+    });
+    group.bench_function("interpolate_with_ctx", |b| {
+        b.iter(|| {
+            for shares in &groups {
+                black_box(interpolate_with_ctx(&mut ctx, shares).unwrap());
+            }
+        })
+    });
+    group.finish();
 }
 
 fn share(c: &mut Criterion) {
@@ -36,9 +308,25 @@ fn share(c: &mut Criterion) {
     });
 }
 
+// Deals a reproducible set of `threshold` shares, each with `chunks`
+// y coordinates.
+fn get_share_set(threshold: u32, chunks: usize) -> Vec<Share> {
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    // the encoded secret has a 4 byte length prefix
+    let secret = vec![0xab; chunks * SECRET_CHUNK_LEN - 4];
+    let shares: Vec<Share> = Sharks(threshold)
+        .dealer_rng(&Secret::from_bytes(&secret), &mut rng)
+        .take(threshold as usize)
+        .collect();
+    assert_eq!(shares[0].y.len(), chunks);
+    shares
+}
+
 fn get_test_bytes() -> Vec<u8> {
     let suffix = vec![0u8; 31];
-    let mut bytes = vec![1u8; 1];
+    let mut bytes = vec![2u8, 0, 0, 0]; // threshold
+    bytes.extend(vec![0u8; 4]); // version
+    bytes.extend(vec![1u8; 1]);
     bytes.extend(suffix.clone()); // x coord
     bytes.extend(vec![2u8; 1]);
     bytes.extend(suffix.clone()); // y coord #1
@@ -47,5 +335,17 @@ fn get_test_bytes() -> Vec<u8> {
     bytes
 }
 
-criterion_group!(benches, dealer, recover, share);
+criterion_group!(
+    benches,
+    dealer,
+    dealer_into,
+    gen_many,
+    recover,
+    single,
+    incremental,
+    dedup,
+    interpolation,
+    interpolation_ctx,
+    share
+);
 criterion_main!(benches);
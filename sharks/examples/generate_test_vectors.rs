@@ -0,0 +1,74 @@
+// Regenerates the interoperability test vectors checked by
+// `tests/test_vectors.rs`:
+//
+//     cargo run --example generate_test_vectors > tests/test_vectors.json
+//
+// Each vector deals `secret` with a `ChaCha8Rng` seeded with `seed`, and
+// records the serialized shares and the secret recovered from them.
+
+use rand_chacha::rand_core::SeedableRng;
+use sharks::{Secret, Share, Sharks};
+
+struct Params {
+    name: &'static str,
+    threshold: u32,
+    shares: usize,
+    seed: [u8; 32],
+    secret: Vec<u8>,
+}
+
+fn main() {
+    let params = [
+        Params {
+            name: "threshold 2",
+            threshold: 2,
+            shares: 3,
+            seed: [0x90; 32],
+            secret: b"sta-rs".to_vec(),
+        },
+        Params {
+            name: "threshold 3",
+            threshold: 3,
+            shares: 5,
+            seed: [0x91; 32],
+            secret: (0..32).collect(),
+        },
+        Params {
+            name: "multi-chunk secret",
+            threshold: 3,
+            shares: 4,
+            seed: [0x92; 32],
+            secret: (0..100).map(|i| (i * 7) as u8).collect(),
+        },
+    ];
+
+    let vectors: Vec<String> = params.iter().map(vector).collect();
+    println!("{{\n  \"vectors\": [\n{}\n  ]\n}}", vectors.join(",\n"));
+}
+
+fn vector(params: &Params) -> String {
+    let sharks = Sharks(params.threshold);
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed(params.seed);
+    let shares: Vec<Share> = sharks
+        .dealer_rng(&Secret::from_bytes(&params.secret), &mut rng)
+        .take(params.shares)
+        .collect();
+    let recovered = sharks.recover(&shares).expect("recovery failed");
+    let shares: Vec<String> = shares
+        .iter()
+        .map(|s| format!("        \"{}\"", hex(&Vec::from(s))))
+        .collect();
+    format!(
+        "    {{\n      \"name\": \"{}\",\n      \"threshold\": {},\n      \"seed\": \"{}\",\n      \"secret\": \"{}\",\n      \"shares\": [\n{}\n      ],\n      \"recovered\": \"{}\"\n    }}",
+        params.name,
+        params.threshold,
+        hex(&params.seed),
+        hex(&params.secret),
+        shares.join(",\n"),
+        hex(recovered.as_bytes()),
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -2,7 +2,7 @@
 use libfuzzer_sys::fuzz_target;
 
 use arbitrary::Arbitrary;
-use sharks::{Share, Sharks};
+use sharks::{Secret, Share, Sharks};
 
 #[derive(Debug, Arbitrary)]
 struct Parameters {
@@ -13,7 +13,7 @@ struct Parameters {
 
 fuzz_target!(|params: Parameters| {
     let sharks = Sharks(params.threshold);
-    let dealer = sharks.dealer(&params.secret);
+    let dealer = sharks.dealer(&Secret::from_bytes(&params.secret));
 
     let _shares: Vec<Share> = dealer.take(params.n_shares).collect();
 });
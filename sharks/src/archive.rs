@@ -0,0 +1,237 @@
+//! A single-file encoding of a whole set of shares, for backups.
+//!
+//! An archive is `ARCHIVE_MAGIC`, then the `threshold` and the number of
+//! shares, each as a little-endian `u32`, then each serialized share
+//! prefixed with its length as a little-endian `u32`, and finally the
+//! SHA-256 digest of everything before it. The digest catches any
+//! corruption or truncation of the file, but doesn't authenticate it:
+//! anyone can write a valid archive.
+//!
+//! Reading an archive also checks that every share was dealt with the
+//! archive's `threshold`, so that a mislabelled archive fails to load
+//! rather than to recover.
+
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+use sha2::{Digest, Sha256};
+
+use crate::share_ff::Share;
+use crate::{RecoverError, Secret, Sharks};
+
+/// The magic bytes at the start of every archive, identifying the format.
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"SKA1";
+
+/// Length of the checksum at the end of an archive, in bytes.
+pub const ARCHIVE_CHECKSUM_LEN: usize = 32;
+
+/// A set of shares of one secret, along with the threshold needed to
+/// recover it, which can be written to and read from a single archive.
+///
+/// Example:
+/// ```
+/// # use sharks::{ Secret, Sharks, Share, ShareSet };
+/// # use rand_chacha::rand_core::SeedableRng;
+/// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+/// let shares: Vec<Share> = Sharks(2).dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng).take(3).collect();
+/// let archive = ShareSet::new(2, shares).unwrap().to_archive();
+/// let set = ShareSet::from_archive(&archive).unwrap();
+/// assert_eq!((set.threshold(), set.shares().len()), (2, 3));
+/// assert_eq!(set.recover().unwrap().as_bytes(), vec![1, 2]);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareSet {
+    threshold: u32,
+    shares: Vec<Share>,
+}
+
+impl ShareSet {
+    /// Creates a set from `shares`. Returns an `Err` if a share was
+    /// dealt with a different `threshold`, as `from_archive` would reject
+    /// the archive.
+    pub fn new(threshold: u32, shares: Vec<Share>) -> Result<Self, &'static str> {
+        if shares.iter().any(|share| share.threshold != threshold) {
+            return Err("ShareSet holds a share with a different threshold");
+        }
+        Ok(ShareSet { threshold, shares })
+    }
+
+    /// The threshold the shares were dealt with.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The shares in the set.
+    pub fn shares(&self) -> &[Share] {
+        &self.shares
+    }
+
+    /// Encodes the set as an archive. See the `archive` module for the
+    /// format.
+    pub fn to_archive(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(ARCHIVE_MAGIC);
+        bytes.extend(self.threshold.to_le_bytes());
+        bytes.extend((self.shares.len() as u32).to_le_bytes());
+        for share in &self.shares {
+            let serialized = Vec::from(share);
+            bytes.extend((serialized.len() as u32).to_le_bytes());
+            bytes.extend(serialized);
+        }
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend(checksum);
+        bytes
+    }
+
+    /// Decodes a set from an archive created by `to_archive`. Returns an
+    /// `Err` if the magic bytes or checksum don't match, if the archive
+    /// is truncated or has trailing data, or if a share doesn't parse or
+    /// has a different threshold.
+    pub fn from_archive(bytes: &[u8]) -> Result<ShareSet, &'static str> {
+        if bytes.len() < ARCHIVE_MAGIC.len() + 8 + ARCHIVE_CHECKSUM_LEN {
+            return Err("Archive is too short");
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - ARCHIVE_CHECKSUM_LEN);
+        if body[..ARCHIVE_MAGIC.len()] != ARCHIVE_MAGIC {
+            return Err("Archive does not start with ARCHIVE_MAGIC");
+        } else if Sha256::digest(body)[..] != *checksum {
+            return Err("Archive checksum does not match");
+        }
+
+        let mut rest = &body[ARCHIVE_MAGIC.len()..];
+        let threshold = take_u32(&mut rest)?;
+        let count = take_u32(&mut rest)?;
+        let mut shares = Vec::new();
+        for _ in 0..count {
+            let len = take_u32(&mut rest)? as usize;
+            if rest.len() < len {
+                return Err("Archive is truncated");
+            }
+            let (serialized, tail) = rest.split_at(len);
+            let share = Share::try_from(serialized)?;
+            if share.threshold != threshold {
+                return Err("Archive holds a share with a different threshold");
+            }
+            shares.push(share);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err("Archive has trailing data");
+        }
+        Ok(ShareSet { threshold, shares })
+    }
+
+    /// Recovers the secret from the shares in the set, as
+    /// `Sharks::recover`.
+    pub fn recover(&self) -> Result<Secret, RecoverError> {
+        Sharks(self.threshold).recover(&self.shares)
+    }
+}
+
+fn take_u32(rest: &mut &[u8]) -> Result<u32, &'static str> {
+    if rest.len() < 4 {
+        return Err("Archive is truncated");
+    }
+    let (value, tail) = rest.split_at(4);
+    *rest = tail;
+    Ok(u32::from_le_bytes(value.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShareSet, ARCHIVE_CHECKSUM_LEN, ARCHIVE_MAGIC};
+    use crate::{Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+    use sha2::{Digest, Sha256};
+
+    fn share_set() -> ShareSet {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret: Vec<u8> = (0..40).collect();
+        let shares: Vec<Share> = Sharks(3)
+            .dealer_rng(&Secret::from_bytes(&secret), &mut rng)
+            .take(5)
+            .collect();
+        ShareSet::new(3, shares).unwrap()
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let set = share_set();
+        let archive = set.to_archive();
+        let read = ShareSet::from_archive(&archive).unwrap();
+        assert_eq!(read, set);
+        assert_eq!(
+            read.recover().unwrap().as_bytes(),
+            (0..40).collect::<Vec<u8>>()
+        );
+
+        let empty = ShareSet::new(2, Vec::new()).unwrap();
+        assert_eq!(ShareSet::from_archive(&empty.to_archive()), Ok(empty));
+    }
+
+    // Encodes an archive as `ShareSet::to_archive` would, without the
+    // checks in `ShareSet::new`.
+    fn raw_archive(threshold: u32, shares: &[Share]) -> Vec<u8> {
+        let mut bytes = Vec::from(ARCHIVE_MAGIC);
+        bytes.extend(threshold.to_le_bytes());
+        bytes.extend((shares.len() as u32).to_le_bytes());
+        for share in shares {
+            let serialized = Vec::from(share);
+            bytes.extend((serialized.len() as u32).to_le_bytes());
+            bytes.extend(serialized);
+        }
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend(checksum);
+        bytes
+    }
+
+    #[test]
+    fn test_archive_mismatched_threshold() {
+        let set = share_set();
+        assert_eq!(set.threshold(), 3);
+        assert_eq!(
+            ShareSet::from_archive(&raw_archive(3, set.shares())),
+            Ok(set.clone())
+        );
+
+        let mut shares = set.shares().to_vec();
+        shares[2].threshold = 2;
+        assert!(ShareSet::new(3, shares.clone()).is_err());
+        assert!(ShareSet::new(2, shares.clone()).is_err());
+
+        // A crafted archive holding the same shares doesn't read back
+        assert_eq!(
+            ShareSet::from_archive(&raw_archive(3, &shares)),
+            Err("Archive holds a share with a different threshold")
+        );
+        assert_eq!(
+            ShareSet::from_archive(&raw_archive(2, &shares)),
+            Err("Archive holds a share with a different threshold")
+        );
+    }
+
+    #[test]
+    fn test_archive_detects_corruption() {
+        let archive = share_set().to_archive();
+
+        // Every single bit flip is caught
+        for i in 0..archive.len() {
+            for bit in 0..8 {
+                let mut corrupt = archive.clone();
+                corrupt[i] ^= 1 << bit;
+                assert!(ShareSet::from_archive(&corrupt).is_err());
+            }
+        }
+        for len in 0..archive.len() {
+            assert!(ShareSet::from_archive(&archive[..len]).is_err());
+        }
+        let mut trailing = archive.clone();
+        trailing.push(0);
+        assert!(ShareSet::from_archive(&trailing).is_err());
+
+        // A valid checksum over a mislabelled threshold
+        let mut relabelled = archive[..archive.len() - ARCHIVE_CHECKSUM_LEN].to_vec();
+        relabelled[4] = 2;
+        let checksum = Sha256::digest(&relabelled);
+        relabelled.extend(checksum);
+        assert!(ShareSet::from_archive(&relabelled).is_err());
+    }
+}
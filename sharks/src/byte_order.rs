@@ -0,0 +1,157 @@
+//! Recovery from shares serialized with either byte order.
+//!
+//! Shares serialize their coordinates in little-endian order, but a
+//! port which wrote them big-endian produces bytes that parse as
+//! unrelated field elements, or fail to parse at all, and recovering
+//! from a mix of both gives garbage. `recover_mixed_endian` tries both
+//! orders for each share, and only succeeds if exactly one secret can
+//! be recovered consistently. With more shares than the threshold, the
+//! extra shares must lie on the interpolated polynomials, so a wrong
+//! choice of byte order is all but certain to be rejected; with exactly
+//! the threshold, only the encoding of the secret is checked, which is
+//! weaker but still catches most mistakes.
+//!
+//! Every combination of byte orders is tried for the shares which parse
+//! both ways, so their number is limited to
+//! `MAX_AMBIGUOUS_SHARES`.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::consistency::is_consistent;
+use crate::ff::Field;
+use crate::share_ff::{decode_secret, interpolate_at_with_ctx, Fp, Share};
+use crate::{InterpolationCtx, RecoverError, Secret, Sharks};
+
+/// The largest number of shares which parse in both byte orders that
+/// `recover_mixed_endian` will try combinations of.
+pub const MAX_AMBIGUOUS_SHARES: usize = 12;
+
+impl Sharks {
+    /// Recovers the secret from serialized shares whose coordinates may
+    /// be little-endian, as written by this crate, or big-endian. Returns
+    /// `UnknownByteOrder` if a share can't be parsed either way, if no
+    /// choice of byte orders gives a consistent secret, or if more than
+    /// one does. See the module documentation for how shares are checked.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share, SHARE_HEADER_LEN, FIELD_ELEMENT_LEN };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(2);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let mut bytes: Vec<Vec<u8>> = sharks
+    ///     .dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng)
+    ///     .take(3)
+    ///     .map(|s| Vec::from(&s))
+    ///     .collect();
+    /// // Reverse the coordinates of the second share to big-endian
+    /// for element in bytes[1][SHARE_HEADER_LEN..].chunks_mut(FIELD_ELEMENT_LEN) {
+    ///     element.reverse();
+    /// }
+    /// let shares: Vec<&[u8]> = bytes.iter().map(|b| b.as_slice()).collect();
+    /// assert_eq!(sharks.recover_mixed_endian(&shares).unwrap().as_bytes(), vec![1, 2]);
+    pub fn recover_mixed_endian(&self, shares: &[&[u8]]) -> Result<Secret, RecoverError> {
+        let candidates: Vec<Vec<Share>> = shares
+            .iter()
+            .map(|bytes| {
+                let mut parsed: Vec<Share> = Share::try_from(*bytes).into_iter().collect();
+                if let Ok(share) = Share::from_be_bytes(bytes) {
+                    if !parsed.contains(&share) {
+                        parsed.push(share);
+                    }
+                }
+                if parsed.is_empty() {
+                    Err(RecoverError::UnknownByteOrder)
+                } else {
+                    Ok(parsed)
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        let ambiguous: Vec<usize> = (0..candidates.len())
+            .filter(|&i| candidates[i].len() > 1)
+            .collect();
+        if ambiguous.len() > MAX_AMBIGUOUS_SHARES {
+            return Err(RecoverError::UnknownByteOrder);
+        }
+
+        let mut recovered: Option<Secret> = None;
+        for choice in 0..1usize << ambiguous.len() {
+            let chosen: Vec<&Share> = candidates
+                .iter()
+                .enumerate()
+                .map(|(i, parsed)| match ambiguous.iter().position(|&a| a == i) {
+                    Some(bit) => &parsed[(choice >> bit) & 1],
+                    None => &parsed[0],
+                })
+                .collect();
+            if let Some(secret) = self.recover_consistent(&chosen) {
+                match &recovered {
+                    Some(other) if *other != secret => return Err(RecoverError::UnknownByteOrder),
+                    _ => recovered = Some(secret),
+                }
+            }
+        }
+        recovered.ok_or(RecoverError::UnknownByteOrder)
+    }
+
+    // Recovers the secret from `shares` if all of them lie on the
+    // polynomials interpolated from the first `threshold` distinct ones,
+    // and the result is a valid encoding.
+    fn recover_consistent(&self, shares: &[&Share]) -> Option<Secret> {
+        let values = self.distinct_shares(shares.iter().copied()).ok()?;
+        let (basis, extra) = values.split_at(self.0 as usize);
+        let mut ctx = InterpolationCtx::new();
+        for share in extra {
+            if !is_consistent(&mut ctx, basis, share).ok()? {
+                return None;
+            }
+        }
+        let elements = interpolate_at_with_ctx(&mut ctx, basis, Fp::zero()).ok()?;
+        decode_secret(elements).map(Secret::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RecoverError, Secret, Sharks, FIELD_ELEMENT_LEN, SHARE_HEADER_LEN};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    fn to_big_endian(share: &mut [u8]) {
+        for element in share[SHARE_HEADER_LEN..].chunks_mut(FIELD_ELEMENT_LEN) {
+            element.reverse();
+        }
+    }
+
+    #[test]
+    fn test_recover_mixed_endian() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&(0..70).collect::<Vec<u8>>());
+        let mut bytes: Vec<Vec<u8>> = sharks
+            .dealer_rng(&secret, &mut rng)
+            .take(5)
+            .map(|s| Vec::from(&s))
+            .collect();
+        to_big_endian(&mut bytes[1]);
+        to_big_endian(&mut bytes[4]);
+        let shares: Vec<&[u8]> = bytes.iter().map(|b| b.as_slice()).collect();
+
+        assert_eq!(sharks.recover_mixed_endian(&shares), Ok(secret.clone()));
+        assert_eq!(sharks.recover_mixed_endian(&shares[1..4]), Ok(secret));
+
+        // Shares which are corrupt in either byte order
+        let mut corrupt = bytes.clone();
+        corrupt[2][SHARE_HEADER_LEN + FIELD_ELEMENT_LEN] ^= 1;
+        let shares: Vec<&[u8]> = corrupt.iter().map(|b| b.as_slice()).collect();
+        assert_eq!(
+            sharks.recover_mixed_endian(&shares),
+            Err(RecoverError::UnknownByteOrder)
+        );
+        assert_eq!(
+            sharks.recover_mixed_endian(&[&[0xff; 72][..]]),
+            Err(RecoverError::UnknownByteOrder)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Recovery with a measure of confidence in the result.
+//!
+//! With exactly `threshold` shares, any set of points lies on a
+//! polynomial of the right degree, so a single corrupt share silently
+//! gives a wrong secret. Each share beyond the threshold is a check: it
+//! must lie on the polynomials interpolated from the first `threshold`
+//! distinct shares. `recover_with_consistency` reports how many of the
+//! extra shares pass that check.
+//!
+//! The score doesn't identify a corrupt share. A corrupt extra share
+//! fails its own check only, while a corrupt share among the first
+//! `threshold` moves the polynomials, so that all the extra shares fail,
+//! or the result doesn't decode at all.
+
+use crate::ff::Field;
+use crate::share_ff::{decode_secret, interpolate_at_with_ctx, Fp, Share};
+use crate::{InterpolationCtx, RecoverError, Secret, Sharks};
+
+/// How many of the shares beyond the threshold lie on the polynomials
+/// interpolated from the others, as returned by
+/// `Sharks::recover_with_consistency`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Consistency {
+    /// The number of extra shares that lie on the polynomials.
+    pub consistent: usize,
+    /// The number of distinct shares beyond the threshold.
+    pub extra: usize,
+}
+
+impl Consistency {
+    /// The fraction of the extra shares that lie on the polynomials,
+    /// or `None` if there were none to check. Anything below `1.0`
+    /// means that at least one share is corrupt.
+    pub fn score(&self) -> Option<f64> {
+        if self.extra == 0 {
+            None
+        } else {
+            Some(self.consistent as f64 / self.extra as f64)
+        }
+    }
+}
+
+impl Sharks {
+    /// As `recover`, but also checks the distinct shares beyond the
+    /// threshold against the polynomials interpolated from the first
+    /// `threshold` ones, and returns how many of them agree. See the
+    /// `consistency` module for what the score shows.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(2);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng).take(4).collect();
+    /// let (secret, consistency) = sharks.recover_with_consistency(&shares).unwrap();
+    /// assert_eq!(secret.as_bytes(), vec![1, 2]);
+    /// assert_eq!(consistency.score(), Some(1.0));
+    pub fn recover_with_consistency<'a, T>(
+        &self,
+        shares: T,
+    ) -> Result<(Secret, Consistency), RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let values = self.distinct_shares(shares)?;
+        let (basis, extra) = values.split_at(self.0 as usize);
+        let mut ctx = InterpolationCtx::new();
+        let mut consistent = 0;
+        for share in extra {
+            if is_consistent(&mut ctx, basis, share)? {
+                consistent += 1;
+            }
+        }
+        let elements = interpolate_at_with_ctx(&mut ctx, basis, Fp::zero())?;
+        let secret = decode_secret(elements)
+            .map(Secret::from)
+            .ok_or(RecoverError::InvalidEncoding)?;
+        let consistency = Consistency {
+            consistent,
+            extra: extra.len(),
+        };
+        Ok((secret, consistency))
+    }
+}
+
+// Checks whether `share` lies on the polynomials through `basis`.
+pub(crate) fn is_consistent(
+    ctx: &mut InterpolationCtx,
+    basis: &[&Share],
+    share: &Share,
+) -> Result<bool, RecoverError> {
+    Ok(interpolate_at_with_ctx(ctx, basis, share.x)? == share.y.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Consistency;
+    use crate::ff::Field;
+    use crate::{Fp, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_recover_with_consistency() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&(0..40).collect::<Vec<u8>>());
+        let mut shares: Vec<Share> = sharks.dealer_rng(&secret, &mut rng).take(6).collect();
+
+        let (recovered, consistency) = sharks.recover_with_consistency(&shares).unwrap();
+        assert_eq!(recovered, secret);
+        assert_eq!(consistency.score(), Some(1.0));
+        let (_, consistency) = sharks.recover_with_consistency(&shares[..3]).unwrap();
+        assert_eq!(consistency.score(), None);
+
+        // One corrupt share among t + 3, beyond the threshold
+        shares[4].y[1] += Fp::one();
+        let (recovered, consistency) = sharks.recover_with_consistency(&shares).unwrap();
+        assert_eq!(recovered, secret);
+        assert_eq!(
+            consistency,
+            Consistency {
+                consistent: 2,
+                extra: 3
+            }
+        );
+        assert!(consistency.score().unwrap() < 1.0);
+
+        // Among the first t, no extra share agrees and the secret is wrong
+        shares.swap(0, 4);
+        let (recovered, consistency) = sharks.recover_with_consistency(&shares).unwrap();
+        assert_ne!(recovered, secret);
+        assert_eq!(
+            consistency,
+            Consistency {
+                consistent: 0,
+                extra: 3
+            }
+        );
+        assert_eq!(consistency.score(), Some(0.0));
+    }
+}
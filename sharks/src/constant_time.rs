@@ -0,0 +1,204 @@
+//! Recovery which doesn't reveal which shares were used.
+//!
+//! `recover` interpolates over the first `threshold` distinct shares it
+//! finds, so its running time and memory accesses depend on where any
+//! duplicate shares are in the input. Someone timing the recovering
+//! party could learn which holders' shares were actually used, and so
+//! something about the committee behind the secret.
+//!
+//! `recover_constant_time` selects the same shares, but the sequence of
+//! operations only depends on the number of shares, their threshold and
+//! their number of chunks, all of which are public. Duplicates are found
+//! by comparing every pair of shares, and every share takes part in the
+//! interpolation, with unselected shares masked out of the Lagrange
+//! basis.
+//!
+//! This does not hide the values being recovered from someone who can
+//! see the result, and only covers the selection and interpolation:
+//! the checks of the (public) share thresholds, versions and lengths
+//! return early, and decoding the secret takes time depending on its
+//! length.
+//! The cost is quadratic in the number of shares provided, rather than
+//! in the threshold, so callers should avoid passing many more shares
+//! than they need.
+
+use alloc::vec::Vec;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+use crate::ff::Field;
+use crate::share_ff::{decode_secret, invert, mul, Fp, Share};
+use crate::{RecoverError, Secret, Sharks};
+
+impl Sharks {
+    /// Like `recover`, but recovers the secret from the same shares
+    /// without revealing through timing which of them were used. See
+    /// the module documentation for the threat model.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let mut shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(4).collect();
+    /// shares.insert(1, shares[0].clone());
+    /// assert_eq!(sharks.recover_constant_time(&shares).unwrap().as_bytes(), vec![1]);
+    pub fn recover_constant_time(&self, shares: &[Share]) -> Result<Secret, RecoverError> {
+        let (chunks, version, namespace) = match shares.first() {
+            Some(share) => (share.y.len(), share.version, share.namespace),
+            None => return Err(RecoverError::NotEnoughShares),
+        };
+        for share in shares {
+            if share.threshold != self.0 {
+                return Err(RecoverError::ThresholdMismatch {
+                    expected: self.0,
+                    found: share.threshold,
+                });
+            } else if share.version != version {
+                return Err(RecoverError::VersionMismatch {
+                    expected: version,
+                    found: share.version,
+                });
+            } else if share.namespace != namespace {
+                return Err(RecoverError::NamespaceMismatch);
+            } else if share.y.len() != chunks {
+                return Err(RecoverError::InconsistentShareLength);
+            }
+        }
+
+        // Select the first `threshold` distinct shares, as `recover`
+        // does. The count never exceeds the threshold, so comparing it
+        // for equality is enough.
+        let mut selected: Vec<Choice> = Vec::with_capacity(shares.len());
+        let mut count = 0u32;
+        for (i, s_i) in shares.iter().enumerate() {
+            let duplicate = shares[..i]
+                .iter()
+                .fold(Choice::from(0), |acc, s_j| acc | s_i.x.ct_eq(&s_j.x));
+            let select = !duplicate & !count.ct_eq(&self.0);
+            count += u32::from(select.unwrap_u8());
+            selected.push(select);
+        }
+        if count != self.0 {
+            return Err(RecoverError::NotEnoughShares);
+        }
+
+        // The Lagrange basis at zero over the selected shares. The
+        // denominators of unselected shares are replaced with one, so
+        // that all of them can be inverted together.
+        let mut numerators: Vec<Fp> = Vec::with_capacity(shares.len());
+        let mut denominators: Vec<Fp> = Vec::with_capacity(shares.len());
+        for (i, s_i) in shares.iter().enumerate() {
+            let mut num = Fp::one();
+            let mut den = Fp::one();
+            for (j, s_j) in shares.iter().enumerate() {
+                let use_j = selected[j] & !Choice::from((i == j) as u8);
+                num = mul(num, Fp::conditional_select(&Fp::one(), &s_j.x, use_j));
+                den = mul(
+                    den,
+                    Fp::conditional_select(&Fp::one(), &(s_j.x - s_i.x), use_j),
+                );
+            }
+            numerators.push(num);
+            denominators.push(Fp::conditional_select(&Fp::one(), &den, selected[i]));
+        }
+
+        // Batch invert the denominators, using `basis` to hold the prefix
+        // products.
+        let mut basis: Vec<Fp> = Vec::with_capacity(shares.len());
+        let mut acc = Fp::one();
+        for den in &denominators {
+            basis.push(acc);
+            acc = mul(acc, *den);
+        }
+        let mut inv = invert(&acc).unwrap();
+        for i in (0..shares.len()).rev() {
+            let den_inv = mul(basis[i], inv);
+            inv = mul(inv, denominators[i]);
+            basis[i] =
+                Fp::conditional_select(&Fp::zero(), &mul(numerators[i], den_inv), selected[i]);
+        }
+
+        let elements: Vec<Fp> = (0..chunks)
+            .map(|chunk| {
+                shares
+                    .iter()
+                    .zip(basis.iter())
+                    .fold(Fp::zero(), |acc, (s_i, b)| acc + mul(*b, s_i.y[chunk]))
+            })
+            .collect();
+        decode_secret(&elements)
+            .map(Secret::from)
+            .ok_or(RecoverError::InvalidEncoding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RecoverError, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    fn deal(sharks: &Sharks, secret: &[u8], n: usize) -> Vec<Share> {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        sharks
+            .dealer_rng(&Secret::from_bytes(secret), &mut rng)
+            .take(n)
+            .collect()
+    }
+
+    #[test]
+    fn test_recover_constant_time_matches_recover() {
+        let sharks = Sharks(3);
+        let secret = [0x5a; 70];
+        let shares = deal(&sharks, &secret, 6);
+
+        // Duplicates at the start, middle and end of the input
+        let inputs: Vec<Vec<Share>> = alloc::vec![
+            shares[..3].to_vec(),
+            shares.clone(),
+            [&shares[..1], &shares[..1], &shares[1..]].concat(),
+            [&shares[2..3], &shares[..2], &shares[2..3], &shares[4..]].concat(),
+            [&shares[3..5], &shares[3..5], &shares[5..]].concat(),
+        ];
+        for input in &inputs {
+            assert_eq!(
+                sharks.recover_constant_time(input).unwrap().as_bytes(),
+                secret
+            );
+            assert_eq!(
+                sharks.recover_constant_time(input),
+                sharks.recover(input.as_slice())
+            );
+        }
+    }
+
+    #[test]
+    fn test_recover_constant_time_errors() {
+        let sharks = Sharks(3);
+        let shares = deal(&sharks, &[1, 2, 3], 3);
+
+        assert_eq!(
+            sharks.recover_constant_time(&[]),
+            Err(RecoverError::NotEnoughShares)
+        );
+        let repeated = [&shares[..2], &shares[..2]].concat();
+        assert_eq!(
+            sharks.recover_constant_time(&repeated),
+            Err(RecoverError::NotEnoughShares)
+        );
+        assert_eq!(
+            Sharks(2).recover_constant_time(&shares),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 2,
+                found: 3
+            })
+        );
+        let mut long = shares.clone();
+        long[1].y.push(shares[1].y[0]);
+        assert_eq!(
+            sharks.recover_constant_time(&long),
+            Err(RecoverError::InconsistentShareLength)
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Distributed key generation, so that a shared secret can be set up
+//! without any single dealer ever knowing it. Only available with the
+//! `vss` feature.
+//!
+//! Each of the `n` participants deals a random secret of its own with
+//! `DkgParticipant::deal_round`, sending share `j` privately to
+//! participant `j` and broadcasting its Feldman `Commitments`. Each
+//! participant then checks the shares it received and sums them with
+//! `DkgParticipant::aggregate`. The summed shares are shares of the sum
+//! of all the dealt secrets, which no participant knows unless a
+//! threshold of them collude, and they verify against the sum of the
+//! commitments, from `group_commitments`.
+//!
+//! Complaints: when `aggregate` rejects a share, the receiver broadcasts
+//! a complaint against its dealer. The accused dealer answers by
+//! publishing the share it sent. If the published share verifies
+//! against its commitments, everyone, including the complainer, uses it;
+//! otherwise, or if the dealer doesn't answer, the dealer is
+//! disqualified. Every participant must then aggregate the shares of the
+//! same set of qualified dealers, and sum the commitments of that set,
+//! for the shares to be consistent. Agreeing on that set needs a
+//! broadcast channel, which is out of scope here.
+//!
+//! Bias: this is the Joint-Feldman protocol, which Gennaro, Jarecki,
+//! Krawczyk and Rabin showed doesn't produce a uniformly random group
+//! secret ("Secure Distributed Key Generation for Discrete-Log Based
+//! Cryptosystems", EUROCRYPT 1999). The commitments are public as soon
+//! as they are broadcast, so a dealer who waits to see the others'
+//! commitments can choose which of its own to send, or get dealers
+//! disqualified with complaints, to bias the group public key, for
+//! instance to set some of its bits. Their protocol avoids this with a
+//! first round of Pedersen commitments, and isn't implemented here.
+//! Only use this where a biased key is acceptable, as it is for some
+//! threshold signature schemes, or where every dealer is committed to
+//! its dealing before seeing any other.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use bls12_381::G1Projective;
+use group::Curve;
+
+use crate::ff::Field;
+use crate::share_ff::{get_evaluator, random_polynomial, zeroize_elements, Fp, Share};
+use crate::vss::Commitments;
+
+/// Errors returned by `DkgParticipant::aggregate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DkgError {
+    /// No dealings were passed to aggregate.
+    NoDealings,
+    /// The shares of the dealings at these positions don't verify
+    /// against their commitments, or aren't for this participant. Each
+    /// one is grounds for a complaint against its dealer.
+    InvalidShares(Vec<usize>),
+}
+
+impl fmt::Display for DkgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DkgError::NoDealings => write!(f, "No dealings to aggregate"),
+            DkgError::InvalidShares(dealings) => {
+                write!(f, "Invalid shares in dealings {:?}", dealings)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DkgError {}
+
+/// One of the `n` participants of a distributed key generation, holding
+/// the random polynomial it deals. The polynomial is zeroized when the
+/// participant is dropped, and isn't shown by `Debug`.
+pub struct DkgParticipant {
+    index: u32,
+    n: u32,
+    poly: Vec<Fp>,
+}
+
+impl fmt::Debug for DkgParticipant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DkgParticipant")
+            .field("index", &self.index)
+            .field("n", &self.n)
+            .field("poly", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Drop for DkgParticipant {
+    fn drop(&mut self) {
+        zeroize_elements(&mut self.poly);
+    }
+}
+
+impl DkgParticipant {
+    /// Creates participant `index`, counting from 1, of `n`, who will
+    /// deal a random secret with `threshold`. The participant's share
+    /// of the group secret is at `x = index`.
+    ///
+    /// Panics unless `1 <= index <= n` and `1 <= threshold <= n`.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ group_commitments, DkgParticipant };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let participants: Vec<DkgParticipant> =
+    ///     (1..=3).map(|i| DkgParticipant::new(i, 2, 3, &mut rng)).collect();
+    /// let dealings: Vec<_> = participants.iter().map(|p| p.deal_round()).collect();
+    /// let shares: Vec<_> = participants
+    ///     .iter()
+    ///     .enumerate()
+    ///     .map(|(j, p)| {
+    ///         let received: Vec<_> = dealings.iter().map(|(c, s)| (c.clone(), s[j].clone())).collect();
+    ///         p.aggregate(&received).unwrap()
+    ///     })
+    ///     .collect();
+    /// let commitments: Vec<_> = dealings.into_iter().map(|(c, _)| c).collect();
+    /// let group = group_commitments(&commitments).unwrap();
+    /// assert!(shares.iter().all(|s| s.verify(&group)));
+    pub fn new<R: rand::Rng>(index: u32, threshold: u32, n: u32, rng: &mut R) -> Self {
+        assert!(
+            index >= 1 && index <= n && threshold >= 1 && threshold <= n,
+            "Participant index ({}) and threshold ({}) must be between 1 and n ({})",
+            index,
+            threshold,
+            n
+        );
+        let secret = Fp::random(&mut *rng);
+        DkgParticipant {
+            index,
+            n,
+            poly: random_polynomial(secret, threshold, rng),
+        }
+    }
+
+    /// The index of this participant, which is the `x` coordinate of
+    /// the shares it receives.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Deals this participant's secret, returning the commitments to
+    /// broadcast and the `n` shares, where share `j - 1` is to be sent
+    /// privately to participant `j`.
+    pub fn deal_round(&self) -> (Commitments, Vec<Share>) {
+        let mut evaluator = get_evaluator(alloc::vec![self.poly.clone()]);
+        let commitments = Commitments::from_evaluator(&evaluator);
+        let shares = evaluator.by_ref().take(self.n as usize).collect();
+        zeroize_elements(&mut evaluator.polys[0]);
+        (commitments, shares)
+    }
+
+    /// Checks the shares received from each qualified dealer against
+    /// its commitments, and sums them into this participant's share of
+    /// the group secret. Each entry of `received` is a dealer's
+    /// broadcast commitments with the share it sent to this participant.
+    ///
+    /// Returns `DkgError::InvalidShares` with the positions of all the
+    /// dealings whose shares fail the check, so that complaints can be
+    /// raised against their dealers.
+    pub fn aggregate(&self, received: &[(Commitments, Share)]) -> Result<Share, DkgError> {
+        let first = &received.first().ok_or(DkgError::NoDealings)?.1;
+        let x = Fp::from(self.index as u64);
+        let invalid: Vec<usize> = received
+            .iter()
+            .enumerate()
+            .filter(|(_, (commitments, share))| {
+                share.x != x
+                    || share.threshold != first.threshold
+                    || commitments.chunks() != 1
+                    || !share.verify(commitments)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if !invalid.is_empty() {
+            return Err(DkgError::InvalidShares(invalid));
+        }
+        Ok(Share {
+            x,
+            y: alloc::vec![received
+                .iter()
+                .fold(Fp::zero(), |acc, (_, share)| acc + share.y[0])],
+            threshold: first.threshold,
+            version: 0,
+            namespace: None,
+        })
+    }
+}
+
+/// Sums the commitments of the qualified dealers into commitments to
+/// the group secret, which the aggregated shares verify against. The
+/// last point is the group public key, the group secret times the G1
+/// generator. Returns `None` if there are no commitments, or they have
+/// different thresholds or numbers of chunks.
+pub fn group_commitments(commitments: &[Commitments]) -> Option<Commitments> {
+    let (first, rest) = commitments.split_first()?;
+    if rest
+        .iter()
+        .any(|c| c.threshold() != first.threshold() || c.chunks() != first.chunks())
+    {
+        return None;
+    }
+    let points = (0..first.chunks())
+        .map(|chunk| {
+            (0..first.threshold() as usize)
+                .map(|i| {
+                    commitments
+                        .iter()
+                        .fold(G1Projective::identity(), |acc, c| acc + c.points[chunk][i])
+                        .to_affine()
+                })
+                .collect()
+        })
+        .collect();
+    Some(Commitments {
+        threshold: first.threshold,
+        points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group_commitments, DkgError, DkgParticipant};
+    use crate::ff::Field;
+    use crate::share_ff::{interpolate_at, Share};
+    use crate::vss::Commitments;
+    use crate::{Fp, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_debug_redacts_poly() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let participant = DkgParticipant::new(1, 2, 3, &mut rng);
+        let debug = alloc::format!("{:?}", participant);
+        assert_eq!(
+            debug,
+            r#"DkgParticipant { index: 1, n: 3, poly: "<redacted>" }"#
+        );
+        assert!(!debug.contains(&alloc::format!("{:?}", participant.poly[0])));
+    }
+
+    // Runs a generation where every dealer is honest, returning each
+    // participant's dealing and the shares it received.
+    fn run(participants: &[DkgParticipant]) -> (Vec<Commitments>, Vec<Vec<(Commitments, Share)>>) {
+        let dealings: Vec<(Commitments, Vec<Share>)> =
+            participants.iter().map(|p| p.deal_round()).collect();
+        let received = (0..participants.len())
+            .map(|j| {
+                dealings
+                    .iter()
+                    .map(|(c, shares)| (c.clone(), shares[j].clone()))
+                    .collect()
+            })
+            .collect();
+        (dealings.into_iter().map(|(c, _)| c).collect(), received)
+    }
+
+    #[test]
+    fn test_dkg_consistent_group_secret() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let participants: Vec<DkgParticipant> = (1..=3)
+            .map(|i| DkgParticipant::new(i, 2, 3, &mut rng))
+            .collect();
+        let (commitments, received) = run(&participants);
+        let shares: Vec<Share> = participants
+            .iter()
+            .zip(&received)
+            .map(|(p, r)| p.aggregate(r).unwrap())
+            .collect();
+
+        // The group secret is the sum of the dealt secrets, which no
+        // participant holds, and any two shares recover it
+        let expected = participants
+            .iter()
+            .fold(Fp::zero(), |acc, p| acc + p.poly[1]);
+        for pair in &[[0, 1], [0, 2], [1, 2]] {
+            let subset = [shares[pair[0]].clone(), shares[pair[1]].clone()];
+            assert_eq!(interpolate_at(&subset, Fp::zero()).unwrap(), [expected]);
+        }
+        assert!(participants.iter().all(|p| p.poly[1] != expected));
+        assert_eq!(Sharks(2).recover_fp(&shares).unwrap(), [expected]);
+
+        // The shares verify against the summed commitments, whose last
+        // point commits to the group secret
+        let group = group_commitments(&commitments).unwrap();
+        assert!(shares.iter().all(|s| s.verify(&group)));
+        assert_eq!(shares[0].x, Fp::from(1));
+        assert_eq!(shares[2].x, Fp::from(3));
+        assert!(group_commitments(&[]).is_none());
+    }
+
+    #[test]
+    fn test_dkg_complaints() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let participants: Vec<DkgParticipant> = (1..=3)
+            .map(|i| DkgParticipant::new(i, 2, 3, &mut rng))
+            .collect();
+        let (_, mut received) = run(&participants);
+        assert_eq!(participants[0].aggregate(&[]), Err(DkgError::NoDealings));
+
+        // A corrupted share, and a share meant for someone else
+        received[0][1].1.y[0] += Fp::from(1);
+        received[0][2].1 = received[1][2].1.clone();
+        assert_eq!(
+            participants[0].aggregate(&received[0]),
+            Err(DkgError::InvalidShares(alloc::vec![1, 2]))
+        );
+        // Dropping the disqualified dealers gives a valid share of the
+        // first dealer's secret alone
+        let share = participants[0].aggregate(&received[0][..1]).unwrap();
+        assert!(share.verify(&received[0][0].0));
+    }
+}
@@ -0,0 +1,192 @@
+//! Threshold encryption: a payload is encrypted under a random key, and
+//! the key is shared, so that any threshold of shares can decrypt it.
+//! Only available with the `threshold-encryption` feature. The key is
+//! zeroized once it has been used.
+//!
+//! The payload is encrypted and authenticated with STROBE-128, keyed
+//! with a fresh key for each call to `seal`, so no nonce is needed. A
+//! ciphertext is the encrypted payload followed by a MAC of `MAC_LEN`
+//! bytes.
+
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+use core::fmt;
+
+use strobe_rs::{SecParam, Strobe};
+use zeroize::Zeroizing;
+
+use crate::{RecoverError, Secret, Share, Sharks};
+
+/// Length of the random key which is shared by `seal`, in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// Length of the MAC at the end of a serialized `Ciphertext`, in bytes.
+pub const MAC_LEN: usize = 16;
+
+/// A payload encrypted by `seal`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ciphertext {
+    body: Vec<u8>,
+    mac: [u8; MAC_LEN],
+}
+
+/// Obtains a byte vector from a `Ciphertext` instance
+impl From<&Ciphertext> for Vec<u8> {
+    fn from(c: &Ciphertext) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(c.body.len() + MAC_LEN);
+        bytes.extend(&c.body);
+        bytes.extend(&c.mac);
+        bytes
+    }
+}
+
+/// Obtains a `Ciphertext` instance from a byte slice
+impl TryFrom<&[u8]> for Ciphertext {
+    type Error = &'static str;
+
+    fn try_from(s: &[u8]) -> Result<Ciphertext, Self::Error> {
+        if s.len() < MAC_LEN {
+            return Err("A Ciphertext must be at least MAC_LEN bytes long");
+        }
+        let (body, mac) = s.split_at(s.len() - MAC_LEN);
+        Ok(Ciphertext {
+            body: body.to_vec(),
+            mac: mac.try_into().unwrap(),
+        })
+    }
+}
+
+/// Errors returned by `open`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenError {
+    /// The key couldn't be recovered from the shares.
+    Recover(RecoverError),
+    /// The recovered key doesn't authenticate the ciphertext, because
+    /// the shares are from another call to `seal`, or the ciphertext
+    /// or shares were modified.
+    Authentication,
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::Recover(e) => write!(f, "Failed to recover the key: {}", e),
+            OpenError::Authentication => write!(f, "Ciphertext failed authentication"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OpenError {}
+
+impl From<RecoverError> for OpenError {
+    fn from(e: RecoverError) -> Self {
+        OpenError::Recover(e)
+    }
+}
+
+/// Encrypts `plaintext` under a random key, and deals `n` shares of
+/// the key with `threshold`. Any `threshold` of the shares can `open`
+/// the returned ciphertext.
+///
+/// Example:
+/// ```
+/// # use sharks::{ open, seal };
+/// # use rand_chacha::rand_core::SeedableRng;
+/// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+/// let (shares, ciphertext) = seal(b"payload", 3, 5, &mut rng);
+/// assert_eq!(open(&shares[2..], &ciphertext).unwrap(), b"payload");
+pub fn seal<R: rand::RngCore + rand::CryptoRng>(
+    plaintext: &[u8],
+    threshold: u32,
+    n: usize,
+    rng: &mut R,
+) -> (Vec<Share>, Ciphertext) {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    rng.fill_bytes(&mut *key);
+    let shares = Sharks(threshold)
+        .dealer_rng(&Secret::from_bytes(&*key), rng)
+        .take(n)
+        .collect();
+
+    let mut s = transcript(&*key);
+    let mut body = plaintext.to_vec();
+    s.send_enc(&mut body, false);
+    let mut mac = [0u8; MAC_LEN];
+    s.send_mac(&mut mac, false);
+    (shares, Ciphertext { body, mac })
+}
+
+/// Recovers the key from `shares`, which must include a threshold of
+/// those returned by `seal`, and decrypts `ciphertext` with it.
+pub fn open(shares: &[Share], ciphertext: &Ciphertext) -> Result<Vec<u8>, OpenError> {
+    let threshold = shares
+        .first()
+        .ok_or(RecoverError::NotEnoughShares)?
+        .threshold;
+    let key = Zeroizing::new(Sharks(threshold).recover(shares)?.into_bytes());
+
+    let mut s = transcript(&key);
+    let mut plaintext = ciphertext.body.clone();
+    s.recv_enc(&mut plaintext, false);
+    let mut mac = ciphertext.mac;
+    s.recv_mac(&mut mac)
+        .map_err(|_| OpenError::Authentication)?;
+    Ok(plaintext)
+}
+
+fn transcript(key: &[u8]) -> Strobe {
+    let mut s = Strobe::new(b"sharks seal", SecParam::B128);
+    s.key(key, false);
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open, seal, Ciphertext, OpenError, MAC_LEN};
+    use crate::RecoverError;
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_seal_open() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let plaintext = b"the most common real application";
+        let (shares, ciphertext) = seal(plaintext, 3, 5, &mut rng);
+        assert_eq!(shares.len(), 5);
+        assert_eq!(open(&shares[..3], &ciphertext).unwrap(), plaintext);
+        assert_eq!(open(&shares[1..], &ciphertext).unwrap(), plaintext);
+
+        let bytes = Vec::from(&ciphertext);
+        assert_eq!(bytes.len(), plaintext.len() + MAC_LEN);
+        assert_eq!(Ciphertext::try_from(&bytes[..]), Ok(ciphertext.clone()));
+        assert!(Ciphertext::try_from(&bytes[..MAC_LEN - 1]).is_err());
+
+        let (_, empty) = seal(&[], 2, 2, &mut rng);
+        assert_eq!(Vec::from(&empty).len(), MAC_LEN);
+    }
+
+    #[test]
+    fn test_open_errors() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let (shares, ciphertext) = seal(b"payload", 3, 5, &mut rng);
+        assert_eq!(
+            open(&shares[..2], &ciphertext),
+            Err(OpenError::Recover(RecoverError::NotEnoughShares))
+        );
+        assert_eq!(
+            open(&[], &ciphertext),
+            Err(OpenError::Recover(RecoverError::NotEnoughShares))
+        );
+
+        // Shares of another key, or a modified ciphertext, don't
+        // authenticate
+        let (other, _) = seal(b"payload", 3, 5, &mut rng);
+        assert_eq!(open(&other, &ciphertext), Err(OpenError::Authentication));
+        let mut bytes = Vec::from(&ciphertext);
+        bytes[0] ^= 1;
+        let tampered = Ciphertext::try_from(&bytes[..]).unwrap();
+        assert_eq!(open(&shares, &tampered), Err(OpenError::Authentication));
+    }
+}
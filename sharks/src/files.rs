@@ -0,0 +1,133 @@
+//! Writing shares to, and recovering them from, a directory of files,
+//! one share per file. Only available with the `std` feature.
+//!
+//! Shares are written in their serialized form to `share-1.bin`,
+//! `share-2.bin` and so on. When recovering, every `share-*.bin` file in
+//! the directory is read, so shares can be copied in from wherever they
+//! were kept, under any number.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::vec::Vec;
+
+use crate::{RecoverError, Secret, Share, Sharks};
+
+/// The result of `Sharks::recover_from_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirRecovery {
+    /// The recovered secret, or the reason it couldn't be recovered
+    /// from the valid shares.
+    pub secret: Result<Secret, RecoverError>,
+    /// The number of files which held a share.
+    pub valid: usize,
+    /// The number of share files which couldn't be read, or didn't hold
+    /// a share, and of directory entries which couldn't be read.
+    pub skipped: usize,
+}
+
+impl Sharks {
+    /// Deals `n` shares of `secret` and writes them to `share-1.bin` to
+    /// `share-n.bin` in `dir`, which must exist. Existing files with the
+    /// same names are overwritten. On unix, the files are only readable
+    /// and writable by their owner.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// # let dir = std::env::temp_dir().join(format!("sharks-doctest-{}", std::process::id()));
+    /// # std::fs::create_dir_all(&dir).unwrap();
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// sharks.split_into_dir_rng(&Secret::from_bytes(&[1, 2]), 4, &dir, &mut rng).unwrap();
+    /// std::fs::remove_file(dir.join("share-2.bin")).unwrap();
+    /// let recovery = sharks.recover_from_dir(&dir).unwrap();
+    /// assert_eq!(recovery.secret, Ok(Secret::from_bytes(&[1, 2])));
+    /// assert_eq!(recovery.valid, 3);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    pub fn split_into_dir_rng<P: AsRef<Path>, R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        n: usize,
+        dir: P,
+        rng: &mut R,
+    ) -> io::Result<()> {
+        for (i, share) in self.dealer_rng(secret, rng).take(n).enumerate() {
+            let path = dir.as_ref().join(format!("share-{}.bin", i + 1));
+            write_share_file(&path, &Vec::from(&share))?;
+        }
+        Ok(())
+    }
+
+    /// Like `split_into_dir_rng`, but uses `rand::thread_rng`.
+    #[cfg(not(feature = "no-thread-rng"))]
+    pub fn split_into_dir<P: AsRef<Path>>(
+        &self,
+        secret: &Secret,
+        n: usize,
+        dir: P,
+    ) -> io::Result<()> {
+        self.split_into_dir_rng(secret, n, dir, &mut rand::thread_rng())
+    }
+
+    /// Reads the shares from the `share-*.bin` files in `dir`, and
+    /// recovers the secret from them if there are enough. Directory
+    /// entries which can't be read, and files which can't be read or
+    /// parsed, are skipped and counted. Only failing to open `dir`
+    /// itself is an error.
+    ///
+    /// Parsing only catches files of the wrong length or format: a share
+    /// which was corrupted in place is still used, and recovers a wrong
+    /// secret. Deal with `verifiable_dealer_rng` or
+    /// `dealer_rng_with_checksum` to detect this.
+    pub fn recover_from_dir<P: AsRef<Path>>(&self, dir: P) -> io::Result<DirRecovery> {
+        let mut shares: Vec<Share> = Vec::new();
+        let mut skipped = 0;
+        for entry in fs::read_dir(dir)? {
+            // An entry we can't read might be a share, so it counts as
+            // skipped rather than ending the recovery.
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(_) => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) if name.starts_with("share-") && name.ends_with(".bin") => {}
+                _ => continue,
+            }
+            match fs::read(&path)
+                .ok()
+                .and_then(|bytes| Share::try_from(bytes.as_slice()).ok())
+            {
+                Some(share) => shares.push(share),
+                None => skipped += 1,
+            }
+        }
+        Ok(DirRecovery {
+            secret: self.recover(&shares),
+            valid: shares.len(),
+            skipped,
+        })
+    }
+}
+
+// Writes `bytes` to the file at `path`, creating it or replacing its
+// contents. On unix the file is made private to its owner, including
+// when it already existed with wider permissions, so that a share is
+// never readable by other users.
+fn write_share_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(bytes)
+}
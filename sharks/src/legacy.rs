@@ -0,0 +1,221 @@
+//! Interoperability with shares from the GF(256) based versions of this
+//! crate (0.4 and earlier).
+//!
+//! Those versions share each byte of the secret separately over GF(256),
+//! reduced by the AES polynomial `x^8 + x^4 + x^3 + x + 1`, and serialize
+//! a share as its `x` coordinate followed by one `y` byte per secret
+//! byte.
+//!
+//! Polynomials over GF(256) can't be mapped to polynomials over `Fp`, so
+//! legacy shares can't be converted one by one. Instead a threshold of
+//! them is recovered with `recover_legacy`, and the secret can then be
+//! dealt again over `Fp` with `migrate_legacy_rng`. The secret itself is
+//! carried over exactly: the only loss is in the size of the field,
+//! which limits legacy dealings to 255 shares.
+
+use alloc::vec::Vec;
+use hashbrown::HashSet;
+
+use crate::share_ff::Evaluator;
+use crate::{RecoverError, Secret, Sharks};
+
+/// A share dealt over GF(256), in the format of sharks 0.4.
+///
+/// Example:
+/// ```
+/// # use sharks::LegacyShare;
+/// # use core::convert::TryFrom;
+/// let share = LegacyShare::try_from(&[1u8, 0xab, 0xcd][..]).unwrap();
+/// assert_eq!(share.x, 1);
+/// assert_eq!(Vec::from(&share), vec![1, 0xab, 0xcd]);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LegacyShare {
+    pub x: u8,
+    pub y: Vec<u8>,
+}
+
+/// Obtains a byte vector from a `LegacyShare` instance
+impl From<&LegacyShare> for Vec<u8> {
+    fn from(s: &LegacyShare) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(s.y.len() + 1);
+        bytes.push(s.x);
+        bytes.extend(&s.y);
+        bytes
+    }
+}
+
+/// Obtains a `LegacyShare` instance from a byte slice
+impl core::convert::TryFrom<&[u8]> for LegacyShare {
+    type Error = &'static str;
+
+    fn try_from(s: &[u8]) -> Result<LegacyShare, Self::Error> {
+        if s.len() < 2 {
+            Err("A LegacyShare must be at least 2 bytes long")
+        } else if s[0] == 0 {
+            Err("A LegacyShare must have a non-zero x coordinate")
+        } else {
+            Ok(LegacyShare {
+                x: s[0],
+                y: s[1..].to_vec(),
+            })
+        }
+    }
+}
+
+impl Sharks {
+    /// Recovers a byte secret from shares dealt by sharks 0.4 or earlier
+    /// with this threshold. Duplicate shares are ignored, as in `recover`.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ LegacyShare, Sharks };
+    /// # use core::convert::TryFrom;
+    /// // Shares of [1, 2, 3] with threshold 2
+    /// let shares: Vec<LegacyShare> = [[2u8, 0xb6, 0x44, 0xf5], [3, 0x60, 0x67, 0x8e]]
+    ///     .iter()
+    ///     .map(|s| LegacyShare::try_from(&s[..]).unwrap())
+    ///     .collect();
+    /// assert_eq!(Sharks(2).recover_legacy(&shares).unwrap().as_bytes(), vec![1, 2, 3]);
+    pub fn recover_legacy(&self, shares: &[LegacyShare]) -> Result<Secret, RecoverError> {
+        let mut keys: HashSet<u8> = HashSet::new();
+        let mut values: Vec<&LegacyShare> = Vec::new();
+        for share in shares {
+            if share.y.len() != shares[0].y.len() {
+                return Err(RecoverError::InconsistentShareLength);
+            } else if keys.insert(share.x) {
+                values.push(share);
+            }
+        }
+        if values.is_empty() || values.len() < self.0 as usize {
+            return Err(RecoverError::NotEnoughShares);
+        }
+        let values = &values[..self.0 as usize];
+
+        // The Lagrange basis at zero, where subtraction is addition
+        let basis: Vec<u8> = values
+            .iter()
+            .map(|s_i| {
+                let (num, den) = values
+                    .iter()
+                    .filter(|s_j| s_j.x != s_i.x)
+                    .fold((1, 1), |(num, den), s_j| {
+                        (gf_mul(num, s_j.x), gf_mul(den, s_j.x ^ s_i.x))
+                    });
+                gf_mul(num, gf_inv(den))
+            })
+            .collect();
+        let secret: Vec<u8> = (0..values[0].y.len())
+            .map(|i| {
+                values
+                    .iter()
+                    .zip(&basis)
+                    .fold(0, |acc, (s, &b)| acc ^ gf_mul(b, s.y[i]))
+            })
+            .collect();
+        Ok(Secret::from(secret))
+    }
+
+    /// Recovers a byte secret from legacy shares as in `recover_legacy`,
+    /// and deals it again over `Fp`, returning the `Evaluator` for the
+    /// new shares.
+    pub fn migrate_legacy_rng<R: rand::Rng>(
+        &self,
+        shares: &[LegacyShare],
+        rng: &mut R,
+    ) -> Result<Evaluator, RecoverError> {
+        let secret = self.recover_legacy(shares)?;
+        Ok(self.dealer_rng(&secret, rng))
+    }
+}
+
+// Multiplies in GF(256), reducing by the AES polynomial.
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a, b, 0u8);
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = a >> 7;
+        a = (a << 1) ^ (0x1b & carry.wrapping_neg());
+        b >>= 1;
+    }
+    product
+}
+
+// Inverts a non-zero element of GF(256), as `a^254`.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 {
+        result = gf_mul(result, a);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gf_inv, gf_mul, LegacyShare};
+    use crate::{RecoverError, Sharks};
+    use alloc::{vec, vec::Vec};
+    use core::convert::TryFrom;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    // Shares of "legacy" with threshold 3, in the sharks 0.4 format
+    const LEGACY_SHARES: [[u8; 7]; 4] = [
+        [1, 0x84, 0xb7, 0x72, 0xc7, 0x1d, 0x8b],
+        [2, 0x12, 0x8a, 0xed, 0x78, 0xf5, 0x74],
+        [3, 0xfa, 0x58, 0xf8, 0xde, 0x8b, 0x86],
+        [4, 0x72, 0xfb, 0xde, 0x70, 0xe7, 0x86],
+    ];
+
+    fn legacy_shares() -> Vec<LegacyShare> {
+        LEGACY_SHARES
+            .iter()
+            .map(|s| LegacyShare::try_from(&s[..]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_gf_arithmetic() {
+        // The worked example of FIPS-197 section 4.2
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        assert!((1..=255).all(|a| gf_mul(a, gf_inv(a)) == 1));
+    }
+
+    #[test]
+    fn test_recover_legacy() {
+        let sharks = Sharks(3);
+        let shares = legacy_shares();
+        assert_eq!(
+            sharks.recover_legacy(&shares).unwrap().as_bytes(),
+            b"legacy"
+        );
+        assert_eq!(
+            sharks.recover_legacy(&shares[1..]).unwrap().as_bytes(),
+            b"legacy"
+        );
+        let repeated = [&shares[..2], &shares[..2]].concat();
+        assert_eq!(
+            sharks.recover_legacy(&repeated),
+            Err(RecoverError::NotEnoughShares)
+        );
+
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let migrated: Vec<_> = sharks
+            .migrate_legacy_rng(&shares, &mut rng)
+            .unwrap()
+            .take(3)
+            .collect();
+        assert_eq!(sharks.recover(&migrated).unwrap().as_bytes(), b"legacy");
+    }
+
+    #[test]
+    fn test_legacy_share_bytes() {
+        let share = legacy_shares().remove(0);
+        assert_eq!(Vec::from(&share), LEGACY_SHARES[0].to_vec());
+        assert!(LegacyShare::try_from(&[1u8][..]).is_err());
+        assert!(LegacyShare::try_from(&[0u8, 1][..]).is_err());
+        assert_eq!(
+            LegacyShare::try_from(&[5u8, 1][..]),
+            Ok(LegacyShare { x: 5, y: vec![1] })
+        );
+    }
+}
@@ -7,90 +7,457 @@ extern crate alloc;
 
 // implement operations using a larger finite field as well
 extern crate ff;
+mod archive;
+mod byte_order;
+mod consistency;
+mod constant_time;
+#[cfg(feature = "vss")]
+mod dkg;
+#[cfg(feature = "threshold-encryption")]
+mod encryption;
+#[cfg(feature = "std")]
+mod files;
+mod legacy;
+mod merkle;
+mod migrate;
+mod namespace;
+pub mod prelude;
+#[cfg(feature = "profile")]
+pub mod profile;
+mod qr;
+mod recovery;
+mod seeded;
 mod share_ff;
+mod single;
+mod sparse;
+#[cfg(feature = "vss")]
+mod vss;
 
 use alloc::vec::Vec;
 use core::convert::TryInto;
+use core::fmt;
 use hashbrown::HashSet;
 
-use crate::ff::PrimeField;
+use crate::ff::{Field, PrimeField};
+pub use archive::{ShareSet, ARCHIVE_CHECKSUM_LEN, ARCHIVE_MAGIC};
+pub use byte_order::MAX_AMBIGUOUS_SHARES;
+pub use consistency::Consistency;
+#[cfg(feature = "vss")]
+pub use dkg::{group_commitments, DkgError, DkgParticipant};
+#[cfg(feature = "threshold-encryption")]
+pub use encryption::{open, seal, Ciphertext, OpenError, KEY_LEN, MAC_LEN};
+#[cfg(feature = "std")]
+pub use files::DirRecovery;
+pub use legacy::LegacyShare;
+pub use merkle::{hash_commit, MerklePath, MERKLE_HASH_LEN};
+pub use namespace::namespace_id;
+pub use qr::{QR_CHECKSUM_LEN, QR_PAYLOAD_PREFIX};
+pub use recovery::Recovery;
+pub use seeded::SEED_LEN;
 pub use share_ff::Evaluator;
-pub use share_ff::Share;
-pub use share_ff::{get_evaluator, interpolate, random_polynomial};
+pub use share_ff::{decode_secret, encode_secret, MAX_SECRET_LEN, SECRET_CHUNK_LEN};
+use share_ff::{decode_secret_array, interpolate_at_with_ctx, interpolate_elements};
+pub use share_ff::{
+    eval_polynomial, get_evaluator, interpolate, interpolate_at, interpolate_refs,
+    lagrange_coefficients, random_polynomial,
+};
+pub use share_ff::{interpolate_checked, interpolate_with_ctx, InterpolationCtx};
+use share_ff::{try_random_polynomial, zeroize_elements};
 pub use share_ff::{Fp, FpRepr, FIELD_ELEMENT_LEN};
+pub use share_ff::{Share, NAMESPACE_LEN, SHARE_HEADER_LEN};
+pub use single::SingleEvaluator;
+pub use sparse::SparseShare;
+#[cfg(feature = "vss")]
+pub use vss::{Commitments, COMMITMENT_LEN};
 
-/// Tuple struct which implements methods to generate shares and recover secrets over a 256 bits Galois Field.
+/// Common interface for operations which consume randomness, defined
+/// in the `randomized` crate and shared with `adss` and `ppoprf`.
+///
+/// Example:
+/// ```
+/// # use sharks::{ Randomized, Secret, Sharks, Share };
+/// # use rand_chacha::rand_core::SeedableRng;
+/// # let sharks = Sharks(3);
+/// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+/// // Deal the secret [1, 2] and sample a share at a random point
+/// let evaluator = sharks.dealing(&Secret::from_bytes(&[1, 2])).with_rng(&mut rng);
+/// let share: Share = evaluator.with_rng(&mut rng);
+pub use randomized::Randomized;
+
+/// Runs any `Randomized` operation with `rand::thread_rng`, as a
+/// convenience under `std`, unless the `no-thread-rng` feature is
+/// enabled.
+#[cfg(all(feature = "std", not(feature = "no-thread-rng")))]
+pub trait ThreadRandomized: Randomized {
+    fn with_thread_rng(&self) -> Self::Output {
+        self.with_rng(&mut rand::thread_rng())
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "no-thread-rng")))]
+impl<T: Randomized + ?Sized> ThreadRandomized for T {}
+
+/// Tuple struct which implements methods to generate shares and recover secrets over a 255 bit prime field.
 /// Its only parameter is the minimum shares threshold.
 pub struct Sharks(pub u32);
 
+/// A secret to be shared with `Sharks`, as taken by the dealers and
+/// returned by recovery.
+///
+/// Secrets are dealt as a sequence of field elements. `Secret::from_bytes`
+/// accepts a secret of any length, which is padded to a whole number of
+/// field elements when dealt (see `encode_secret`), while
+/// `Secret::from_field_aligned` only accepts secrets which are already a
+/// multiple of `FIELD_ELEMENT_LEN` bytes long. In both cases `recover`
+/// returns exactly the original bytes. The bytes are left out of the
+/// `Debug` output, and are zeroized when the secret is dropped.
+///
+/// Example:
+/// ```
+/// # use sharks::{ Secret, FIELD_ELEMENT_LEN };
+/// let secret = Secret::from_bytes(&[1, 2]);
+/// assert_eq!(format!("{:?}", secret), "Secret(<redacted>)");
+/// assert_eq!(secret.into_bytes(), vec![1, 2]);
+/// // Aligned secrets must be whole field elements
+/// assert!(Secret::from_field_aligned(&[1, 2]).is_err());
+/// assert!(Secret::from_field_aligned(&[1; 2 * FIELD_ELEMENT_LEN]).is_ok());
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    /// Creates a secret from a byte slice of any length. The secret is
+    /// padded to a whole number of field elements when it is dealt.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Secret(bytes.to_vec())
+    }
+
+    /// Creates a secret from a byte slice whose length is a multiple of
+    /// `FIELD_ELEMENT_LEN`. Returns an `Err` otherwise.
+    pub fn from_field_aligned(bytes: &[u8]) -> Result<Self, &'static str> {
+        if !bytes.chunks_exact(FIELD_ELEMENT_LEN).remainder().is_empty() {
+            return Err("Secret length must be a multiple of FIELD_ELEMENT_LEN");
+        }
+        Ok(Secret(bytes.to_vec()))
+    }
+
+    /// Returns the secret bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Consumes the secret, returning its bytes. The caller becomes
+    /// responsible for zeroizing them.
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        core::mem::take(&mut self.0)
+    }
+}
+
+impl From<&[u8]> for Secret {
+    fn from(bytes: &[u8]) -> Self {
+        Secret::from_bytes(bytes)
+    }
+}
+
+impl From<Vec<u8>> for Secret {
+    fn from(bytes: Vec<u8>) -> Self {
+        Secret(bytes)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+impl Drop for Secret {
+    // As with `zeroize_elements`, the writes can only be relied on with
+    // the `zeroize_memory` feature.
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize_memory")]
+        zeroize::Zeroize::zeroize(&mut self.0);
+        #[cfg(not(feature = "zeroize_memory"))]
+        for byte in self.0.iter_mut() {
+            *byte = 0;
+        }
+    }
+}
+
+/// A pending dealing of `secret` with the threshold of `sharks`,
+/// created by `Sharks::dealing`. Supplying randomness via
+/// `Randomized::with_rng` produces the share `Evaluator`.
+pub struct Dealing<'a> {
+    sharks: &'a Sharks,
+    secret: &'a Secret,
+}
+
+impl<'a> Randomized for Dealing<'a> {
+    type Output = Evaluator;
+
+    fn with_rng<R: rand::RngCore + rand::CryptoRng>(&self, rng: &mut R) -> Evaluator {
+        self.sharks
+            .deal_elements(encode_secret(self.secret.as_bytes()), rng)
+    }
+}
+
 impl Sharks {
     /// This method is useful when `std` is not available. For typical usage
     /// see the `dealer` method.
     ///
-    /// Given a `secret` byte slice, returns an `Iterator` along new shares.
-    /// The maximum number of shares that can be generated is 256.
+    /// Given a `Secret`, returns an `Iterator` along new shares.
+    /// The shares are at distinct `x` coordinates; see
+    /// `Evaluator::remaining_shares` for how many can be generated.
     /// A random number generator has to be provided.
     ///
+    /// The secret is packed into field elements with `encode_secret`, so
+    /// that `recover` returns exactly the original bytes.
+    ///
     /// Example:
     /// ```
-    /// # use sharks::{ Sharks, Share };
+    /// # use sharks::{ Secret, Sharks, Share };
     /// # use rand_chacha::rand_core::SeedableRng;
     /// # let sharks = Sharks(3);
     /// // Obtain an iterator over the shares for secret [1, 2]
     /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
-    /// let dealer = sharks.dealer_rng(&[1, 2], &mut rng);
+    /// let dealer = sharks.dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng);
     /// // Get 3 shares
     /// let shares: Vec<Share> = dealer.take(3).collect();
     pub fn dealer_rng<R: rand::Rng>(
         &self,
-        secret: &[u8],
+        secret: &Secret,
         rng: &mut R,
         //) -> impl Iterator<Item = Share> {
     ) -> Evaluator {
-        let mut polys = Vec::with_capacity(secret.len());
+        self.deal_elements(encode_secret(secret.as_bytes()), rng)
+    }
+
+    /// Like `dealer_rng`, but returns a `DealError::RngFailure` error if
+    /// the random number generator fails, rather than panicking as
+    /// `rand::Rng` does. Hardware generators on embedded targets can
+    /// fail, for instance before they have gathered enough entropy.
+    ///
+    /// The polynomial coefficients are drawn differently, so the shares
+    /// differ from those `dealer_rng` deals with the same generator.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let dealer = sharks.try_dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng).unwrap();
+    /// let shares: Vec<Share> = dealer.take(3).collect();
+    /// assert_eq!(sharks.recover(&shares).unwrap().as_bytes(), vec![1, 2]);
+    pub fn try_dealer_rng<R: rand::RngCore>(
+        &self,
+        secret: &Secret,
+        rng: &mut R,
+    ) -> Result<Evaluator, DealError> {
+        let mut polys = Vec::new();
+        let mut elements = encode_secret(secret.as_bytes());
+        for element in &elements {
+            match try_random_polynomial(*element, self.0, rng) {
+                Some(poly) => polys.push(poly),
+                None => {
+                    for poly in &mut polys {
+                        zeroize_elements(poly);
+                    }
+                    zeroize_elements(&mut elements);
+                    return Err(DealError::RngFailure);
+                }
+            }
+        }
+        zeroize_elements(&mut elements);
+        let mut evaluator = get_evaluator(polys);
+        evaluator.threshold = self.0;
+        Ok(evaluator)
+    }
+
+    // Shares each of the field `elements` with a random polynomial.
+    fn deal_elements<R: rand::Rng>(&self, elements: Vec<Fp>, rng: &mut R) -> Evaluator {
+        let polys = elements
+            .into_iter()
+            .map(|element| random_polynomial(element, self.0, rng))
+            .collect();
+
+        let mut evaluator = get_evaluator(polys);
+        evaluator.threshold = self.0;
+        evaluator
+    }
+
+    /// Like `dealer_rng`, but additionally shares a checksum of the
+    /// secret as one extra field element chunk, so that
+    /// `recover_with_checksum` can detect when shares of different
+    /// secrets have been mixed. Shares are one field element larger
+    /// than those produced by `dealer_rng`.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// # let secret = Secret::from_bytes(&[1u8; 32]);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let dealer = sharks.dealer_rng_with_checksum(&secret, &mut rng);
+    /// let shares: Vec<Share> = dealer.take(3).collect();
+    /// assert_eq!(sharks.recover_with_checksum(&shares).unwrap(), secret);
+    pub fn dealer_rng_with_checksum<R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        rng: &mut R,
+    ) -> Evaluator {
+        let mut elements = encode_secret(secret.as_bytes());
+        elements.push(secret_checksum(&elements));
+        self.deal_elements(elements, rng)
+    }
 
-        let secret_fp_len = secret.len() / FIELD_ELEMENT_LEN;
-        for i in 0..secret_fp_len {
-            let element = Fp::from_repr(FpRepr(
-                secret[i * FIELD_ELEMENT_LEN..(i + 1) * FIELD_ELEMENT_LEN]
-                    .try_into()
-                    .expect("bad chunk"),
-            ))
-            .unwrap();
-            polys.push(random_polynomial(element, self.0, rng));
+    /// Deals `n` shares of `secret` into `out`, replacing its contents
+    /// with what `dealer_rng(secret, rng).take(n).collect()` would
+    /// return. The shares already in `out` are overwritten in place, so
+    /// dealing many secrets to the same `n` shareholders with one buffer
+    /// only allocates the polynomials for each secret, not the shares.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let mut shares = Vec::new();
+    /// for secret in &[[1, 2], [3, 4]] {
+    ///     sharks.dealer_into(&Secret::from_bytes(secret), 5, &mut rng, &mut shares);
+    ///     assert_eq!(sharks.recover(&shares[..3]).unwrap().as_bytes(), &secret[..]);
+    /// }
+    pub fn dealer_into<R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        n: usize,
+        rng: &mut R,
+        out: &mut Vec<Share>,
+    ) {
+        let evaluator = self.dealer_rng(secret, rng);
+        out.truncate(n);
+        let mut x = Fp::zero();
+        for share in out.iter_mut() {
+            x += Fp::one();
+            evaluator.evaluate_into(x, share);
         }
+        out.extend((out.len()..n).map(|_| {
+            x += Fp::one();
+            evaluator.evaluate(x)
+        }));
+    }
 
-        get_evaluator(polys)
+    /// Deals shares of `secret` to parties with the given `weights`,
+    /// returning one block of shares per party.
+    ///
+    /// Party `i` receives `weights[i]` ordinary shares, with contiguous
+    /// `x` coordinates following those of party `i - 1`: the first party
+    /// holds `x = 1..=weights[0]`, the second party
+    /// `x = weights[0] + 1..=weights[0] + weights[1]`, and so on. A set
+    /// of parties can then recover the secret whenever the sum of
+    /// their weights meets the threshold.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let secret = Secret::from_bytes(&[1, 2]);
+    /// let parties = sharks.weighted_dealer_rng(&secret, &[2, 1, 1], &mut rng);
+    /// assert_eq!(parties[0].len(), 2);
+    /// // The first two parties have a combined weight of 3
+    /// assert!(sharks.weighted_recover(&parties[..2]).is_ok());
+    pub fn weighted_dealer_rng<R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        weights: &[u32],
+        rng: &mut R,
+    ) -> Vec<Vec<Share>> {
+        let mut dealer = self.deal_elements(encode_secret(secret.as_bytes()), rng);
+        weights
+            .iter()
+            .map(|&w| dealer.by_ref().take(w as usize).collect())
+            .collect()
+    }
+
+    /// Like `weighted_dealer_rng`, using the thread random number
+    /// generator.
+    #[cfg(all(feature = "std", not(feature = "no-thread-rng")))]
+    pub fn weighted_dealer(&self, secret: &Secret, weights: &[u32]) -> Vec<Vec<Share>> {
+        let mut rng = rand::thread_rng();
+        self.weighted_dealer_rng(secret, weights, &mut rng)
+    }
+
+    /// Recovers the secret from the share blocks of the parties present,
+    /// as dealt by `weighted_dealer_rng`. Recovery succeeds if the sum
+    /// of the weights of the parties, i.e. the number of distinct shares
+    /// they hold, meets the threshold.
+    pub fn weighted_recover(&self, parties: &[Vec<Share>]) -> Result<Secret, RecoverError> {
+        self.recover(parties.iter().flatten())
+    }
+
+    /// Returns a `Dealing` of `secret` which can be evaluated with any
+    /// random number generator through the `Randomized` trait.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Randomized, Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let dealer = sharks.dealing(&Secret::from_bytes(&[1, 2])).with_rng(&mut rng);
+    /// // Get 3 shares
+    /// let shares: Vec<Share> = dealer.take(3).collect();
+    pub fn dealing<'a>(&'a self, secret: &'a Secret) -> Dealing<'a> {
+        Dealing {
+            sharks: self,
+            secret,
+        }
     }
 
-    /// Given a `secret` byte slice, returns an `Iterator` along new shares.
-    /// The maximum number of shares that can be generated is 256.
+    /// Given a `Secret`, returns an `Iterator` along new shares.
+    /// The shares are at distinct `x` coordinates; see
+    /// `Evaluator::remaining_shares` for how many can be generated.
     ///
     /// Example:
     /// ```
-    /// # use sharks::{ Sharks, Share };
+    /// # use sharks::{ Secret, Sharks, Share };
     /// # let sharks = Sharks(3);
     /// // Obtain an iterator over the shares for secret [1, 2]
-    /// let dealer = sharks.dealer(&[1, 2]);
+    /// let dealer = sharks.dealer(&Secret::from_bytes(&[1, 2]));
     /// // Get 3 shares
     /// let shares: Vec<Share> = dealer.take(3).collect();
-    #[cfg(feature = "std")]
-    pub fn dealer(&self, secret: &[u8]) -> Evaluator {
+    #[cfg(all(feature = "std", not(feature = "no-thread-rng")))]
+    pub fn dealer(&self, secret: &Secret) -> Evaluator {
+        self.dealing(secret).with_thread_rng()
+    }
+
+    /// Like `dealer`, but embeds a checksum of the secret in the shares.
+    /// See `dealer_rng_with_checksum`.
+    #[cfg(all(feature = "std", not(feature = "no-thread-rng")))]
+    pub fn dealer_with_checksum(&self, secret: &Secret) -> Evaluator {
         let mut rng = rand::thread_rng();
-        self.dealer_rng(secret, &mut rng)
+        self.dealer_rng_with_checksum(secret, &mut rng)
     }
 
     /// Given an iterable collection of shares, recovers the original secret.
     /// If the number of distinct shares is less than the minimum threshold an `Err` is returned,
     /// otherwise an `Ok` containing the secret.
     ///
+    /// Each share records the threshold it was dealt with. If it
+    /// differs from the threshold of this `Sharks` instance, a
+    /// `RecoverError::ThresholdMismatch` is returned instead of
+    /// interpolating with the wrong number of shares.
+    ///
     /// Example:
     /// ```
-    /// # use sharks::{ Sharks, Share };
+    /// # use sharks::{ Secret, Sharks, Share };
     /// # use rand_chacha::rand_core::SeedableRng;
     /// # let sharks = Sharks(3);
     /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
-    /// # let mut shares: Vec<Share> = sharks.dealer_rng(&[1], &mut rng).take(3).collect();
+    /// # let mut shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(3).collect();
     /// // Recover original secret from shares
     /// let mut secret = sharks.recover(&shares);
     /// // Secret correctly recovered
@@ -100,54 +467,577 @@ impl Sharks {
     /// secret = sharks.recover(&shares);
     /// // Not enough shares to recover secret
     /// assert!(secret.is_err());
-    pub fn recover<'a, T>(&self, shares: T) -> Result<Vec<u8>, &str>
+    pub fn recover<'a, T>(&self, shares: T) -> Result<Secret, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        self.recover_with_ctx(&mut InterpolationCtx::new(), shares)
+    }
+
+    /// As `recover`, but borrows the interpolation scratch space from
+    /// `ctx`, so that recovering many secrets with the same context
+    /// only allocates when a set has more shares or chunks than any
+    /// before it. See `InterpolationCtx`.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ InterpolationCtx, Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(2);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let mut ctx = InterpolationCtx::new();
+    /// for secret in [[1, 2], [3, 4]] {
+    ///     let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&secret), &mut rng).take(2).collect();
+    ///     assert_eq!(sharks.recover_with_ctx(&mut ctx, &shares).unwrap().as_bytes(), secret);
+    /// }
+    pub fn recover_with_ctx<'a, T>(
+        &self,
+        ctx: &mut InterpolationCtx,
+        shares: T,
+    ) -> Result<Secret, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let values = self.distinct_shares(shares)?;
+        // We only need the threshold number of shares to recover
+        let elements = interpolate_at_with_ctx(ctx, &values[0..self.0 as usize], Fp::zero())?;
+        decode_secret(elements)
+            .map(Secret)
+            .ok_or(RecoverError::InvalidEncoding)
+    }
+
+    /// Like `recover`, but returns the secret as an array of `N` bytes,
+    /// for secrets of a fixed length such as keys. The secret is decoded
+    /// straight into the array, rather than through a `Vec`.
+    /// Returns `SecretLengthMismatch` if the recovered secret has a
+    /// different length.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(2);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[7; 32]), &mut rng).take(2).collect();
+    /// let key: [u8; 32] = sharks.recover_array(&shares).unwrap();
+    /// assert_eq!(key, [7; 32]);
+    pub fn recover_array<'a, T, const N: usize>(&self, shares: T) -> Result<[u8; N], RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let values = self.distinct_shares(shares)?;
+        let mut ctx = InterpolationCtx::new();
+        let elements = interpolate_at_with_ctx(&mut ctx, &values[0..self.0 as usize], Fp::zero())?;
+        decode_secret_array(elements)
+    }
+
+    /// Like `recover`, but reads the shares from several sources in
+    /// turn, such as one per channel they are gathered from. Duplicates
+    /// are ignored across all of the sources, and nothing is collected
+    /// beyond what `recover` itself needs.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(3).collect();
+    /// let sources = [&shares[..2], &shares[1..]];
+    /// assert_eq!(sharks.recover_from_iters(sources.iter().copied()).unwrap().as_bytes(), vec![1]);
+    pub fn recover_from_iters<'a, I, S>(&self, iters: I) -> Result<Secret, RecoverError>
+    where
+        I: IntoIterator<Item = S>,
+        S: IntoIterator<Item = &'a Share>,
+    {
+        self.recover(iters.into_iter().flatten())
+    }
+
+    /// Like `recover`, but lets the caller choose which of the distinct
+    /// shares are used for reconstruction. The `selector` is passed the
+    /// distinct shares (in the order they were first seen), and must
+    /// return the indices of exactly `threshold` of them. Otherwise an
+    /// `InvalidSelection` error is returned.
+    ///
+    /// `recover` is equivalent to selecting the first `threshold`
+    /// distinct shares.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// # let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(5).collect();
+    /// // Recover using the last three shares
+    /// let secret = sharks.recover_with(&shares, |s| (s.len() - 3..s.len()).collect());
+    /// assert_eq!(secret.unwrap().as_bytes(), vec![1]);
+    pub fn recover_with<'a, T, F>(&self, shares: T, selector: F) -> Result<Secret, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+        F: Fn(&[&Share]) -> Vec<usize>,
+    {
+        let values = self.distinct_shares(shares)?;
+        let mut indices = selector(&values);
+        if indices.len() != self.0 as usize {
+            return Err(RecoverError::InvalidSelection);
+        }
+        let selected: Vec<&Share> = indices
+            .iter()
+            .map(|&i| values.get(i).copied().ok_or(RecoverError::InvalidSelection))
+            .collect::<Result<_, _>>()?;
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.len() != selected.len() {
+            return Err(RecoverError::InvalidSelection);
+        }
+        let mut elements = interpolate_elements(&selected)?;
+        let secret = decode_secret(&elements).ok_or(RecoverError::InvalidEncoding);
+        zeroize_elements(&mut elements);
+        secret.map(Secret)
+    }
+
+    /// Returns whether `recover` would have enough distinct, consistent
+    /// shares to reconstruct the secret, without reconstructing it.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// # let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(3).collect();
+    /// assert!(sharks.threshold_met(&shares));
+    /// assert!(!sharks.threshold_met(&shares[..2]));
+    pub fn threshold_met<'a, T>(&self, shares: T) -> bool
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        self.distinct_shares(shares).is_ok()
+    }
+
+    /// Returns how many more distinct shares are needed to reach the
+    /// threshold. Shares dealt with a different threshold, or which are
+    /// inconsistent with the first valid share, are not counted.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(5);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// # let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(3).collect();
+    /// // 3 of 5 shares are present
+    /// assert_eq!(sharks.shares_remaining(&shares), 2);
+    pub fn shares_remaining<'a, T>(&self, shares: T) -> usize
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let mut first: Option<&Share> = None;
+        let mut keys: HashSet<[u8; FIELD_ELEMENT_LEN]> = HashSet::new();
+        for share in shares.into_iter().filter(|s| s.threshold == self.0) {
+            let first = *first.get_or_insert(share);
+            if share.y.len() == first.y.len()
+                && share.version == first.version
+                && share.namespace == first.namespace
+            {
+                keys.insert(share.x.to_repr().as_ref().try_into().unwrap());
+            }
+        }
+        (self.0 as usize).saturating_sub(keys.len())
+    }
+
+    /// Creates a share for a new holder at `new_x`, which is consistent
+    /// with the existing sharing, without dealing the secret again.
+    ///
+    /// At least a threshold of `existing_shares` must be provided, and
+    /// they are sufficient to recover the secret, so enrolling a holder
+    /// requires the same trust as recovery. `new_x` must be non-zero
+    /// (the share at zero is the secret) and not already in use.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Fp, Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(2);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(2).collect();
+    /// let share = sharks.enroll(&shares, Fp::from(10)).unwrap();
+    /// assert_eq!(sharks.recover(&[share, shares[0].clone()]).unwrap().as_bytes(), vec![1]);
+    pub fn enroll(&self, existing_shares: &[Share], new_x: Fp) -> Result<Share, RecoverError> {
+        let values = self.distinct_shares(existing_shares)?;
+        if new_x.is_zero() || values.iter().any(|s| s.x == new_x) {
+            return Err(RecoverError::InvalidShareIndex);
+        }
+        Ok(Share {
+            x: new_x,
+            y: interpolate_at_with_ctx(
+                &mut InterpolationCtx::new(),
+                &values[0..self.0 as usize],
+                new_x,
+            )?
+            .to_vec(),
+            threshold: self.0,
+            version: values[0].version,
+            namespace: values[0].namespace,
+        })
+    }
+
+    /// Like `recover`, but returns the recovered field elements, one
+    /// per chunk, without decoding them into bytes. For shares from
+    /// `dealer_rng` these are the elements of `encode_secret(secret)`,
+    /// while shares evaluated directly from polynomials built with
+    /// `random_polynomial` recover their constant terms.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ get_evaluator, random_polynomial, Fp, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let scalar = Fp::from(42);
+    /// let evaluator = get_evaluator(vec![random_polynomial(scalar, 3, &mut rng)]);
+    /// let shares: Vec<Share> = evaluator.take(3).collect();
+    /// assert_eq!(Sharks(3).recover_fp(&shares).unwrap(), vec![scalar]);
+    pub fn recover_fp<'a, T>(&self, shares: T) -> Result<Vec<Fp>, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        self.recover_elements(shares)
+    }
+
+    // Interpolates the field elements encoding the secret from the
+    // shares.
+    fn recover_elements<'a, T>(&self, shares: T) -> Result<Vec<Fp>, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let values = self.distinct_shares(shares)?;
+        // We only need the threshold number of shares to recover
+        interpolate_elements(&values[0..self.0 as usize])
+    }
+
+    // Checks that the shares are consistent with each other and this
+    // `Sharks` instance, and that at least a threshold of them are
+    // distinct. Returns references to the distinct shares, so that
+    // nothing is cloned.
+    fn distinct_shares<'a, T>(&self, shares: T) -> Result<Vec<&'a Share>, RecoverError>
     where
         T: IntoIterator<Item = &'a Share>,
         T::IntoIter: Iterator<Item = &'a Share>,
     {
         let mut share_length: Option<usize> = None;
-        let mut keys: HashSet<Vec<u8>> = HashSet::new();
-        let mut values: Vec<Share> = Vec::new();
+        let mut version: Option<u32> = None;
+        let mut namespace: Option<[u8; NAMESPACE_LEN]> = None;
+        // `hashbrown` hashes with AHash rather than SipHash, which is
+        // both faster and still resistant to collisions chosen by
+        // whoever supplies the shares. See the `dedup_t1000` benchmark.
+        let mut keys: HashSet<[u8; FIELD_ELEMENT_LEN]> = HashSet::new();
+        let mut values: Vec<&'a Share> = Vec::new();
 
         for share in shares.into_iter() {
             if share_length.is_none() {
                 share_length = Some(share.y.len());
+                version = Some(share.version);
+                namespace = share.namespace;
             }
 
-            if Some(share.y.len()) != share_length {
-                return Err("All shares must have the same length");
-            } else if keys.insert(share.x.to_repr().as_ref().to_vec()) {
-                values.push(share.clone());
+            if share.threshold != self.0 {
+                return Err(RecoverError::ThresholdMismatch {
+                    expected: self.0,
+                    found: share.threshold,
+                });
+            } else if Some(share.version) != version {
+                return Err(RecoverError::VersionMismatch {
+                    expected: version.unwrap(),
+                    found: share.version,
+                });
+            } else if share.namespace != namespace {
+                return Err(RecoverError::NamespaceMismatch);
+            } else if Some(share.y.len()) != share_length {
+                return Err(RecoverError::InconsistentShareLength);
+            } else if keys.insert(share.x.to_repr().as_ref().try_into().unwrap()) {
+                values.push(share);
             }
         }
 
         if keys.is_empty() || (keys.len() < self.0 as usize) {
-            Err("Not enough shares to recover original secret")
+            Err(RecoverError::NotEnoughShares)
         } else {
-            // We only need the threshold number of shares to recover
-            Ok(interpolate(&values[0..self.0 as usize]))
+            Ok(values)
+        }
+    }
+
+    /// Checks that `shares` are a well-formed set to recover from,
+    /// returning the first problem found. Unlike `recover`, which
+    /// ignores repeated shares, every share must be at a distinct `x`
+    /// coordinate. The checks, in order for each share, are:
+    ///
+    /// - `InvalidShareIndex` if its `x` coordinate is zero,
+    /// - `ThresholdMismatch` if it wasn't dealt with this threshold,
+    /// - `VersionMismatch` if its version differs from the first share's,
+    /// - `NamespaceMismatch` if its namespace differs from the first
+    ///   share's,
+    /// - `InconsistentShareLength` if it has a different number of `y`
+    ///   coordinates from the first share,
+    /// - `DuplicateShareX` if an earlier share has the same `x`,
+    ///
+    /// and finally `NotEnoughShares` if there are fewer shares than the
+    /// threshold. The coordinates don't need checking for canonicality,
+    /// as an `Fp` is always reduced, and `Share::try_from` rejects
+    /// encodings that aren't. A set that passes can still fail to
+    /// recover with `InvalidEncoding` if the shares are of different
+    /// secrets.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ RecoverError, Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(2);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1]), &mut rng).take(2).collect();
+    /// assert_eq!(sharks.verify_share_set(&shares), Ok(()));
+    /// let repeated = [shares[0].clone(), shares[0].clone()];
+    /// assert_eq!(sharks.verify_share_set(&repeated), Err(RecoverError::DuplicateShareX));
+    pub fn verify_share_set<'a, T>(&self, shares: T) -> Result<(), RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+    {
+        let mut shares = shares.into_iter().peekable();
+        let (share_length, version, namespace) = match shares.peek() {
+            Some(first) => (first.y.len(), first.version, first.namespace),
+            None => return Err(RecoverError::NotEnoughShares),
+        };
+        let mut keys: HashSet<[u8; FIELD_ELEMENT_LEN]> = HashSet::new();
+        for share in shares {
+            if share.x.is_zero() {
+                return Err(RecoverError::InvalidShareIndex);
+            } else if share.threshold != self.0 {
+                return Err(RecoverError::ThresholdMismatch {
+                    expected: self.0,
+                    found: share.threshold,
+                });
+            } else if share.version != version {
+                return Err(RecoverError::VersionMismatch {
+                    expected: version,
+                    found: share.version,
+                });
+            } else if share.namespace != namespace {
+                return Err(RecoverError::NamespaceMismatch);
+            } else if share.y.len() != share_length {
+                return Err(RecoverError::InconsistentShareLength);
+            } else if !keys.insert(share.x.to_repr().as_ref().try_into().unwrap()) {
+                return Err(RecoverError::DuplicateShareX);
+            }
+        }
+        if keys.len() < self.0 as usize {
+            return Err(RecoverError::NotEnoughShares);
+        }
+        Ok(())
+    }
+
+    /// Given an iterable collection of shares generated by
+    /// `dealer_rng_with_checksum`, recovers the original secret and
+    /// verifies its embedded checksum.
+    ///
+    /// A `ChecksumMismatch` error is returned if the recovered checksum
+    /// does not match, which indicates that the shares did not all come
+    /// from the same secret. Note that the checksum only guards against
+    /// accidental mistakes, it does not authenticate the shares.
+    pub fn recover_with_checksum<'a, T>(&self, shares: T) -> Result<Secret, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let mut elements = self.recover_elements(shares)?;
+        let checksum = elements.pop();
+        let secret = if checksum != Some(secret_checksum(&elements)) {
+            Err(RecoverError::ChecksumMismatch)
+        } else {
+            decode_secret(&elements).ok_or(RecoverError::InvalidEncoding)
+        };
+        zeroize_elements(&mut elements);
+        secret.map(Secret)
+    }
+}
+
+/// Errors returned when dealing shares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DealError {
+    /// The random number generator returned an error.
+    RngFailure,
+}
+
+impl fmt::Display for DealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DealError::RngFailure => write!(f, "The random number generator failed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DealError {}
+
+/// Errors returned when recovering a secret from shares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoverError {
+    /// Fewer distinct shares than the threshold were provided.
+    NotEnoughShares,
+    /// The shares do not all contain the same number of chunks.
+    InconsistentShareLength,
+    /// A share was dealt with a different threshold than the one used
+    /// for recovery.
+    ThresholdMismatch { expected: u32, found: u32 },
+    /// The shares were dealt for different versions of the secret.
+    VersionMismatch { expected: u32, found: u32 },
+    /// The shares were dealt for secrets in different namespaces.
+    NamespaceMismatch,
+    /// The recovered secret does not match its recovered checksum.
+    ChecksumMismatch,
+    /// The recovered field elements are not a valid secret encoding.
+    InvalidEncoding,
+    /// The shares chosen for recovery are not a threshold of distinct
+    /// shares.
+    InvalidSelection,
+    /// The `x` coordinate requested for a new share is zero, or is
+    /// already held by one of the shares. Also returned by
+    /// `verify_share_set` for a share at `x = 0`.
+    InvalidShareIndex,
+    /// The recovered secret does not match the dealer's published
+    /// commitment to it.
+    CommitmentMismatch,
+    /// No single choice of byte order for each of the serialized shares
+    /// gives a consistent secret.
+    UnknownByteOrder,
+    /// Two of the shares being interpolated have the same `x`
+    /// coordinate.
+    DuplicateShareX,
+    /// The recovered secret is not the length that was asked for.
+    SecretLengthMismatch { expected: usize, found: usize },
+}
+
+impl fmt::Display for RecoverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecoverError::NotEnoughShares => {
+                write!(f, "Not enough shares to recover original secret")
+            }
+            RecoverError::InconsistentShareLength => {
+                write!(f, "All shares must have the same length")
+            }
+            RecoverError::ThresholdMismatch { expected, found } => write!(
+                f,
+                "Share was dealt with threshold {}, but {} was expected",
+                found, expected
+            ),
+            RecoverError::VersionMismatch { expected, found } => write!(
+                f,
+                "Share is for version {} of the secret, but {} was expected",
+                found, expected
+            ),
+            RecoverError::NamespaceMismatch => {
+                write!(f, "Shares are for secrets in different namespaces")
+            }
+            RecoverError::ChecksumMismatch => {
+                write!(f, "Recovered secret does not match its checksum")
+            }
+            RecoverError::InvalidEncoding => {
+                write!(f, "Recovered secret is not correctly encoded")
+            }
+            RecoverError::InvalidSelection => {
+                write!(f, "Selected shares are not a threshold of distinct shares")
+            }
+            RecoverError::InvalidShareIndex => {
+                write!(f, "New share x coordinate must be non-zero and unused")
+            }
+            RecoverError::CommitmentMismatch => {
+                write!(f, "Recovered secret does not match its commitment")
+            }
+            RecoverError::UnknownByteOrder => {
+                write!(
+                    f,
+                    "Shares can't be parsed consistently in either byte order"
+                )
+            }
+            RecoverError::DuplicateShareX => {
+                write!(
+                    f,
+                    "Shares being interpolated must have distinct x coordinates"
+                )
+            }
+            RecoverError::SecretLengthMismatch { expected, found } => write!(
+                f,
+                "Recovered secret is {} bytes long, but {} bytes were expected",
+                found, expected
+            ),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for RecoverError {}
+
+// Checks that the methods which implicitly use `thread_rng` are absent
+// with the `no-thread-rng` feature.
+///
+/// ```compile_fail
+/// sharks::Sharks(2).dealer(&sharks::Secret::from_bytes(&[1]));
+/// ```
+///
+/// ```compile_fail
+/// sharks::Sharks(2).dealer_with_checksum(&sharks::Secret::from_bytes(&[1]));
+/// ```
+///
+/// ```compile_fail
+/// sharks::Sharks(2).weighted_dealer(&sharks::Secret::from_bytes(&[1]), &[1, 1]);
+/// ```
+///
+/// ```compile_fail
+/// use sharks::ThreadRandomized;
+/// sharks::Sharks(2)
+///     .dealing(&sharks::Secret::from_bytes(&[1]))
+///     .with_thread_rng();
+/// ```
+#[cfg(all(feature = "std", feature = "no-thread-rng"))]
+mod no_thread_rng {}
+
+// Computes a checksum of the secret field elements `s_i` as the
+// evaluation of `sum_i s_i * g^(i+1)`, where `g` is the field
+// generator.
+fn secret_checksum(elements: &[Fp]) -> Fp {
+    let g = Fp::multiplicative_generator();
+    elements
+        .iter()
+        .rev()
+        .fold(Fp::zero(), |acc, s| (acc + s) * g)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Fp, Share, Sharks};
+    use super::{Fp, Randomized, RecoverError, Secret, Share, Sharks, FIELD_ELEMENT_LEN};
     use crate::ff::{Field, PrimeField};
     use alloc::{vec, vec::Vec};
 
     impl Sharks {
-        #[cfg(not(feature = "std"))]
+        #[cfg(any(not(feature = "std"), feature = "no-thread-rng"))]
         fn make_shares(&self, secret: &[u8]) -> impl Iterator<Item = Share> {
             use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
 
             let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
-            self.dealer_rng(secret, &mut rng)
+            self.dealer_rng(&Secret::from_bytes(secret), &mut rng)
         }
 
-        #[cfg(feature = "std")]
+        #[cfg(all(feature = "std", not(feature = "no-thread-rng")))]
         fn make_shares(&self, secret: &[u8]) -> impl Iterator<Item = Share> {
-            self.dealer(secret)
+            self.dealer(&Secret::from_bytes(secret))
         }
     }
 
@@ -190,6 +1080,9 @@ mod tests {
         shares[1] = Share {
             x: shares[0].x,
             y: shares[0].y.clone(),
+            threshold: shares[0].threshold,
+            version: shares[0].version,
+            namespace: shares[0].namespace,
         };
         let secret = sharks.recover(&shares);
         assert!(secret.is_err());
@@ -205,11 +1098,12 @@ mod tests {
         input.extend(fp_four_repr());
         let shares: Vec<Share> = sharks.make_shares(&input).take(500).collect();
         let secret = sharks.recover(&shares).unwrap();
-        assert_eq!(secret, get_test_bytes());
+        assert_eq!(secret.as_bytes(), get_test_bytes());
     }
 
     use core::iter;
     #[test]
+    #[cfg(all(feature = "std", not(feature = "no-thread-rng")))]
     fn test_integration_works_random() {
         let sharks = Sharks(40);
         let mut rng = rand::thread_rng();
@@ -218,13 +1112,536 @@ mod tests {
         input.extend(fp_two_repr());
         input.extend(fp_three_repr());
         input.extend(fp_four_repr());
-        let evaluator = sharks.dealer(&input);
+        let evaluator = sharks.dealer(&Secret::from_bytes(&input));
         let shares: Vec<Share> = iter::repeat_with(|| evaluator.gen(&mut rng))
             .take(55)
             .collect();
         //let shares: Vec<Share> = sharks.make_shares(&[1, 2, 3, 4]).take(255).collect();
         let secret = sharks.recover(&shares).unwrap();
-        assert_eq!(secret, get_test_bytes());
+        assert_eq!(secret.as_bytes(), get_test_bytes());
+    }
+
+    #[test]
+    fn test_dealing_with_rng_matches_dealer_rng() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+        let sharks = Sharks(3);
+        let secret = fp_one_repr();
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let evaluator = sharks
+            .dealing(&Secret::from_bytes(&secret))
+            .with_rng(&mut rng);
+        let shares: Vec<Share> = iter::repeat_with(|| evaluator.with_rng(&mut rng))
+            .take(3)
+            .collect();
+
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let evaluator = sharks.dealer_rng(&Secret::from_bytes(&secret), &mut rng);
+        let chk_shares: Vec<Share> = iter::repeat_with(|| evaluator.gen(&mut rng))
+            .take(3)
+            .collect();
+        assert_eq!(shares, chk_shares);
+        assert_eq!(sharks.recover(&shares).unwrap().as_bytes(), secret);
+    }
+
+    #[test]
+    fn test_dealer_degree() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        for threshold in 1..8 {
+            let sharks = Sharks(threshold);
+            let evaluator = sharks.dealer_rng(&Secret::from_bytes(&get_test_bytes()), &mut rng);
+            assert_eq!(evaluator.degree(), Some((threshold - 1) as usize));
+        }
+    }
+
+    #[test]
+    fn test_secret_from_bytes() {
+        let sharks = Sharks(3);
+        for len in [0, 1, FIELD_ELEMENT_LEN - 1, FIELD_ELEMENT_LEN + 1] {
+            let bytes: Vec<u8> = (0..len as u8).collect();
+            let secret = Secret::from_bytes(&bytes);
+            let shares: Vec<Share> = sharks.make_shares(secret.as_bytes()).take(3).collect();
+            assert_eq!(
+                sharks.recover(&shares).unwrap().as_bytes(),
+                secret.into_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn test_secret_from_field_aligned() {
+        let bytes = vec![0xff; 2 * FIELD_ELEMENT_LEN];
+        let secret = Secret::from_field_aligned(&bytes).unwrap();
+        assert_eq!(secret, Secret::from_bytes(&bytes));
+        assert_eq!(secret.into_bytes(), bytes);
+
+        assert!(Secret::from_field_aligned(&[]).is_ok());
+        assert!(Secret::from_field_aligned(&bytes[1..]).is_err());
+        assert!(Secret::from_field_aligned(&[0; FIELD_ELEMENT_LEN + 1]).is_err());
+    }
+
+    #[test]
+    fn test_secret_debug_redacted() {
+        let secret = Secret::from_bytes(b"hunter2");
+        let debug = alloc::format!("{:?}", secret);
+        assert!(!debug.contains("104"));
+        assert_eq!(debug, "Secret(<redacted>)");
+        assert_eq!(
+            alloc::format!("{:?}", Ok::<_, ()>(secret)),
+            "Ok(Secret(<redacted>))"
+        );
+    }
+
+    #[test]
+    fn test_threshold_met() {
+        let sharks = Sharks(5);
+        let shares: Vec<Share> = sharks.make_shares(&fp_one_repr()).take(5).collect();
+        assert!(sharks.threshold_met(&shares));
+        assert!(!sharks.threshold_met(&shares[..4]));
+        // duplicates don't count towards the threshold
+        let duplicated: Vec<Share> = shares[..4].iter().chain(&shares[..1]).cloned().collect();
+        assert!(!sharks.threshold_met(&duplicated));
+        assert!(!Sharks(4).threshold_met(&shares));
+    }
+
+    #[test]
+    fn test_recover_fp() {
+        let sharks = Sharks(3);
+        let secret: Vec<u8> = (0..50).collect();
+        let shares: Vec<Share> = sharks.make_shares(&secret).take(3).collect();
+        let elements = sharks.recover_fp(&shares).unwrap();
+        assert_eq!(elements, crate::encode_secret(&secret));
+        assert_eq!(
+            crate::decode_secret(&elements).map(Secret::from),
+            sharks.recover(&shares).ok()
+        );
+        assert_eq!(
+            sharks.recover_fp(&shares[..2]),
+            Err(RecoverError::NotEnoughShares)
+        );
+    }
+
+    #[test]
+    fn test_recover_array() {
+        let sharks = Sharks(3);
+        let mut key = [0u8; 32];
+        for (i, b) in key.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        let shares: Vec<Share> = sharks.make_shares(&key).take(3).collect();
+        let recovered: [u8; 32] = sharks.recover_array(&shares).unwrap();
+        assert_eq!(recovered, key);
+        assert_eq!(
+            sharks.recover_array::<_, 16>(&shares),
+            Err(RecoverError::SecretLengthMismatch {
+                expected: 16,
+                found: 32
+            })
+        );
+        assert_eq!(
+            sharks.recover_array::<_, 32>(&shares[..2]),
+            Err(RecoverError::NotEnoughShares)
+        );
+    }
+
+    #[test]
+    fn test_recover_from_iters() {
+        use alloc::{boxed::Box, collections::VecDeque};
+
+        let sharks = Sharks(4);
+        let secret: Vec<u8> = (0..50).collect();
+        let shares: Vec<Share> = sharks.make_shares(&secret).take(6).collect();
+
+        // Three overlapping sources, of different types
+        let first = shares[..2].to_vec();
+        let second: VecDeque<Share> = shares[1..3].iter().cloned().collect();
+        let third = [&shares[0], &shares[2], &shares[5]];
+        let sources: Vec<Box<dyn Iterator<Item = &Share>>> = vec![
+            Box::new(first.iter()),
+            Box::new(second.iter()),
+            Box::new(third.iter().copied()),
+        ];
+        assert_eq!(sharks.recover_from_iters(sources), Ok(Secret::from(secret)));
+
+        // The overlap leaves only three distinct shares
+        assert_eq!(
+            sharks.recover_from_iters(vec![first.iter(), shares[..3].iter()]),
+            Err(RecoverError::NotEnoughShares)
+        );
+    }
+
+    #[test]
+    fn test_shares_remaining() {
+        let sharks = Sharks(5);
+        let shares: Vec<Share> = sharks.make_shares(&fp_one_repr()).take(6).collect();
+        assert_eq!(sharks.shares_remaining(&shares[..3]), 2);
+        assert_eq!(sharks.shares_remaining(&shares[..0]), 5);
+        assert_eq!(sharks.shares_remaining(&shares), 0);
+
+        // Duplicates and invalid shares are not counted
+        let mut present = shares[..3].to_vec();
+        present.push(shares[0].clone());
+        present.extend(Sharks(3).make_shares(&fp_one_repr()).take(1));
+        let mut long = shares[3].clone();
+        long.y.push(long.y[0]);
+        present.push(long);
+        assert_eq!(sharks.shares_remaining(&present), 2);
+    }
+
+    #[test]
+    fn test_enroll() {
+        let sharks = Sharks(3);
+        let secret = get_test_bytes();
+        let shares: Vec<Share> = sharks.make_shares(&secret).take(3).collect();
+        let enrolled: Vec<Share> = (10..13)
+            .map(|x| sharks.enroll(&shares, Fp::from(x)).unwrap())
+            .collect();
+        assert!(enrolled.iter().all(|s| s.threshold == 3));
+        assert_eq!(sharks.recover(&enrolled).unwrap().as_bytes(), secret);
+        let mixed = [shares[0].clone(), enrolled[1].clone(), shares[2].clone()];
+        assert_eq!(sharks.recover(&mixed).unwrap().as_bytes(), secret);
+
+        assert_eq!(
+            sharks.enroll(&shares[..2], Fp::from(10)).err(),
+            Some(RecoverError::NotEnoughShares)
+        );
+        assert_eq!(
+            sharks.enroll(&shares, Fp::zero()).err(),
+            Some(RecoverError::InvalidShareIndex)
+        );
+        assert_eq!(
+            sharks.enroll(&shares, shares[1].x).err(),
+            Some(RecoverError::InvalidShareIndex)
+        );
+    }
+
+    #[test]
+    fn test_threshold_mismatch_err() {
+        let shares: Vec<Share> = Sharks(3).make_shares(&fp_one_repr()).take(5).collect();
+        assert_eq!(
+            Sharks(5).recover(&shares),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 5,
+                found: 3
+            })
+        );
+        assert_eq!(
+            Sharks(3).recover(&shares).unwrap().as_bytes(),
+            fp_one_repr()
+        );
+    }
+
+    #[test]
+    fn test_mixed_thresholds_err() {
+        // Each share records the threshold it was dealt with, so mixing
+        // dealings is caught whichever threshold recovery expects
+        let t3: Vec<Share> = Sharks(3).make_shares(&fp_one_repr()).take(3).collect();
+        let t5: Vec<Share> = Sharks(5).make_shares(&fp_one_repr()).take(5).collect();
+        let mixed = [&t3[..], &t5[3..]].concat();
+        assert_eq!(
+            Sharks(3).recover(&mixed),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 3,
+                found: 5
+            })
+        );
+        assert_eq!(
+            Sharks(5).recover(&mixed),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 5,
+                found: 3
+            })
+        );
+    }
+
+    // A generator which fails once it has produced `remaining` bytes.
+    struct FailingRng {
+        remaining: usize,
+    }
+
+    impl rand::RngCore for FailingRng {
+        fn next_u32(&mut self) -> u32 {
+            rand_core::impls::next_u32_via_fill(self)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            rand_core::impls::next_u64_via_fill(self)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.try_fill_bytes(dest)
+                .expect("FailingRng ran out of bytes")
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            if dest.len() > self.remaining {
+                let code = core::num::NonZeroU32::new(rand::Error::CUSTOM_START).unwrap();
+                return Err(rand::Error::from(code));
+            }
+            self.remaining -= dest.len();
+            dest.fill(0x90);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_try_dealer_rng() {
+        use super::DealError;
+        let sharks = Sharks(3);
+        let secret = Secret::from_bytes(&[1; 40]);
+        // Failing at once, or part way through the second chunk
+        for &remaining in &[0, 3 * 64] {
+            let mut rng = FailingRng { remaining };
+            assert_eq!(
+                sharks.try_dealer_rng(&secret, &mut rng).err(),
+                Some(DealError::RngFailure)
+            );
+        }
+        // Enough bytes for both chunks of the secret
+        let mut rng = FailingRng { remaining: 4 * 64 };
+        let shares: Vec<Share> = sharks
+            .try_dealer_rng(&secret, &mut rng)
+            .unwrap()
+            .take(3)
+            .collect();
+        assert_eq!(sharks.recover(&shares).unwrap().as_bytes(), [1; 40]);
+        // A threshold of one needs no randomness
+        let mut rng = FailingRng { remaining: 0 };
+        assert!(Sharks(1).try_dealer_rng(&secret, &mut rng).is_ok());
+    }
+
+    #[test]
+    fn test_dealer_into() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let mut out = Vec::new();
+        // Growing, shrinking, and changing the secret length reuse the
+        // buffer but give the same shares as the iterator
+        for &(len, n) in &[(40, 5), (2, 3), (90, 7), (1, 0)] {
+            let secret = Secret::from_bytes(&vec![7; len]);
+            let expected: Vec<Share> = sharks
+                .dealer_rng(&secret, &mut rng.clone())
+                .take(n)
+                .collect();
+            sharks.dealer_into(&secret, n, &mut rng, &mut out);
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn test_version_mismatch_err() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let v1: Vec<Share> = sharks
+            .dealer_rng(&Secret::from_bytes(&[1]), &mut rng)
+            .with_version(1)
+            .take(3)
+            .collect();
+        let v2: Vec<Share> = sharks
+            .dealer_rng(&Secret::from_bytes(&[2]), &mut rng)
+            .with_version(2)
+            .take(3)
+            .collect();
+        assert!(v2.iter().all(|s| s.version == 2));
+
+        let mixed = [&v1[..2], &v2[..]].concat();
+        assert_eq!(
+            sharks.recover(&mixed),
+            Err(RecoverError::VersionMismatch {
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            sharks.recover_constant_time(&mixed),
+            Err(RecoverError::VersionMismatch {
+                expected: 1,
+                found: 2
+            })
+        );
+
+        // Each version recovers on its own
+        for (version, secret) in [(1, vec![1]), (2, vec![2])] {
+            let shares = mixed.iter().chain(&v1).filter(|s| s.version == version);
+            assert_eq!(sharks.recover(shares).unwrap().as_bytes(), secret);
+        }
+    }
+
+    #[test]
+    fn test_verify_share_set() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let shares: Vec<Share> = sharks
+            .dealer_rng(&Secret::from_bytes(&[1; 40]), &mut rng)
+            .take(4)
+            .collect();
+        assert_eq!(sharks.verify_share_set(&shares), Ok(()));
+        assert_eq!(sharks.verify_share_set(&shares[1..]), Ok(()));
+
+        // Applies `corrupt` to the last share, returning the result
+        let check = |corrupt: &dyn Fn(&mut Share)| {
+            let mut shares = shares.clone();
+            corrupt(shares.last_mut().unwrap());
+            sharks.verify_share_set(&shares)
+        };
+        assert_eq!(
+            check(&|s| s.x = Fp::zero()),
+            Err(RecoverError::InvalidShareIndex)
+        );
+        assert_eq!(
+            check(&|s| s.threshold = 2),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            check(&|s| s.version = 1),
+            Err(RecoverError::VersionMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            check(&|s| s.y.truncate(1)),
+            Err(RecoverError::InconsistentShareLength)
+        );
+        // Both repeated shares, which `recover` would skip, and
+        // conflicting ones are rejected
+        assert_eq!(
+            check(&|s| *s = shares[0].clone()),
+            Err(RecoverError::DuplicateShareX)
+        );
+        assert_eq!(
+            check(&|s| s.x = shares[0].x),
+            Err(RecoverError::DuplicateShareX)
+        );
+        assert_eq!(
+            sharks.verify_share_set(&shares[..2]),
+            Err(RecoverError::NotEnoughShares)
+        );
+        assert_eq!(
+            sharks.verify_share_set(&[]),
+            Err(RecoverError::NotEnoughShares)
+        );
+        // The first problem is the one reported
+        let mut bad = shares.clone();
+        bad[1].threshold = 2;
+        bad[2].x = Fp::zero();
+        assert_eq!(
+            sharks.verify_share_set(&bad),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_short_secrets_work() {
+        let sharks = Sharks(3);
+        for len in 0..70 {
+            let secret: Vec<u8> = (0..len).map(|i| 0xff - i as u8).collect();
+            let shares: Vec<Share> = sharks.make_shares(&secret).take(3).collect();
+            assert_eq!(sharks.recover(&shares).unwrap().as_bytes(), secret);
+        }
+    }
+
+    #[test]
+    fn test_weighted_recover() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+        let sharks = Sharks(4);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&get_test_bytes());
+        let parties = sharks.weighted_dealer_rng(&secret, &[4, 1, 2, 0], &mut rng);
+        assert_eq!(
+            parties.iter().map(|p| p.len()).collect::<Vec<usize>>(),
+            vec![4, 1, 2, 0]
+        );
+        // x coordinates are assigned in contiguous blocks
+        assert_eq!(parties[1][0].x, Fp::from(5));
+        assert_eq!(parties[2][1].x, Fp::from(7));
+
+        // the heavy party meets the threshold alone
+        assert_eq!(sharks.weighted_recover(&parties[..1]), Ok(secret.clone()));
+        // the other parties only have a combined weight of 3
+        assert_eq!(
+            sharks.weighted_recover(&parties[1..]),
+            Err(RecoverError::NotEnoughShares)
+        );
+        let mixed = vec![parties[1].clone(), parties[2].clone(), parties[0].clone()];
+        assert_eq!(sharks.weighted_recover(&mixed), Ok(secret));
+    }
+
+    #[test]
+    fn test_recover_with_selection() {
+        let sharks = Sharks(3);
+        let secret = Secret::from_bytes(&get_test_bytes());
+        let shares: Vec<Share> = sharks.make_shares(secret.as_bytes()).take(6).collect();
+        let secret_odd = sharks.recover_with(&shares, |_| vec![5, 1, 3]);
+        assert_eq!(secret_odd, Ok(secret.clone()));
+        let secret_lowest_x = sharks.recover_with(&shares, |s| {
+            let mut indices: Vec<usize> = (0..s.len()).collect();
+            indices.sort_by_key(|&i| s[i].x.to_repr().as_ref().to_vec());
+            indices.truncate(3);
+            indices
+        });
+        assert_eq!(secret_lowest_x, Ok(secret));
+
+        for bad in [vec![0, 1], vec![0, 1, 1], vec![0, 1, 6]] {
+            assert_eq!(
+                sharks.recover_with(&shares, |_| bad.clone()),
+                Err(RecoverError::InvalidSelection)
+            );
+        }
+    }
+
+    #[test]
+    fn test_checksum_works() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&get_test_bytes());
+        let shares: Vec<Share> = sharks
+            .dealer_rng_with_checksum(&secret, &mut rng)
+            .take(3)
+            .collect();
+        // four elements of secret, a length prefix, and the checksum
+        assert_eq!(shares[0].y.len(), 6);
+        assert_eq!(sharks.recover_with_checksum(&shares), Ok(secret));
+    }
+
+    #[test]
+    fn test_checksum_mixed_shares_err() {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let mut shares: Vec<Share> = sharks
+            .dealer_rng_with_checksum(&Secret::from_bytes(&fp_one_repr()), &mut rng)
+            .take(2)
+            .collect();
+        let other: Vec<Share> = sharks
+            .dealer_rng_with_checksum(&Secret::from_bytes(&fp_two_repr()), &mut rng)
+            .take(3)
+            .collect();
+        shares.push(other[2].clone());
+        assert_eq!(
+            sharks.recover_with_checksum(&shares),
+            Err(RecoverError::ChecksumMismatch)
+        );
+        shares.pop();
+        assert_eq!(
+            sharks.recover_with_checksum(&shares),
+            Err(RecoverError::NotEnoughShares)
+        );
     }
 
     fn get_test_bytes() -> Vec<u8> {
@@ -0,0 +1,229 @@
+//! Hash-based commitments to shares.
+//!
+//! Unlike the Feldman commitments of the `vss` feature, these don't rest
+//! on the hardness of discrete logarithms, only on SHA-256 being
+//! collision resistant. The dealer publishes the root of a Merkle tree
+//! with one leaf per share, and hands each holder the path from its
+//! share to the root.
+//!
+//! A valid path proves that a share is the one the dealer committed to,
+//! so holders can check that their shares weren't altered on the way to
+//! them or in storage. It does not prove that the shares are consistent
+//! with each other: a dishonest dealer can commit to shares which don't
+//! lie on a single polynomial of the threshold's degree, and the paths
+//! still verify.
+//!
+//! Leaves and inner nodes are hashed with distinct prefixes, and the
+//! tree is padded with zero leaves to a power of two, so a path holds
+//! one sibling hash per level.
+
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use sha2::{Digest, Sha256};
+
+use crate::share_ff::Share;
+use crate::{Secret, Sharks};
+
+/// Length of a Merkle tree node, and so of the published root, in
+/// bytes.
+pub const MERKLE_HASH_LEN: usize = 32;
+
+/// The path from a share's leaf to the root of a `hash_commit` tree.
+///
+/// The serialized form is the leaf `index` as a little-endian `u32`,
+/// followed by the sibling hashes from the leaf up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerklePath {
+    index: u32,
+    siblings: Vec<[u8; MERKLE_HASH_LEN]>,
+}
+
+impl MerklePath {
+    /// The position of the share among those committed to.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+/// Obtains a byte vector from a `MerklePath` instance
+impl From<&MerklePath> for Vec<u8> {
+    fn from(p: &MerklePath) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + p.siblings.len() * MERKLE_HASH_LEN);
+        bytes.extend(p.index.to_le_bytes());
+        for sibling in &p.siblings {
+            bytes.extend(sibling);
+        }
+        bytes
+    }
+}
+
+/// Obtains a `MerklePath` instance from a byte slice
+impl core::convert::TryFrom<&[u8]> for MerklePath {
+    type Error = &'static str;
+
+    fn try_from(s: &[u8]) -> Result<MerklePath, Self::Error> {
+        if s.len() < 4 || !s[4..].chunks_exact(MERKLE_HASH_LEN).remainder().is_empty() {
+            return Err("MerklePath length is not a whole number of hashes");
+        }
+        Ok(MerklePath {
+            index: u32::from_le_bytes(s[..4].try_into().unwrap()),
+            siblings: s[4..]
+                .chunks(MERKLE_HASH_LEN)
+                .map(|h| h.try_into().unwrap())
+                .collect(),
+        })
+    }
+}
+
+/// Commits to `shares` with a Merkle tree, returning its root and the
+/// path of each share, in the same order as `shares`.
+///
+/// Panics if there are no shares, or more than `u32::MAX`.
+pub fn hash_commit(shares: &[Share]) -> ([u8; MERKLE_HASH_LEN], Vec<MerklePath>) {
+    assert!(
+        !shares.is_empty() && shares.len() <= u32::MAX as usize,
+        "Can only commit to between 1 and u32::MAX shares"
+    );
+    let mut level: Vec<[u8; MERKLE_HASH_LEN]> = shares.iter().map(leaf_hash).collect();
+    level.resize(shares.len().next_power_of_two(), [0; MERKLE_HASH_LEN]);
+    let mut paths: Vec<MerklePath> = (0..shares.len())
+        .map(|index| MerklePath {
+            index: index as u32,
+            siblings: Vec::new(),
+        })
+        .collect();
+    let mut position: Vec<usize> = (0..shares.len()).collect();
+    while level.len() > 1 {
+        for (path, pos) in paths.iter_mut().zip(position.iter_mut()) {
+            path.siblings.push(level[*pos ^ 1]);
+            *pos >>= 1;
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| node_hash(&pair[0], &pair[1]))
+            .collect();
+    }
+    (level[0], paths)
+}
+
+impl Sharks {
+    /// Deals `n` shares of `secret` as `dealer_rng` does, along with a
+    /// `hash_commit` root to publish and the path to hand out with each
+    /// share. Panics if `n` is zero.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let (mut shares, root) = sharks.hash_committed_dealer_rng(&Secret::from_bytes(&[1, 2]), 5, &mut rng);
+    /// let (share, path) = &mut shares[0];
+    /// assert!(share.verify_merkle(&root, path));
+    /// share.y[0] += sharks::Fp::from(1);
+    /// assert!(!share.verify_merkle(&root, path));
+    pub fn hash_committed_dealer_rng<R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        n: usize,
+        rng: &mut R,
+    ) -> (Vec<(Share, MerklePath)>, [u8; MERKLE_HASH_LEN]) {
+        let shares: Vec<Share> = self.dealer_rng(secret, rng).take(n).collect();
+        let (root, paths) = hash_commit(&shares);
+        (shares.into_iter().zip(paths).collect(), root)
+    }
+}
+
+impl Share {
+    /// Checks that this share is the one committed to at `path.index()`
+    /// under `root` by `hash_commit`. This proves the share is
+    /// authentic, not that it is consistent with the other shares.
+    pub fn verify_merkle(&self, root: &[u8; MERKLE_HASH_LEN], path: &MerklePath) -> bool {
+        if path.siblings.len() >= 32 || path.index >> path.siblings.len() != 0 {
+            return false;
+        }
+        let mut pos = path.index;
+        let node = path.siblings.iter().fold(leaf_hash(self), |node, sibling| {
+            let parent = if pos & 1 == 0 {
+                node_hash(&node, sibling)
+            } else {
+                node_hash(sibling, &node)
+            };
+            pos >>= 1;
+            parent
+        });
+        &node == root
+    }
+}
+
+fn leaf_hash(share: &Share) -> [u8; MERKLE_HASH_LEN] {
+    Sha256::new()
+        .chain([0u8])
+        .chain(Vec::from(share))
+        .finalize()
+        .into()
+}
+
+fn node_hash(left: &[u8; MERKLE_HASH_LEN], right: &[u8; MERKLE_HASH_LEN]) -> [u8; MERKLE_HASH_LEN] {
+    Sha256::new()
+        .chain([1u8])
+        .chain(left)
+        .chain(right)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_commit, MerklePath};
+    use crate::{Fp, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_verify_merkle() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        for &n in &[1, 2, 5, 8] {
+            let (committed, root) =
+                sharks.hash_committed_dealer_rng(&Secret::from_bytes(&[1; 40]), n, &mut rng);
+            for (share, path) in &committed {
+                assert!(share.verify_merkle(&root, path));
+                let bytes = Vec::from(path);
+                assert_eq!(MerklePath::try_from(&bytes[..]).as_ref(), Ok(path));
+            }
+            if n >= 3 {
+                let shares: Vec<Share> = committed.iter().map(|(s, _)| s.clone()).collect();
+                assert_eq!(sharks.recover(&shares).unwrap().as_bytes(), [1; 40]);
+            }
+        }
+        assert!(MerklePath::try_from(&[0u8; 3][..]).is_err());
+        assert!(MerklePath::try_from(&[0u8; 5][..]).is_err());
+    }
+
+    #[test]
+    fn test_verify_merkle_rejects_tampering() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let (committed, root) =
+            sharks.hash_committed_dealer_rng(&Secret::from_bytes(&[1, 2]), 5, &mut rng);
+        let (share, path) = &committed[2];
+
+        let mut tampered = share.clone();
+        tampered.y[0] += Fp::from(1);
+        assert!(!tampered.verify_merkle(&root, path));
+
+        // Another share's path, or the same path at another index
+        assert!(!share.verify_merkle(&root, &committed[3].1));
+        let mut moved = path.clone();
+        moved.index = 3;
+        assert!(!share.verify_merkle(&root, &moved));
+        moved.index = 2 + 8;
+        assert!(!share.verify_merkle(&root, &moved));
+
+        // A root over other shares
+        let (other_root, _) = hash_commit(core::slice::from_ref(share));
+        assert!(!share.verify_merkle(&other_root, path));
+    }
+}
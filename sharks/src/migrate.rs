@@ -0,0 +1,133 @@
+//! Moving a secret to a new set of shareholders.
+
+use alloc::vec::Vec;
+
+use crate::share_ff::{get_evaluator, random_polynomial, zeroize_elements, Share};
+use crate::{RecoverError, Sharks};
+
+impl Sharks {
+    /// Recovers the secret from `old_shares`, dealt with this instance's
+    /// threshold, and deals it again as `new_n` shares with
+    /// `new_threshold` and `new_version`, in one call. The new shares
+    /// keep the namespace of the old ones, if any.
+    ///
+    /// The secret is only held as field elements, in the interpolation
+    /// buffers and the recovered elements, which are zeroized as soon as
+    /// the new polynomials are drawn, and the new polynomials are
+    /// zeroized once the shares are evaluated. With the `zeroize_memory`
+    /// feature these writes can't be optimized away.
+    ///
+    /// The new shares are on fresh random polynomials, so old and new
+    /// shares don't combine. Give them a `new_version` other than that
+    /// of the old shares, so that mixing them is reported as a
+    /// `VersionMismatch` even when the thresholds are the same.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let old: Vec<Share> = Sharks(2).dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng).take(3).collect();
+    /// let new = Sharks(2).migrate(&old[1..], 3, 4, 1, &mut rng).unwrap();
+    /// assert_eq!(Sharks(3).recover(&new[..3]).unwrap().as_bytes(), vec![1, 2]);
+    pub fn migrate<R: rand::Rng>(
+        &self,
+        old_shares: &[Share],
+        new_threshold: u32,
+        new_n: usize,
+        new_version: u32,
+        rng: &mut R,
+    ) -> Result<Vec<Share>, RecoverError> {
+        let mut elements = self.recover_fp(old_shares)?;
+        let polys = elements
+            .iter()
+            .map(|element| random_polynomial(*element, new_threshold, rng))
+            .collect();
+        zeroize_elements(&mut elements);
+
+        let mut evaluator = get_evaluator(polys).with_version(new_version);
+        if let Some(namespace) = old_shares[0].namespace {
+            evaluator = evaluator.with_namespace(namespace);
+        }
+        let shares = (&mut evaluator).take(new_n).collect();
+        for poly in &mut evaluator.polys {
+            zeroize_elements(poly);
+        }
+        Ok(shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RecoverError, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_migrate() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&[0xab; 40]);
+        let old: Vec<Share> = Sharks(3).dealer_rng(&secret, &mut rng).take(5).collect();
+
+        let new = Sharks(3).migrate(&old[2..], 4, 7, 1, &mut rng).unwrap();
+        assert_eq!(new.len(), 7);
+        assert!(new.iter().all(|s| s.threshold == 4 && s.version == 1));
+        assert_eq!(
+            Sharks(4).recover(&new[3..]).unwrap().as_bytes(),
+            secret.as_bytes()
+        );
+        assert_eq!(
+            Sharks(4).recover(&new[..3]),
+            Err(RecoverError::NotEnoughShares)
+        );
+
+        // Old shares can't be mixed into the new set
+        let mixed = [&old[..1], &new[..3]].concat();
+        assert_eq!(
+            Sharks(4).recover(&mixed),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 4,
+                found: 3
+            })
+        );
+        let same = Sharks(3).migrate(&old, 3, 3, 1, &mut rng).unwrap();
+        assert_eq!(
+            Sharks(3).recover(&[&old[..2], &same[..1]].concat()),
+            Err(RecoverError::VersionMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+
+        assert_eq!(
+            Sharks(3).migrate(&old[..2], 4, 7, 1, &mut rng),
+            Err(RecoverError::NotEnoughShares)
+        );
+    }
+
+    #[test]
+    fn test_migrate_namespaced() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let sharks = Sharks(2);
+        let old: Vec<Share> = sharks
+            .dealer_ns_rng(b"a", &Secret::from_bytes(b"secret"), &mut rng)
+            .with_version(5)
+            .take(2)
+            .collect();
+
+        let new = sharks.migrate(&old, 2, 3, 6, &mut rng).unwrap();
+        assert!(new.iter().all(|s| s.version == 6));
+        assert!(new.iter().all(|s| s.namespace == old[0].namespace));
+        assert_eq!(
+            sharks.recover_ns(b"a", &new[1..]).unwrap().as_bytes(),
+            b"secret"
+        );
+        assert_eq!(
+            sharks.recover_ns(b"a", &[&old[..1], &new[..1]].concat()),
+            Err(RecoverError::VersionMismatch {
+                expected: 5,
+                found: 6
+            })
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Dealing several secrets under one `Sharks` threshold, keeping their
+//! shares apart by namespace.
+//!
+//! A namespace is hashed into the `namespace` of each share, so shares
+//! of secrets in different namespaces are rejected by `recover` with a
+//! `NamespaceMismatch` error rather than silently combined, and
+//! `recover_ns` picks out the shares of one namespace from a mixed set.
+//! The hash is 256 bits long, so distinct namespaces collide only with
+//! negligible probability. The share `version` is left alone, so
+//! namespaced secrets can still be versioned with `with_version`.
+
+use sha2::{Digest, Sha512};
+
+use crate::share_ff::{Evaluator, Share, NAMESPACE_LEN};
+use crate::{RecoverError, Secret, Sharks};
+
+/// Returns the share namespace used for `namespace` by `dealer_ns_rng`.
+pub fn namespace_id(namespace: &[u8]) -> [u8; NAMESPACE_LEN] {
+    let digest = Sha512::new()
+        .chain(b"sharks_namespace")
+        .chain(namespace)
+        .finalize();
+    let mut id = [0u8; NAMESPACE_LEN];
+    id.copy_from_slice(&digest[..NAMESPACE_LEN]);
+    id
+}
+
+impl Sharks {
+    /// Like `dealer_rng`, but tags the shares with `namespace`, so that
+    /// they can't be combined with shares from another namespace.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(2);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let mut shares: Vec<Share> =
+    ///     sharks.dealer_ns_rng(b"alpha", &Secret::from_bytes(&[1]), &mut rng).take(2).collect();
+    /// shares.extend(sharks.dealer_ns_rng(b"beta", &Secret::from_bytes(&[2]), &mut rng).take(2));
+    /// assert_eq!(sharks.recover_ns(b"alpha", &shares).unwrap().as_bytes(), vec![1]);
+    /// assert_eq!(sharks.recover_ns(b"beta", &shares).unwrap().as_bytes(), vec![2]);
+    pub fn dealer_ns_rng<R: rand::Rng>(
+        &self,
+        namespace: &[u8],
+        secret: &Secret,
+        rng: &mut R,
+    ) -> Evaluator {
+        self.dealer_rng(secret, rng)
+            .with_namespace(namespace_id(namespace))
+    }
+
+    /// Recovers the secret in `namespace` from the shares dealt for it
+    /// by `dealer_ns_rng`, ignoring any shares from other namespaces.
+    pub fn recover_ns<'a, T>(&self, namespace: &[u8], shares: T) -> Result<Secret, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let id = namespace_id(namespace);
+        self.recover(shares.into_iter().filter(|s| s.namespace == Some(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::namespace_id;
+    use crate::{RecoverError, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_namespaces_are_isolated() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let a: Vec<Share> = sharks
+            .dealer_ns_rng(b"a", &Secret::from_bytes(b"secret a"), &mut rng)
+            .take(4)
+            .collect();
+        let b: Vec<Share> = sharks
+            .dealer_ns_rng(b"b", &Secret::from_bytes(b"secret b"), &mut rng)
+            .take(4)
+            .collect();
+        let mixed = [&a[..2], &b[..3], &a[2..], &b[3..]].concat();
+
+        assert_eq!(
+            sharks.recover_ns(b"a", &mixed).unwrap().as_bytes(),
+            b"secret a"
+        );
+        assert_eq!(
+            sharks.recover_ns(b"b", &mixed).unwrap().as_bytes(),
+            b"secret b"
+        );
+        assert_eq!(
+            sharks.recover_ns(b"c", &mixed),
+            Err(RecoverError::NotEnoughShares)
+        );
+
+        // Shares of different namespaces can't be combined
+        let combined = [&a[..2], &b[..1]].concat();
+        assert_eq!(
+            sharks.recover(&combined),
+            Err(RecoverError::NamespaceMismatch)
+        );
+        assert_eq!(
+            sharks.recover_ns(b"a", &combined),
+            Err(RecoverError::NotEnoughShares)
+        );
+        // Nor with shares outside any namespace
+        let plain: Vec<Share> = sharks
+            .dealer_rng(&Secret::from_bytes(b"plain"), &mut rng)
+            .take(1)
+            .collect();
+        let combined = [&a[..2], &plain[..]].concat();
+        assert_eq!(
+            sharks.recover(&combined),
+            Err(RecoverError::NamespaceMismatch)
+        );
+    }
+
+    #[test]
+    fn test_namespace_keeps_version() {
+        let sharks = Sharks(2);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let shares: Vec<Share> = sharks
+            .dealer_ns_rng(b"a", &Secret::from_bytes(b"secret"), &mut rng)
+            .with_version(3)
+            .take(2)
+            .collect();
+        assert!(shares.iter().all(|s| s.version == 3));
+        assert!(shares
+            .iter()
+            .all(|s| s.namespace == Some(namespace_id(b"a"))));
+        assert_eq!(
+            sharks.recover_ns(b"a", &shares).unwrap().as_bytes(),
+            b"secret"
+        );
+    }
+
+    #[test]
+    fn test_namespaced_share_round_trip() {
+        let sharks = Sharks(2);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let shares: Vec<Share> = sharks
+            .dealer_ns_rng(b"a", &Secret::from_bytes(b"secret"), &mut rng)
+            .with_version(3)
+            .take(2)
+            .collect();
+        let parsed: Vec<Share> = shares
+            .iter()
+            .map(|s| Share::try_from(Vec::from(s).as_slice()).unwrap())
+            .collect();
+        assert_eq!(parsed, shares);
+
+        // The namespace is part of the header, so a namespaced share
+        // needs room for it as well as the `x` coordinate
+        let bytes = Vec::from(&shares[0]);
+        assert!(Share::try_from(&bytes[..40]).is_err());
+        assert_eq!(
+            sharks.recover_ns(b"a", &parsed).unwrap().as_bytes(),
+            b"secret"
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! The types most code using this crate needs, together with the `ff`
+//! traits their methods come from.
+//!
+//! `Fp` implements the `Field` and `PrimeField` traits of the version
+//! of `ff` this crate is built with. Importing the traits from here,
+//! rather than from a separate dependency on `ff`, means they are
+//! always the ones `Fp` implements, so there are no version mismatches
+//! to keep in step.
+//!
+//! Example:
+//! ```
+//! use sharks::prelude::*;
+//! # use rand_chacha::rand_core::SeedableRng;
+//! # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+//! let shares: Vec<Share> = Sharks(2).dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng).take(2).collect();
+//! // Round trip an `x` coordinate through its canonical encoding
+//! let repr: FpRepr = shares[0].x.to_repr();
+//! assert_eq!(Fp::from_repr(repr).unwrap(), shares[0].x);
+//! assert_eq!(Fp::one() + Fp::zero(), shares[0].x);
+//! assert_eq!(Sharks(2).recover(&shares).unwrap().as_bytes(), vec![1, 2]);
+
+pub use crate::{Evaluator, RecoverError, Secret, Share, Sharks};
+pub use crate::{Fp, FpRepr, FIELD_ELEMENT_LEN};
+pub use ff::{Field, PrimeField};
@@ -0,0 +1,147 @@
+//! Counts of the field operations performed by interpolation, for
+//! profiling. Only available with the `profile` feature.
+//!
+//! Each inversion and multiplication is counted as it is made, by every
+//! way of recovering: Lagrange interpolation, including with an
+//! `InterpolationCtx` and in consistency checks, incremental
+//! `Recovery`, `recover_single` and `recover_constant_time`. Evaluating
+//! shares when dealing isn't counted.
+//!
+//! Inversions are far more expensive than multiplications, so the
+//! inversion count is the main thing to watch: batch inversion keeps it
+//! at one per interpolation, whatever the threshold.
+//!
+//! Counts are kept per thread, so that concurrent interpolations on
+//! other threads don't interfere with them.
+
+use core::cell::Cell;
+
+/// The number of field operations performed by interpolations on the
+/// current thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InterpolationStats {
+    /// Field inversions.
+    pub inversions: usize,
+    /// Field multiplications.
+    pub multiplications: usize,
+}
+
+std::thread_local! {
+    static STATS: Cell<InterpolationStats> = Cell::new(InterpolationStats::default());
+}
+
+/// Returns the counts accumulated on the current thread since the last
+/// call, and resets them.
+///
+/// Example:
+/// ```
+/// # use sharks::{ profile, Sharks, Share };
+/// # use rand_chacha::rand_core::SeedableRng;
+/// # let sharks = Sharks(10);
+/// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+/// let shares: Vec<Share> = sharks.dealer_rng(&[1][..].into(), &mut rng).take(10).collect();
+/// profile::take_stats();
+/// sharks::interpolate(&shares).unwrap();
+/// assert_eq!(profile::take_stats().inversions, 1);
+pub fn take_stats() -> InterpolationStats {
+    STATS.with(|stats| stats.take())
+}
+
+// Adds to the counts of the current thread.
+pub(crate) fn record(inversions: usize, multiplications: usize) {
+    STATS.with(|stats| {
+        let mut current = stats.get();
+        current.inversions += inversions;
+        current.multiplications += multiplications;
+        stats.set(current);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{take_stats, InterpolationStats};
+    use crate::{interpolate, interpolate_with_ctx, InterpolationCtx};
+    use crate::{Fp, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    // Deals `n` shares of a secret short enough for a single chunk.
+    fn deal(threshold: u32, n: usize) -> Vec<Share> {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let shares: Vec<Share> = Sharks(threshold)
+            .dealer_rng(&Secret::from_bytes(&[1]), &mut rng)
+            .take(n)
+            .collect();
+        assert_eq!(shares[0].y.len(), 1);
+        shares
+    }
+
+    fn stats(inversions: usize, multiplications: usize) -> InterpolationStats {
+        InterpolationStats {
+            inversions,
+            multiplications,
+        }
+    }
+
+    #[test]
+    fn test_lagrange_counts() {
+        let shares = deal(3, 3);
+        take_stats();
+        interpolate(&shares).unwrap();
+        // Each of the 3 numerators and denominators is a product of 2
+        // terms (12), the batch inversion takes 3 prefix products and 3
+        // per element on the way back (12), and each share is weighted
+        // by its basis element (3). Inverting each denominator
+        // separately would take 3 inversions.
+        assert_eq!(take_stats(), stats(1, 27));
+
+        let mut ctx = InterpolationCtx::new();
+        interpolate_with_ctx(&mut ctx, &shares).unwrap();
+        assert_eq!(take_stats(), stats(1, 27));
+        assert_eq!(take_stats(), Default::default());
+    }
+
+    #[test]
+    fn test_newton_counts() {
+        let shares = deal(3, 3);
+        let mut recovery = Sharks(3).recovery();
+        take_stats();
+        for share in &shares {
+            recovery.add(share).unwrap();
+        }
+        recovery.recover_fp().unwrap();
+        // Adding the `k`th share takes a product of `k` terms, one
+        // inversion, `k` multiplications to evaluate the polynomial so
+        // far and one to scale the new coefficient: 1 + 3 + 5. Evaluating
+        // at zero takes another 3.
+        assert_eq!(take_stats(), stats(3, 12));
+    }
+
+    #[test]
+    fn test_consistency_counts() {
+        let shares = deal(2, 3);
+        take_stats();
+        let (_, consistency) = Sharks(2).recover_with_consistency(&shares).unwrap();
+        assert_eq!(consistency.score(), Some(1.0));
+        // Two interpolations over 2 shares, one at the extra share and
+        // one at zero, each taking 2 * 2 for the basis terms, 4 * 2 for
+        // the batch inversion, and 2 to weight the shares.
+        assert_eq!(take_stats(), stats(2, 28));
+    }
+
+    #[test]
+    fn test_single_counts() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let sharks = Sharks(3);
+        let shares: Vec<Share> = sharks
+            .dealer_single_rng(Fp::from(7), &mut rng)
+            .take(3)
+            .collect();
+        take_stats();
+        assert_eq!(sharks.recover_single(&shares), Ok(Fp::from(7)));
+        // The product of the 3 x coordinates (3), 2 terms of each
+        // denominator (6), 3 to accumulate each share (9), and 2 to
+        // scale the sum.
+        assert_eq!(take_stats(), stats(1, 20));
+    }
+}
@@ -0,0 +1,196 @@
+//! A text encoding of shares for printing as QR codes.
+//!
+//! QR codes hold text most compactly in alphanumeric mode, whose
+//! character set is the digits, the uppercase letters, and a few
+//! symbols. A payload is `QR_PAYLOAD_PREFIX` followed by the unpadded
+//! base32 (RFC 4648) encoding of the serialized share and a checksum,
+//! which all fall in that set. The checksum is the first
+//! `QR_CHECKSUM_LEN` bytes of the SHA-256 digest of the serialized
+//! share, and catches misread or mistyped payloads.
+//!
+//! A share of a single chunk secret is 130 characters long, which fits
+//! a version 6 QR code with medium error correction.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use sha2::{Digest, Sha256};
+
+use crate::share_ff::Share;
+
+/// The prefix of every QR payload, identifying the encoding.
+pub const QR_PAYLOAD_PREFIX: &str = "SHARKS1:";
+
+/// Length of the checksum appended to the share, in bytes.
+pub const QR_CHECKSUM_LEN: usize = 4;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+impl Share {
+    /// Encodes this share as a checksummed string for a QR code in
+    /// alphanumeric mode. See the `qr` module for the format.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let share = Sharks(2).dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng).next().unwrap();
+    /// let payload = share.to_qr_payload();
+    /// assert!(payload.starts_with("SHARKS1:"));
+    /// assert_eq!(Share::from_qr_payload(&payload), Ok(share));
+    pub fn to_qr_payload(&self) -> String {
+        let mut bytes = Vec::from(self);
+        let checksum = qr_checksum(&bytes);
+        bytes.extend(&checksum);
+        let mut payload = String::from(QR_PAYLOAD_PREFIX);
+        payload.push_str(&base32_encode(&bytes));
+        payload
+    }
+
+    /// Decodes a share from a string created by `to_qr_payload`.
+    /// Lowercase letters are accepted, for payloads typed in by hand.
+    /// Returns an `Err` if the prefix, encoding or checksum is invalid.
+    pub fn from_qr_payload(payload: &str) -> Result<Share, &'static str> {
+        let encoded = payload
+            .strip_prefix(QR_PAYLOAD_PREFIX)
+            .ok_or("QR payload does not start with QR_PAYLOAD_PREFIX")?;
+        let bytes = base32_decode(encoded).ok_or("QR payload is not valid base32")?;
+        if bytes.len() < QR_CHECKSUM_LEN {
+            return Err("QR payload is too short");
+        }
+        let (share, checksum) = bytes.split_at(bytes.len() - QR_CHECKSUM_LEN);
+        if qr_checksum(share) != checksum {
+            return Err("QR payload checksum does not match");
+        }
+        Share::try_from(share)
+    }
+}
+
+fn qr_checksum(bytes: &[u8]) -> [u8; QR_CHECKSUM_LEN] {
+    let digest = Sha256::digest(bytes);
+    let mut checksum = [0u8; QR_CHECKSUM_LEN];
+    checksum.copy_from_slice(&digest[..QR_CHECKSUM_LEN]);
+    checksum
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 8 / 5 + 1);
+    let (mut buffer, mut bits) = (0u16, 0);
+    for &byte in bytes {
+        buffer = buffer << 8 | byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    out
+}
+
+// Decodes unpadded base32, rejecting characters outside the alphabet
+// and non-zero trailing bits, so that each byte string has exactly one
+// encoding.
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u16, 0);
+    for c in encoded.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c.to_ascii_uppercase())?;
+        buffer = buffer << 5 | value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    if bits >= 5 || buffer & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base32_decode, base32_encode, QR_PAYLOAD_PREFIX};
+    use crate::{Secret, Share, Sharks};
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_base32() {
+        // RFC 4648 test vectors, without padding
+        let vectors = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+        for (bytes, encoded) in &vectors {
+            assert_eq!(base32_encode(bytes.as_bytes()), *encoded);
+            assert_eq!(base32_decode(encoded).unwrap(), bytes.as_bytes());
+        }
+        assert!(base32_decode("MZXW6YTB1").is_none());
+        // Non-zero trailing bits, and a dangling character
+        assert!(base32_decode("MZ").is_none());
+        assert!(base32_decode("MZXW6YTBO").is_none());
+    }
+
+    #[test]
+    fn test_qr_payload_roundtrip() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let shares: Vec<Share> = Sharks(3)
+            .dealer_rng(&Secret::from_bytes(&[0xab; 27]), &mut rng)
+            .take(3)
+            .collect();
+        for share in &shares {
+            let payload = share.to_qr_payload();
+            // Only characters of the QR alphanumeric set
+            assert!(payload
+                .bytes()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == b':'));
+            assert_eq!(payload.len(), 130);
+            assert_eq!(Share::from_qr_payload(&payload).as_ref(), Ok(share));
+            let lower = String::from(QR_PAYLOAD_PREFIX) + &payload[8..].to_lowercase();
+            assert_eq!(Share::from_qr_payload(&lower).as_ref(), Ok(share));
+        }
+        let recovered: Vec<Share> = shares
+            .iter()
+            .map(|s| Share::from_qr_payload(&s.to_qr_payload()).unwrap())
+            .collect();
+        assert_eq!(
+            Sharks(3).recover(&recovered).unwrap().as_bytes(),
+            [0xab; 27]
+        );
+    }
+
+    #[test]
+    fn test_qr_payload_detects_corruption() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let share = Sharks(2)
+            .dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng)
+            .next()
+            .unwrap();
+        let payload = share.to_qr_payload();
+
+        // Every single character substitution is caught
+        for i in QR_PAYLOAD_PREFIX.len()..payload.len() {
+            let mut corrupt = payload.clone().into_bytes();
+            corrupt[i] = if corrupt[i] == b'A' { b'B' } else { b'A' };
+            let corrupt = String::from_utf8(corrupt).unwrap();
+            assert!(Share::from_qr_payload(&corrupt).is_err());
+        }
+        assert!(Share::from_qr_payload(&payload[1..]).is_err());
+        assert!(Share::from_qr_payload(&payload[..payload.len() - 2]).is_err());
+        assert!(Share::from_qr_payload(&(payload.clone() + "=")).is_err());
+        assert!(Share::from_qr_payload(QR_PAYLOAD_PREFIX).is_err());
+    }
+}
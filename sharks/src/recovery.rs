@@ -0,0 +1,242 @@
+//! Incremental recovery, for shares which arrive one at a time.
+//!
+//! `Recovery` keeps the polynomials through the shares added so far in
+//! Newton's form, `c_0 + (x - x_0)(c_1 + (x - x_1)(c_2 + ...))`. Adding a
+//! share only appends a coefficient to each polynomial: the new one is
+//! the difference between the share and the current polynomial at its
+//! `x`, divided by `prod_i (x - x_i)`. That takes O(n) multiplications
+//! and a single inversion, shared by all chunks, where recomputing the
+//! Lagrange basis for each new share would take O(n^2).
+//!
+//! The polynomial through a set of points is unique, so the secret is
+//! exactly the one `recover` finds from the same shares.
+
+use alloc::vec::Vec;
+
+use crate::ff::Field;
+use crate::share_ff::{decode_secret, invert, mul, Fp, Share, NAMESPACE_LEN};
+use crate::{RecoverError, Secret, Sharks};
+
+/// Recovers a secret from shares added one at a time, created by
+/// `Sharks::recovery`.
+///
+/// Shares are checked as they are added, as `recover` checks them, and
+/// the first `threshold` distinct shares are used. Later shares, and
+/// shares at an `x` coordinate already added, are ignored.
+///
+/// Example:
+/// ```
+/// # use sharks::{ Secret, Sharks, Share };
+/// # use rand_chacha::rand_core::SeedableRng;
+/// # let sharks = Sharks(3);
+/// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+/// let shares: Vec<Share> = sharks.dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng).take(3).collect();
+/// let mut recovery = sharks.recovery();
+/// for share in &shares {
+///     assert!(!recovery.is_complete());
+///     recovery.add(share).unwrap();
+/// }
+/// assert_eq!(recovery.recover().unwrap().as_bytes(), vec![1, 2]);
+#[derive(Debug, Clone)]
+pub struct Recovery {
+    threshold: u32,
+    version: u32,
+    namespace: Option<[u8; NAMESPACE_LEN]>,
+    xs: Vec<Fp>,
+    // The Newton coefficients of each chunk's polynomial
+    coeffs: Vec<Vec<Fp>>,
+}
+
+impl Sharks {
+    /// Starts recovering a secret from shares added one at a time. See
+    /// `Recovery`.
+    pub fn recovery(&self) -> Recovery {
+        Recovery {
+            threshold: self.0,
+            version: 0,
+            namespace: None,
+            xs: Vec::with_capacity(self.0 as usize),
+            coeffs: Vec::new(),
+        }
+    }
+}
+
+impl Recovery {
+    /// Adds `share` to those the secret is recovered from. Returns the
+    /// same errors as `recover` would for it: `ThresholdMismatch`,
+    /// `VersionMismatch`, `NamespaceMismatch`, or
+    /// `InconsistentShareLength`. A share which
+    /// is rejected isn't added.
+    pub fn add(&mut self, share: &Share) -> Result<(), RecoverError> {
+        if share.threshold != self.threshold {
+            return Err(RecoverError::ThresholdMismatch {
+                expected: self.threshold,
+                found: share.threshold,
+            });
+        }
+        if self.xs.is_empty() {
+            self.version = share.version;
+            self.namespace = share.namespace;
+            self.coeffs = (0..share.y.len())
+                .map(|_| Vec::with_capacity(self.threshold as usize))
+                .collect();
+        } else if share.version != self.version {
+            return Err(RecoverError::VersionMismatch {
+                expected: self.version,
+                found: share.version,
+            });
+        } else if share.namespace != self.namespace {
+            return Err(RecoverError::NamespaceMismatch);
+        } else if share.y.len() != self.coeffs.len() {
+            return Err(RecoverError::InconsistentShareLength);
+        }
+        if self.is_complete() || self.xs.contains(&share.x) {
+            return Ok(());
+        }
+
+        // `prod_i (x - x_i)` is never zero, as the `x_i` are distinct
+        let x = share.x;
+        let scale = invert(&self.xs.iter().fold(Fp::one(), |acc, x_i| mul(acc, x - x_i)))?;
+        for (coeffs, y) in self.coeffs.iter_mut().zip(&share.y) {
+            let value = newton_eval(&self.xs, coeffs, x);
+            coeffs.push(mul(*y - value, scale));
+        }
+        self.xs.push(x);
+        Ok(())
+    }
+
+    /// The number of distinct shares added so far.
+    pub fn len(&self) -> usize {
+        self.xs.len()
+    }
+
+    /// Whether no shares have been added.
+    pub fn is_empty(&self) -> bool {
+        self.xs.is_empty()
+    }
+
+    /// Whether a threshold of distinct shares has been added, so that
+    /// the secret can be recovered.
+    pub fn is_complete(&self) -> bool {
+        self.xs.len() >= self.threshold as usize && !self.xs.is_empty()
+    }
+
+    /// Returns the recovered field elements, one per chunk, as
+    /// `Sharks::recover_fp` does.
+    pub fn recover_fp(&self) -> Result<Vec<Fp>, RecoverError> {
+        if !self.is_complete() {
+            return Err(RecoverError::NotEnoughShares);
+        }
+        Ok(self
+            .coeffs
+            .iter()
+            .map(|coeffs| newton_eval(&self.xs, coeffs, Fp::zero()))
+            .collect())
+    }
+
+    /// Returns the recovered secret, as `Sharks::recover` does.
+    pub fn recover(&self) -> Result<Secret, RecoverError> {
+        decode_secret(&self.recover_fp()?)
+            .map(Secret::from)
+            .ok_or(RecoverError::InvalidEncoding)
+    }
+}
+
+// Evaluates the polynomial with Newton coefficients `coeffs` over the
+// points `xs` at `x`. There may be one fewer coefficient than points,
+// while a share is being added.
+fn newton_eval(xs: &[Fp], coeffs: &[Fp], x: Fp) -> Fp {
+    coeffs
+        .iter()
+        .zip(xs)
+        .rev()
+        .fold(Fp::zero(), |acc, (c, x_i)| mul(acc, x - x_i) + c)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ff::Field;
+    use crate::{get_evaluator, interpolate_at, random_polynomial};
+    use crate::{Fp, RecoverError, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_newton_matches_lagrange() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        for &threshold in &[1, 2, 5, 30] {
+            let sharks = Sharks(threshold);
+            let polys = (0..3)
+                .map(|_| random_polynomial(Fp::random(&mut rng), threshold, &mut rng))
+                .collect();
+            let shares: Vec<Share> = get_evaluator(polys).take(threshold as usize + 3).collect();
+
+            // Any order and subset of the shares recovers the same
+            // elements as Lagrange interpolation
+            for shares in [&shares[..threshold as usize], &shares[3..]] {
+                let mut recovery = sharks.recovery();
+                for share in shares.iter().rev() {
+                    recovery.add(share).unwrap();
+                }
+                assert!(recovery.is_complete());
+                assert_eq!(recovery.recover_fp(), sharks.recover_fp(shares));
+                assert_eq!(
+                    recovery.recover_fp().unwrap(),
+                    interpolate_at(shares, Fp::zero()).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_recovery_checks_shares() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let shares: Vec<Share> = sharks
+            .dealer_rng(&Secret::from_bytes(&[7; 40]), &mut rng)
+            .take(4)
+            .collect();
+
+        let mut recovery = sharks.recovery();
+        assert!(recovery.is_empty());
+        assert_eq!(recovery.recover(), Err(RecoverError::NotEnoughShares));
+        recovery.add(&shares[0]).unwrap();
+        recovery.add(&shares[0]).unwrap();
+        assert_eq!(recovery.len(), 1);
+
+        let other: Vec<Share> = Sharks(2)
+            .dealer_rng(&Secret::from_bytes(&[7]), &mut rng)
+            .with_version(1)
+            .take(1)
+            .collect();
+        assert_eq!(
+            recovery.add(&other[0]),
+            Err(RecoverError::ThresholdMismatch {
+                expected: 3,
+                found: 2
+            })
+        );
+        let mut short = shares[1].clone();
+        short.y.pop();
+        assert_eq!(
+            recovery.add(&short),
+            Err(RecoverError::InconsistentShareLength)
+        );
+        let mut versioned = shares[1].clone();
+        versioned.version = 1;
+        assert_eq!(
+            recovery.add(&versioned),
+            Err(RecoverError::VersionMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(recovery.recover(), Err(RecoverError::NotEnoughShares));
+
+        for share in &shares[1..] {
+            recovery.add(share).unwrap();
+        }
+        assert_eq!(recovery.len(), 3);
+        assert_eq!(recovery.recover().unwrap().as_bytes(), [7; 40]);
+    }
+}
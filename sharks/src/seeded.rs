@@ -0,0 +1,307 @@
+//! Dealing where some shareholders keep a short seed instead of their
+//! share.
+//!
+//! A polynomial of degree `threshold - 1` is fixed by its values at
+//! `threshold` points. `seeded_dealer_rng` samples a random seed for
+//! each of the first `threshold - 1` shareholders, derives the `y`
+//! coordinates of their shares from the seeds, and interpolates the
+//! polynomials through those shares and the secret at zero. These
+//! shareholders then only need to store their seed, and can regenerate
+//! their share with `Share::from_seed` when it is needed, trading
+//! storage for recomputation. Any further shares are evaluated from the
+//! polynomials as usual.
+//!
+//! Seeds are expanded with SHA-512 and reduced modulo the field order,
+//! so the seeded `y` coordinates are uniformly distributed as long as
+//! the seeds are, and the polynomials are as random as those of
+//! `dealer_rng`. A seed is equivalent to its share: anyone who learns it
+//! can regenerate the share, so seeds must be kept as carefully as
+//! shares are.
+//!
+//! `deterministic_dealer` goes further, and derives every coefficient
+//! of the polynomials from a single master seed, for escrow schemes
+//! where a recovery agent must be able to regenerate any shareholder's
+//! share without storing it. The coefficients of each chunk are the
+//! SHA-512 digest of the seed, the threshold, the encoded secret and the
+//! position of the coefficient, reduced modulo the field order. Mixing
+//! in the secret means that sharing two different secrets under the same
+//! seed doesn't give shares whose difference is the difference of the
+//! secrets. The master seed is as sensitive as the secret itself: with
+//! it, a single share is enough to check guesses of the secret, and the
+//! seed together with the secret gives every share.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter;
+use sha2::{Digest, Sha512};
+
+use crate::ff::{Field, PrimeField};
+use crate::share_ff::{encode_secret, get_evaluator, Evaluator, Fp, Share};
+use crate::{Secret, Sharks};
+
+/// Length of a shareholder seed, in bytes.
+pub const SEED_LEN: usize = 32;
+
+impl Share {
+    /// Regenerates the share at `x` of a dealing by `seeded_dealer_rng`
+    /// from the shareholder's `seed`. The `threshold` and number of
+    /// `chunks` of the dealing must be those of the original share,
+    /// whose `version` is not recorded, so the regenerated share has
+    /// version 0.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Fp, Secret, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let (seeds, dealer) = sharks.seeded_dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng);
+    /// // The first two shareholders keep seeds, the rest shares
+    /// let mut shares: Vec<Share> = dealer.take(2).collect();
+    /// shares.push(Share::from_seed(&seeds[1], Fp::from(2), 3, 1));
+    /// assert_eq!(sharks.recover(&shares).unwrap().as_bytes(), vec![1, 2]);
+    pub fn from_seed(seed: &[u8; SEED_LEN], x: Fp, threshold: u32, chunks: usize) -> Share {
+        let y = (0..chunks as u32)
+            .map(|chunk| {
+                let digest = Sha512::new()
+                    .chain(b"sharks_seeded_share")
+                    .chain(seed)
+                    .chain(x.to_repr())
+                    .chain(threshold.to_le_bytes())
+                    .chain(chunk.to_le_bytes())
+                    .finalize();
+                let mut wide = [0u8; 64];
+                wide.copy_from_slice(&digest);
+                Fp::from_bytes_wide(&wide)
+            })
+            .collect();
+        Share {
+            x,
+            y,
+            threshold,
+            version: 0,
+            namespace: None,
+        }
+    }
+}
+
+impl Sharks {
+    /// Like `dealer_rng`, but the shares at `x = 1..threshold` are
+    /// derived from random seeds, which are returned alongside the
+    /// `Evaluator` for the remaining shares. The seed at index `i` is
+    /// for the share at `x = i + 1`, which `Share::from_seed`
+    /// regenerates, and the `Evaluator` continues with the shares from
+    /// `x = threshold`. See the module documentation for the security
+    /// model.
+    pub fn seeded_dealer_rng<R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        rng: &mut R,
+    ) -> (Vec<[u8; SEED_LEN]>, Evaluator) {
+        let elements = encode_secret(secret.as_bytes());
+        let seeds: Vec<[u8; SEED_LEN]> = (1..self.0)
+            .map(|_| {
+                let mut seed = [0u8; SEED_LEN];
+                rng.fill_bytes(&mut seed);
+                seed
+            })
+            .collect();
+        let seeded: Vec<Share> = seeds
+            .iter()
+            .zip(1u64..)
+            .map(|(seed, x)| Share::from_seed(seed, Fp::from(x), self.0, elements.len()))
+            .collect();
+
+        let xs: Vec<Fp> = iter::once(Fp::zero())
+            .chain(seeded.iter().map(|s| s.x))
+            .collect();
+        let basis = lagrange_polynomials(&xs);
+        let polys = elements
+            .iter()
+            .enumerate()
+            .map(|(chunk, element)| {
+                let ys: Vec<Fp> = iter::once(*element)
+                    .chain(seeded.iter().map(|s| s.y[chunk]))
+                    .collect();
+                // With the constant term last, as in `random_polynomial`
+                (0..xs.len())
+                    .rev()
+                    .map(|k| {
+                        ys.iter()
+                            .zip(&basis)
+                            .fold(Fp::zero(), |acc, (y, b)| acc + *y * b[k])
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut evaluator = get_evaluator(polys);
+        evaluator.threshold = self.0;
+        evaluator.x = Fp::from(seeds.len() as u64);
+        (seeds, evaluator)
+    }
+
+    /// Deals `secret` with polynomials whose coefficients are all
+    /// derived from `master_seed`, so that the same seed and secret
+    /// always give the same shares. A recovery agent holding the seed
+    /// can regenerate the share at any `x` with `Evaluator::gen_at`. See
+    /// the module documentation for the derivation, and why the seed
+    /// must be kept as carefully as the secret.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Fp, Secret, Sharks, Share };
+    /// # let sharks = Sharks(3);
+    /// let secret = Secret::from_bytes(&[1, 2]);
+    /// let shares: Vec<Share> = sharks.deterministic_dealer(b"escrow master seed", &secret).take(3).collect();
+    /// // Later, the agent regenerates the share at x = 2
+    /// let dealer = sharks.deterministic_dealer(b"escrow master seed", &secret);
+    /// assert_eq!(dealer.gen_at(Fp::from(2)), Ok(shares[1].clone()));
+    pub fn deterministic_dealer(&self, master_seed: &[u8], secret: &Secret) -> Evaluator {
+        let elements = encode_secret(secret.as_bytes());
+        let mut encoded = Sha512::new()
+            .chain(b"sharks_deterministic_dealer")
+            .chain(self.0.to_le_bytes())
+            .chain((elements.len() as u64).to_le_bytes());
+        for element in &elements {
+            encoded.update(element.to_repr());
+        }
+        let polys = elements
+            .iter()
+            .zip(0u64..)
+            .map(|(element, chunk)| {
+                // With the constant term last, as in `random_polynomial`
+                (1..self.0)
+                    .rev()
+                    .map(|degree| {
+                        let digest = encoded
+                            .clone()
+                            .chain(chunk.to_le_bytes())
+                            .chain(degree.to_le_bytes())
+                            .chain(master_seed)
+                            .finalize();
+                        let mut wide = [0u8; 64];
+                        wide.copy_from_slice(&digest);
+                        Fp::from_bytes_wide(&wide)
+                    })
+                    .chain(iter::once(*element))
+                    .collect()
+            })
+            .collect();
+
+        let mut evaluator = get_evaluator(polys);
+        evaluator.threshold = self.0;
+        evaluator
+    }
+}
+
+// Returns the coefficients, constant term first, of the Lagrange basis
+// polynomials for the distinct points `xs`: the `j`th polynomial is one
+// at `xs[j]` and zero at the other points. Each is the product of
+// `z - x_k` over all of the points, divided by `z - x_j` and by its
+// value at `x_j`.
+fn lagrange_polynomials(xs: &[Fp]) -> Vec<Vec<Fp>> {
+    let n = xs.len();
+    let mut product = vec![Fp::one()];
+    for x in xs {
+        let mut next = vec![Fp::zero(); product.len() + 1];
+        for (k, c) in product.iter().enumerate() {
+            next[k + 1] += c;
+            next[k] -= *c * x;
+        }
+        product = next;
+    }
+
+    xs.iter()
+        .map(|x_j| {
+            // Synthetic division by `z - x_j`
+            let mut quotient = vec![Fp::zero(); n];
+            quotient[n - 1] = product[n];
+            for k in (1..n).rev() {
+                quotient[k - 1] = product[k] + *x_j * quotient[k];
+            }
+            let value = quotient
+                .iter()
+                .rev()
+                .fold(Fp::zero(), |acc, c| acc * x_j + c);
+            let scale = value.invert().unwrap();
+            quotient.iter().map(|c| *c * scale).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lagrange_polynomials;
+    use crate::ff::Field;
+    use crate::{get_evaluator, Fp, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_lagrange_polynomials() {
+        let xs = [Fp::zero(), Fp::from(3), Fp::from(7), Fp::from(8)];
+        for (j, basis) in lagrange_polynomials(&xs).iter().enumerate() {
+            for (k, x) in xs.iter().enumerate() {
+                let value = basis.iter().rev().fold(Fp::zero(), |acc, c| acc * x + c);
+                assert_eq!(value, if j == k { Fp::one() } else { Fp::zero() });
+            }
+        }
+    }
+
+    #[test]
+    fn test_regenerated_shares_match() {
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&[0x5a; 70]);
+        for &threshold in &[1u32, 2, 5] {
+            let sharks = Sharks(threshold);
+            let (seeds, dealer) = sharks.seeded_dealer_rng(&secret, &mut rng);
+            assert_eq!(seeds.len(), threshold as usize - 1);
+
+            // All of the shares of the dealt polynomials, from x = 1
+            let all: Vec<Share> = get_evaluator(dealer.polys.clone())
+                .take(threshold as usize + 2)
+                .collect();
+            let chunks = all[0].y.len();
+            for (i, seed) in seeds.iter().enumerate() {
+                let regenerated = Share::from_seed(seed, Fp::from(i as u64 + 1), threshold, chunks);
+                assert_eq!(regenerated, all[i]);
+            }
+            let dealt: Vec<Share> = dealer.take(3).collect();
+            assert_eq!(dealt, all[seeds.len()..seeds.len() + 3]);
+
+            assert_eq!(sharks.recover(&all).unwrap().as_bytes(), secret.as_bytes());
+            assert_eq!(sharks.recover(&dealt[..1]).is_ok(), threshold == 1);
+        }
+    }
+
+    #[test]
+    fn test_deterministic_dealer() {
+        let sharks = Sharks(3);
+        let secret = Secret::from_bytes(&[7; 40]);
+        let seed = b"escrow master seed";
+        let shares: Vec<Share> = sharks.deterministic_dealer(seed, &secret).take(5).collect();
+        assert_eq!(shares[0].y.len(), 2);
+        assert_eq!(shares[0].threshold, 3);
+        assert_eq!(sharks.recover(&shares[2..]).unwrap().as_bytes(), [7; 40]);
+
+        // A second evaluator from the same seed gives the same shares,
+        // in any order
+        let again = sharks.deterministic_dealer(seed, &secret);
+        for (i, share) in shares.iter().enumerate().rev() {
+            let x = Fp::from(i as u64 + 1);
+            assert_eq!(again.gen_at(x).as_ref(), Ok(share));
+        }
+        assert!(again.gen_at(Fp::zero()).is_err());
+
+        // Changing the seed, the secret or the threshold changes the
+        // polynomials
+        let other_seed = sharks.deterministic_dealer(b"another seed", &secret);
+        let other_secret = sharks.deterministic_dealer(seed, &Secret::from_bytes(&[8; 40]));
+        let other_threshold = Sharks(4).deterministic_dealer(seed, &secret);
+        for other in &[other_seed, other_secret, other_threshold] {
+            let share = other.gen_at(Fp::from(1)).unwrap();
+            assert!(share.y.iter().zip(&shares[0].y).all(|(a, b)| a != b));
+        }
+    }
+}
@@ -1,13 +1,27 @@
 use alloc::vec::*;
-use core::convert::TryInto;
+use core::borrow::Borrow;
+use core::convert::{TryFrom, TryInto};
+use rand::seq::SliceRandom;
+use sha2::{Digest, Sha512};
 
 #[cfg(feature = "fuzzing")]
 use arbitrary::Arbitrary;
 
 use crate::ff::*;
+use crate::RecoverError;
 
 pub const FIELD_ELEMENT_LEN: usize = 32;
 
+/// An element of the scalar field of BLS12-381, which shares are
+/// computed over.
+///
+/// The canonical serialization of a field element, returned by
+/// `to_repr` and used in shares, is its integer value as
+/// `FIELD_ELEMENT_LEN` bytes in little-endian order, least significant
+/// byte first. `from_repr` only accepts canonical values, less than the
+/// field order. `FpRepr::to_be_bytes` and `FpRepr::from_be_bytes`
+/// convert to and from big-endian order, for implementations which
+/// expect it.
 #[cfg_attr(feature = "fuzzing", derive(Arbitrary))]
 #[derive(PrimeField)]
 #[PrimeFieldModulus = "52435875175126190479447740508185965837690552500527637822603658699938581184513"]
@@ -15,6 +29,58 @@ pub const FIELD_ELEMENT_LEN: usize = 32;
 #[PrimeFieldReprEndianness = "little"]
 pub struct Fp([u64; 4]);
 
+impl Fp {
+    // Maps 64 bytes, such as a hash output, to a field element by
+    // reducing them as a little-endian integer modulo the field order.
+    // The result is close enough to uniform for hashing to the field.
+    pub fn from_bytes_wide(bytes: &[u8; 64]) -> Fp {
+        Fp::from_bytes_mod_order(bytes)
+    }
+
+    // Maps any number of bytes to a field element by reducing them as a
+    // little-endian integer modulo the field order. Unlike `from_repr`,
+    // this never rejects its input.
+    pub fn from_bytes_mod_order(bytes: &[u8]) -> Fp {
+        // Accumulate the 64-bit limbs, starting from the most significant
+        let limb_base = Fp::from(u64::MAX) + Fp::one();
+        bytes.chunks(8).rev().fold(Fp::zero(), |acc, chunk| {
+            let mut limb = [0u8; 8];
+            limb[..chunk.len()].copy_from_slice(chunk);
+            acc * limb_base + Fp::from(u64::from_le_bytes(limb))
+        })
+    }
+}
+
+impl FpRepr {
+    /// Returns the value in big-endian byte order, most significant
+    /// byte first, which is the reverse of the canonical order.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Fp, FpRepr };
+    /// # use ff::PrimeField;
+    /// let repr = Fp::from(0x0102).to_repr();
+    /// assert_eq!(repr.as_ref()[..2], [0x02, 0x01]);
+    /// assert_eq!(repr.to_be_bytes()[30..], [0x01, 0x02]);
+    /// assert_eq!(FpRepr::from_be_bytes(repr.to_be_bytes()), repr);
+    pub fn to_be_bytes(&self) -> [u8; FIELD_ELEMENT_LEN] {
+        let mut bytes = [0u8; FIELD_ELEMENT_LEN];
+        bytes.copy_from_slice(self.as_ref());
+        bytes.reverse();
+        bytes
+    }
+
+    /// Creates a representation from its value in big-endian byte
+    /// order. As for any representation, `Fp::from_repr` checks that it
+    /// is canonical.
+    pub fn from_be_bytes(bytes: [u8; FIELD_ELEMENT_LEN]) -> FpRepr {
+        let mut repr = FpRepr::default();
+        repr.as_mut().copy_from_slice(&bytes);
+        repr.as_mut().reverse();
+        repr
+    }
+}
+
 impl From<Fp> for Vec<u8> {
     fn from(s: Fp) -> Vec<u8> {
         s.to_repr().as_ref().to_vec()
@@ -27,29 +93,333 @@ impl From<Fp> for Vec<u64> {
     }
 }
 
+// Number of secret bytes packed into each field element by
+// `encode_secret`. One byte less than `FIELD_ELEMENT_LEN`, so that every
+// chunk is guaranteed to be a canonical field element.
+pub const SECRET_CHUNK_LEN: usize = FIELD_ELEMENT_LEN - 1;
+
+// Length of the secret length prefix used by `encode_secret`.
+const SECRET_LEN_PREFIX_LEN: usize = 4;
+
+// The longest secret that can be dealt, which is limited by the `u32`
+// length prefix of `encode_secret`.
+pub const MAX_SECRET_LEN: usize = u32::MAX as usize;
+
+// Encodes a secret of arbitrary length as a vector of field elements.
+//
+// The secret is prefixed with its length as a little-endian `u32`, and
+// the result is split into chunks of `SECRET_CHUNK_LEN` bytes, zero
+// padding the final chunk. Each chunk is read as the little-endian
+// representation of a field element with the most significant byte
+// set to zero. The encoding is reversed by `decode_secret`. Panics if
+// the secret is longer than `MAX_SECRET_LEN`.
+pub fn encode_secret(secret: &[u8]) -> Vec<Fp> {
+    let len = u32::try_from(secret.len()).expect("Secret is longer than MAX_SECRET_LEN");
+    let mut bytes = Vec::with_capacity(SECRET_LEN_PREFIX_LEN + secret.len());
+    bytes.extend(len.to_le_bytes());
+    bytes.extend(secret);
+    bytes
+        .chunks(SECRET_CHUNK_LEN)
+        .map(|chunk| {
+            let mut repr = [0u8; FIELD_ELEMENT_LEN];
+            repr[..chunk.len()].copy_from_slice(chunk);
+            Fp::from_repr(FpRepr(repr)).unwrap()
+        })
+        .collect()
+}
+
+// Decodes a secret encoded with `encode_secret`. Returns `None` if the
+// field elements are not a valid encoding.
+pub fn decode_secret(elements: &[Fp]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(elements.len() * SECRET_CHUNK_LEN);
+    for e in elements {
+        let repr = e.to_repr();
+        if repr.as_ref()[SECRET_CHUNK_LEN] != 0 {
+            return None;
+        }
+        bytes.extend(&repr.as_ref()[..SECRET_CHUNK_LEN]);
+    }
+    if bytes.len() < SECRET_LEN_PREFIX_LEN {
+        return None;
+    }
+    let len = u32::from_le_bytes(bytes[..SECRET_LEN_PREFIX_LEN].try_into().unwrap()) as usize;
+    let end = SECRET_LEN_PREFIX_LEN.checked_add(len)?;
+    if end > bytes.len() || bytes[end..].iter().any(|&b| b != 0) {
+        return None;
+    }
+    bytes.truncate(end);
+    Some(bytes.split_off(SECRET_LEN_PREFIX_LEN))
+}
+
+// As `decode_secret`, but decodes straight into an array of `N` bytes
+// rather than a `Vec`. Returns `SecretLengthMismatch` if the encoded
+// secret is valid but not `N` bytes long.
+pub(crate) fn decode_secret_array<const N: usize>(
+    elements: &[Fp],
+) -> Result<[u8; N], RecoverError> {
+    let mut prefix = [0u8; SECRET_LEN_PREFIX_LEN];
+    let mut array = [0u8; N];
+    // Bytes decoded so far, and the end of the last non-zero byte after
+    // the prefix
+    let mut decoded = 0;
+    let mut data_end = 0;
+    for e in elements {
+        let repr = e.to_repr();
+        if repr.as_ref()[SECRET_CHUNK_LEN] != 0 {
+            zeroize_bytes(&mut array);
+            return Err(RecoverError::InvalidEncoding);
+        }
+        for &b in &repr.as_ref()[..SECRET_CHUNK_LEN] {
+            if decoded < SECRET_LEN_PREFIX_LEN {
+                prefix[decoded] = b;
+            } else {
+                let i = decoded - SECRET_LEN_PREFIX_LEN;
+                if i < N {
+                    array[i] = b;
+                }
+                if b != 0 {
+                    data_end = i + 1;
+                }
+            }
+            decoded += 1;
+        }
+    }
+    let len = u32::from_le_bytes(prefix) as usize;
+    if decoded < SECRET_LEN_PREFIX_LEN || decoded - SECRET_LEN_PREFIX_LEN < len || data_end > len {
+        zeroize_bytes(&mut array);
+        return Err(RecoverError::InvalidEncoding);
+    }
+    if len != N {
+        zeroize_bytes(&mut array);
+        return Err(RecoverError::SecretLengthMismatch {
+            expected: N,
+            found: len,
+        });
+    }
+    Ok(array)
+}
+
 // Finds the [root of the Lagrange polynomial](https://en.wikipedia.org/wiki/Shamir%27s_Secret_Sharing#Computationally_efficient_approach).
 // The expected `shares` argument format is the same as the output by the `get_evaluator´ function.
 // Where each (key, value) pair corresponds to one share, where the key is the `x` and the value is a vector of `y`,
 // where each element corresponds to one of the secret's byte chunks.
-pub fn interpolate(shares: &[Share]) -> Vec<u8> {
-    let res: Vec<Vec<u8>> = (0..shares[0].y.len())
-        .map(|s| {
-            let e: Fp = shares
-                .iter()
-                .map(|s_i| {
-                    let f: Fp = shares
-                        .iter()
-                        .filter(|s_j| s_j.x != s_i.x)
-                        .map(|s_j| s_j.x * (s_j.x - s_i.x).invert().unwrap())
-                        .fold(Fp::one(), |acc, x| acc * x); // take product of all fractions
-                    f * s_i.y[s]
-                })
-                .fold(Fp::zero(), |acc, x| acc + x); // take sum of all field elements
-            Vec::from(e) // turn into byte vector
-        })
-        .collect();
-    res.iter()
-        .fold(Vec::new(), |acc, r| [acc, r.to_vec()].concat())
+// This interpolates whatever polynomial fits the shares it is given, so
+// with fewer shares than the threshold it silently returns a wrong
+// secret. Use `interpolate_checked` to reject such inputs.
+// Returns `DuplicateShareX` if two of the shares have the same `x`
+// coordinate, and `NotEnoughShares` if there are none. The recovered
+// field elements are decoded with `decode_secret`, returning
+// `InvalidEncoding` if they are not a valid secret encoding; use
+// `interpolate_at` at zero for the raw field elements.
+pub fn interpolate(shares: &[Share]) -> Result<Vec<u8>, RecoverError> {
+    decode_secret(&interpolate_elements(shares)?).ok_or(RecoverError::InvalidEncoding)
+}
+
+// As `interpolate`, but returns an error unless all of the shares
+// record the same threshold, and at least that many of them have
+// distinct `x` coordinates. Only the first threshold shares are used.
+pub fn interpolate_checked(shares: &[Share]) -> Result<Vec<u8>, RecoverError> {
+    let threshold = shares
+        .first()
+        .ok_or(RecoverError::NotEnoughShares)?
+        .threshold;
+    if let Some(share) = shares.iter().find(|s| s.threshold != threshold) {
+        return Err(RecoverError::ThresholdMismatch {
+            expected: threshold,
+            found: share.threshold,
+        });
+    }
+    let shares = &shares[..shares.len().min(threshold as usize)];
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|s| s.x == share.x) {
+            return Err(RecoverError::NotEnoughShares);
+        }
+    }
+    if shares.len() < threshold as usize {
+        return Err(RecoverError::NotEnoughShares);
+    }
+    interpolate(shares)
+}
+
+// As `interpolate`, but takes borrowed shares, so that callers holding
+// references don't need to clone them first.
+pub fn interpolate_refs(shares: &[&Share]) -> Result<Vec<u8>, RecoverError> {
+    decode_secret(&interpolate_elements(shares)?).ok_or(RecoverError::InvalidEncoding)
+}
+
+// As `interpolate`, but returns the recovered field elements.
+pub(crate) fn interpolate_elements<S: Borrow<Share>>(
+    shares: &[S],
+) -> Result<Vec<Fp>, RecoverError> {
+    interpolate_at_with_ctx(&mut InterpolationCtx::new(), shares, Fp::zero()).map(<[Fp]>::to_vec)
+}
+
+/// Reusable scratch buffers for `interpolate_with_ctx`.
+///
+/// Interpolating many sets of shares with the same context only
+/// allocates when a set has more shares or chunks than any before it.
+/// The recovered elements are zeroized before the output buffer is
+/// reused, and the buffers are zeroized when the context is dropped.
+#[derive(Debug, Default)]
+pub struct InterpolationCtx {
+    xs: Vec<Fp>,
+    numerators: Vec<Fp>,
+    denominators: Vec<Fp>,
+    basis: Vec<Fp>,
+    output: Vec<Fp>,
+}
+
+impl InterpolationCtx {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Drop for InterpolationCtx {
+    fn drop(&mut self) {
+        zeroize_elements(&mut self.numerators);
+        zeroize_elements(&mut self.denominators);
+        zeroize_elements(&mut self.basis);
+        zeroize_elements(&mut self.output);
+    }
+}
+
+/// As `interpolate`, but borrows its scratch space from `ctx` and
+/// returns the recovered field elements, which are valid until the
+/// next use of `ctx`.
+///
+/// Example:
+/// ```
+/// # use sharks::{ get_evaluator, interpolate_with_ctx, random_polynomial, Fp, InterpolationCtx, Share };
+/// # use rand_chacha::rand_core::SeedableRng;
+/// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+/// let mut ctx = InterpolationCtx::new();
+/// for scalar in [Fp::from(1), Fp::from(2)] {
+///     let evaluator = get_evaluator(vec![random_polynomial(scalar, 3, &mut rng)]);
+///     let shares: Vec<Share> = evaluator.take(3).collect();
+///     assert_eq!(interpolate_with_ctx(&mut ctx, &shares).unwrap(), &[scalar]);
+/// }
+pub fn interpolate_with_ctx<'c>(
+    ctx: &'c mut InterpolationCtx,
+    shares: &[Share],
+) -> Result<&'c [Fp], RecoverError> {
+    interpolate_at_with_ctx(ctx, shares, Fp::zero())
+}
+
+// Evaluates the polynomials through `shares` at `x`, returning one field
+// element per chunk. Interpolating at zero recovers the secret, while
+// interpolating at any other point gives the `y` coordinates of the
+// share at that point.
+pub fn interpolate_at(shares: &[Share], x: Fp) -> Result<Vec<Fp>, RecoverError> {
+    interpolate_at_with_ctx(&mut InterpolationCtx::new(), shares, x).map(<[Fp]>::to_vec)
+}
+
+// Inverts a field element, in constant time. Zero has no inverse, and
+// the only zero denominators in interpolation come from two shares
+// with the same `x` coordinate, so that is the error returned.
+pub(crate) fn invert(value: &Fp) -> Result<Fp, RecoverError> {
+    #[cfg(feature = "profile")]
+    crate::profile::record(1, 0);
+    Option::from(value.invert()).ok_or(RecoverError::DuplicateShareX)
+}
+
+// Multiplies two field elements. Interpolation makes all of its
+// multiplications through here, as it makes its inversions through
+// `invert`, so that the `profile` feature counts them as they happen.
+#[inline]
+pub(crate) fn mul(a: Fp, b: Fp) -> Fp {
+    #[cfg(feature = "profile")]
+    crate::profile::record(0, 1);
+    a * b
+}
+
+// Returns the Lagrange coefficients for the points at `xs`, evaluated
+// at `at`: the `i`th coefficient is `prod_{j != i} (x_j - at) / (x_j - x_i)`,
+// so that for any polynomial `f` of degree less than `xs.len()`,
+// `f(at) = sum_i coefficients[i] * f(xs[i])`. At zero, these are the
+// weights `interpolate` applies to the `y` coordinates of the shares,
+// and they can equally be applied to group elements, such as partial
+// signatures. Returns `DuplicateShareX` if two of the points are the
+// same.
+pub fn lagrange_coefficients(xs: &[Fp], at: Fp) -> Result<Vec<Fp>, RecoverError> {
+    let mut ctx = InterpolationCtx::new();
+    ctx.xs.extend_from_slice(xs);
+    lagrange_basis(&mut ctx, at)?;
+    Ok(core::mem::take(&mut ctx.basis))
+}
+
+// Computes the Lagrange basis at `x` for the points in `ctx.xs` into
+// `ctx.basis`, with a single field inversion for all of the
+// denominators.
+fn lagrange_basis(ctx: &mut InterpolationCtx, x: Fp) -> Result<(), RecoverError> {
+    let InterpolationCtx {
+        xs,
+        numerators,
+        denominators,
+        basis,
+        ..
+    } = ctx;
+    numerators.clear();
+    denominators.clear();
+    // Other points are skipped by position rather than by value, so
+    // that a duplicate point gives a zero denominator rather than a
+    // wrong basis.
+    for (i, x_i) in xs.iter().enumerate() {
+        let (num, den) = xs
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold((Fp::one(), Fp::one()), |(num, den), (_, x_j)| {
+                (mul(num, *x_j - x), mul(den, *x_j - x_i))
+            });
+        numerators.push(num);
+        denominators.push(den);
+    }
+
+    // Batch invert the denominators, using `basis` to hold the prefix
+    // products.
+    basis.clear();
+    let mut acc = Fp::one();
+    for den in denominators.iter() {
+        basis.push(acc);
+        acc = mul(acc, *den);
+    }
+    let mut inv = invert(&acc)?;
+    for i in (0..xs.len()).rev() {
+        let den_inv = mul(basis[i], inv);
+        inv = mul(inv, denominators[i]);
+        basis[i] = mul(numerators[i], den_inv);
+    }
+    Ok(())
+}
+
+// The Lagrange basis at `x` is computed once for all chunks.
+pub(crate) fn interpolate_at_with_ctx<'c, S: Borrow<Share>>(
+    ctx: &'c mut InterpolationCtx,
+    shares: &[S],
+    x: Fp,
+) -> Result<&'c [Fp], RecoverError> {
+    if shares.is_empty() {
+        return Err(RecoverError::NotEnoughShares);
+    }
+    ctx.xs.clear();
+    ctx.xs.extend(shares.iter().map(|s| s.borrow().x));
+    lagrange_basis(ctx, x)?;
+
+    let InterpolationCtx { basis, output, .. } = ctx;
+    // Any earlier output is zeroized first, so that growing the buffer
+    // doesn't free a copy of it
+    zeroize_elements(output);
+    output.clear();
+    output.extend((0..shares[0].borrow().y.len()).map(|chunk| {
+        shares
+            .iter()
+            .zip(basis.iter())
+            .fold(Fp::zero(), |acc, (s_i, b)| {
+                acc + mul(*b, s_i.borrow().y[chunk])
+            })
+    }));
+    Ok(output)
 }
 
 // Generates `k` polynomial coefficients, being the last one `s` and the
@@ -67,38 +437,299 @@ pub fn random_polynomial<R: rand::Rng>(s: Fp, k: u32, rng: &mut R) -> Vec<Fp> {
     poly
 }
 
+// As `random_polynomial`, but draws the coefficients with
+// `try_fill_bytes`, returning `None` rather than panicking if the
+// random number generator fails. Each coefficient is reduced from 64
+// random bytes, so the coefficients differ from those
+// `random_polynomial` draws from the same generator. The random bytes,
+// and the coefficients drawn before any failure, are zeroized.
+pub(crate) fn try_random_polynomial<R: rand::RngCore>(
+    s: Fp,
+    k: u32,
+    rng: &mut R,
+) -> Option<Vec<Fp>> {
+    let k = k as usize;
+    let mut poly = Vec::with_capacity(k);
+    let mut bytes = [0u8; 64];
+    for _ in 1..k {
+        if rng.try_fill_bytes(&mut bytes).is_err() {
+            zeroize_bytes(&mut bytes);
+            zeroize_elements(&mut poly);
+            return None;
+        }
+        poly.push(Fp::from_bytes_wide(&bytes));
+    }
+    zeroize_bytes(&mut bytes);
+    poly.push(s);
+
+    Some(poly)
+}
+
+// Overwrites `elements`, which held secret material, with zeros. With
+// the `zeroize_memory` feature the writes can't be optimized away;
+// without it this is only a best effort.
+pub(crate) fn zeroize_elements(elements: &mut [Fp]) {
+    for element in elements {
+        #[cfg(feature = "zeroize_memory")]
+        zeroize::Zeroize::zeroize(&mut element.0);
+        #[cfg(not(feature = "zeroize_memory"))]
+        {
+            *element = Fp::zero();
+        }
+    }
+}
+
+// As `zeroize_elements`, but for bytes.
+pub(crate) fn zeroize_bytes(bytes: &mut [u8]) {
+    #[cfg(feature = "zeroize_memory")]
+    zeroize::Zeroize::zeroize(bytes);
+    #[cfg(not(feature = "zeroize_memory"))]
+    for byte in bytes {
+        *byte = 0;
+    }
+}
+
+// Evaluates the polynomial with coefficients `coeffs` at `x`, using
+// Horner's method. Coefficients are ordered from the highest degree to
+// the constant term, as `random_polynomial` returns them, so the
+// polynomial evaluates to its last coefficient at zero. An empty
+// polynomial evaluates to zero everywhere.
+pub fn eval_polynomial(coeffs: &[Fp], x: Fp) -> Fp {
+    coeffs.iter().fold(Fp::zero(), |acc, c| acc * x + c)
+}
+
 // Returns an iterator over the points of the `polys` polynomials passed as argument.
 // Each item of the iterator is a tuple `(x, [f_1(x), f_2(x)..])` where eaxh `f_i` is the result for the ith polynomial.
 // Each polynomial corresponds to one byte chunk of the original secret.
+// The threshold recorded in the generated shares is the number of
+// coefficients of the polynomials.
 pub fn get_evaluator(polys: Vec<Vec<Fp>>) -> Evaluator {
+    let threshold = polys.first().map_or(0, |p| p.len() as u32);
     Evaluator {
         polys,
         x: Fp::zero(),
+        threshold,
+        version: 0,
+        namespace: None,
+        last_x: -Fp::one(),
     }
 }
 
 #[derive(Debug)]
 pub struct Evaluator {
-    polys: Vec<Vec<Fp>>,
-    x: Fp,
+    pub(crate) polys: Vec<Vec<Fp>>,
+    pub(crate) x: Fp,
+    pub(crate) threshold: u32,
+    version: u32,
+    namespace: Option<[u8; NAMESPACE_LEN]>,
+    // The `x` of the last share the iterator generates, computed once
+    // so that `next` only has to compare against it
+    last_x: Fp,
 }
 
 impl Evaluator {
-    fn evaluate(&self, x: Fp) -> Share {
+    // Tags the generated shares with the `version` of the secret, so
+    // that they can't be combined with shares of another version of it.
+    // Shares are tagged with version 0 by default.
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    // Tags the generated shares with `namespace`, so that they can't be
+    // combined with shares of a secret from another namespace.
+    pub fn with_namespace(mut self, namespace: [u8; NAMESPACE_LEN]) -> Self {
+        self.namespace = Some(namespace);
+        self
+    }
+
+    pub(crate) fn evaluate(&self, x: Fp) -> Share {
         Share {
             x,
-            y: self
-                .polys
-                .iter()
-                .map(|p| p.iter().fold(Fp::zero(), |acc, c| acc * x + c))
-                .collect(),
+            y: self.polys.iter().map(|p| eval_polynomial(p, x)).collect(),
+            threshold: self.threshold,
+            version: self.version,
+            namespace: self.namespace,
         }
     }
 
+    // Overwrites `share` with the share at `x`, reusing the allocation
+    // of its `y` coordinates.
+    pub(crate) fn evaluate_into(&self, x: Fp, share: &mut Share) {
+        share.x = x;
+        share.y.clear();
+        share
+            .y
+            .extend(self.polys.iter().map(|p| eval_polynomial(p, x)));
+        share.threshold = self.threshold;
+        share.version = self.version;
+        share.namespace = self.namespace;
+    }
+
+    // Returns the length of the longest secret which `encode_secret`
+    // packs into as many chunks as this evaluator's shares have, so
+    // that a fixed-size schema can hold any secret up to this length.
+    // Each chunk holds `SECRET_CHUNK_LEN` bytes, one less than the field
+    // element length so that every chunk is canonical, and the first
+    // chunk also holds the secret length.
+    pub fn max_secret_bytes(&self) -> usize {
+        (self.polys.len() * SECRET_CHUNK_LEN)
+            .saturating_sub(SECRET_LEN_PREFIX_LEN)
+            .min(MAX_SECRET_LEN)
+    }
+
+    // Returns the degree of the polynomials used for generating shares,
+    // which is one less than the threshold of the sharing. Returns
+    // `None` if there are no polynomials, if they have no coefficients,
+    // or if the polynomials for each secret chunk do not share a
+    // degree.
+    pub fn degree(&self) -> Option<usize> {
+        let len = self.polys.first()?.len();
+        if len == 0 || self.polys.iter().any(|p| p.len() != len) {
+            return None;
+        }
+        Some(len - 1)
+    }
+
     pub fn gen<R: rand::Rng>(&self, rng: &mut R) -> Share {
         let rand = Fp::random(rng);
         self.evaluate(rand)
     }
+
+    // Generates the share of the party identified by `id`, at an `x`
+    // coordinate derived deterministically from the `id`, so that the
+    // share can be matched back to its holder without keeping a mapping
+    // of coordinates.
+    pub fn gen_for_id(&self, id: &[u8]) -> Share {
+        self.evaluate(id_to_field(id))
+    }
+
+    // Generates the shares of each of the parties identified by `ids`,
+    // as in `gen_for_id`. Returns an error if two of the ids map to the
+    // same `x` coordinate, as their shares would be identical.
+    pub fn gen_for_ids(&self, ids: &[&[u8]]) -> Result<Vec<Share>, &'static str> {
+        let xs: Vec<Fp> = ids.iter().map(|id| id_to_field(id)).collect();
+        for (i, x) in xs.iter().enumerate() {
+            if xs[..i].contains(x) {
+                return Err("Party ids map to the same x coordinate");
+            }
+        }
+        Ok(xs.into_iter().map(|x| self.evaluate(x)).collect())
+    }
+
+    // Returns the number of shares the `Iterator` can still generate,
+    // or `None` if that doesn't fit in a `u128`. Each share is at the
+    // next `x` coordinate, and the iterator stops before wrapping around
+    // to zero, where the share would be the secret itself, so the
+    // shares never repeat an `x`. Starting from `x = 1`, that allows one
+    // less share than the field order, about 2^254.8, so this is only
+    // `Some` for evaluators that were started near the end of the field.
+    pub fn remaining_shares(&self) -> Option<u128> {
+        // The shares left are at `x + 1..=p - 1`, that is `-1 - x` of them
+        let remaining = (-Fp::one() - self.x).to_repr();
+        let (low, high) = remaining.as_ref().split_at(16);
+        if high.iter().any(|&b| b != 0) {
+            return None;
+        }
+        Some(u128::from_le_bytes(low.try_into().unwrap()))
+    }
+
+    // Generates the share at `x`, so that a dealer which can recreate
+    // its polynomials, such as one from `Sharks::deterministic_dealer`,
+    // can regenerate any single share. Returns an error if `x` is zero,
+    // as the share there would be the secret itself.
+    pub fn gen_at(&self, x: Fp) -> Result<Share, &'static str> {
+        if x.is_zero() {
+            return Err("The x coordinate is zero");
+        }
+        Ok(self.evaluate(x))
+    }
+
+    // Generates the shares at each of `xs`, evaluating them in parallel
+    // on the `rayon` thread pool. The polynomials are fixed, so the
+    // shares are the same as those generated one at a time with
+    // `gen_at`, in the same order. Returns an error if any of `xs` is
+    // zero, where the share would be the secret itself, or if two of
+    // them are equal. Only available with the `parallel` feature.
+    #[cfg(feature = "parallel")]
+    pub fn gen_many_parallel(&self, xs: &[Fp]) -> Result<Vec<Share>, &'static str> {
+        use rayon::prelude::*;
+
+        let mut seen: hashbrown::HashSet<[u8; FIELD_ELEMENT_LEN]> =
+            hashbrown::HashSet::with_capacity(xs.len());
+        for x in xs {
+            if x.is_zero() {
+                return Err("The x coordinate is zero");
+            } else if !seen.insert(x.to_repr().as_ref().try_into().unwrap()) {
+                return Err("The x coordinates are not distinct");
+            }
+        }
+        Ok(xs.par_iter().map(|&x| self.evaluate(x)).collect())
+    }
+
+    // Lazily generates the `count` shares at the contiguous `x`
+    // coordinates starting at `start`, so that several dealers holding
+    // the same polynomials can each produce a disjoint range of shares
+    // without coordinating. Shares from different ranges combine as
+    // usual. Returns an error if the range includes zero, as the share
+    // there would be the secret itself.
+    pub fn gen_range(
+        &self,
+        start: Fp,
+        count: usize,
+    ) -> Result<impl Iterator<Item = Share> + '_, &'static str> {
+        // The range wraps around to zero iff `-start < count`
+        let distance = (-start).to_repr();
+        let (low, high) = distance.as_ref().split_at(8);
+        if count > 0
+            && high.iter().all(|&b| b == 0)
+            && u64::from_le_bytes(low.try_into().unwrap()) < count as u64
+        {
+            return Err("The x coordinate range includes zero");
+        }
+        Ok((0..count as u64).map(move |i| self.evaluate(start + Fp::from(i))))
+    }
+
+    // Generates the `n` shares at `x` coordinates `1..=n`, in an order
+    // shuffled with `rng`. Handing these out in turn means the `x`
+    // coordinate of a share doesn't reveal the order in which its holder
+    // was enrolled, while the coordinates stay small and distinct.
+    pub fn gen_shuffled<R: rand::Rng>(&self, n: usize, rng: &mut R) -> Vec<Share> {
+        let mut xs: Vec<u64> = (1..=n as u64).collect();
+        xs.shuffle(rng);
+        xs.into_iter().map(|x| self.evaluate(Fp::from(x))).collect()
+    }
+}
+
+// Hashes a party id to a non-zero field element, for use as the `x`
+// coordinate of its share. The SHA-512 digest of the id, prefixed with a
+// domain separator and a counter, is reduced modulo the field order. In
+// the negligibly likely case that this gives zero, which would reveal
+// the secret, the counter is incremented and the id hashed again.
+fn id_to_field(id: &[u8]) -> Fp {
+    (0u32..)
+        .map(|counter| {
+            let digest = Sha512::new()
+                .chain(b"sharks_party_id")
+                .chain(counter.to_le_bytes())
+                .chain(id)
+                .finalize();
+            let mut wide = [0u8; 64];
+            wide.copy_from_slice(&digest);
+            Fp::from_bytes_wide(&wide)
+        })
+        .find(|x| !x.is_zero())
+        .unwrap()
+}
+
+// Sampling a share at a random point is the `Randomized` operation of
+// an `Evaluator`.
+impl crate::Randomized for Evaluator {
+    type Output = Share;
+
+    fn with_rng<R: rand::RngCore + rand::CryptoRng>(&self, rng: &mut R) -> Share {
+        self.gen(rng)
+    }
 }
 
 // Implement `Iterator` for `Evaluator`.
@@ -107,23 +738,60 @@ impl Iterator for Evaluator {
     type Item = Share;
 
     fn next(&mut self) -> Option<Share> {
+        if self.x == self.last_x {
+            return None;
+        }
         self.x += Fp::one();
         Some(self.evaluate(self.x))
     }
 }
 
 /// A share used to reconstruct the secret. Can be serialized to and from a byte array.
+///
+/// The serialized form starts with a header containing the `threshold`
+/// the share was dealt with and the `version` of the secret (each as a
+/// little-endian `u32`), followed by the `x` coordinate and the `y`
+/// coordinates. The header of a share tagged with a `namespace` has the
+/// top bit of the threshold set and is followed by the namespace, so
+/// thresholds must be below 2^31.
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "fuzzing", derive(Arbitrary))]
 pub struct Share {
     pub x: Fp,
     pub y: Vec<Fp>,
+    pub threshold: u32,
+    pub version: u32,
+    pub namespace: Option<[u8; NAMESPACE_LEN]>,
 }
 
+/// Length of the serialized `Share` header, in bytes, not counting the
+/// namespace of a namespaced share.
+pub const SHARE_HEADER_LEN: usize = 8;
+
+/// Length of a share namespace, in bytes.
+pub const NAMESPACE_LEN: usize = 32;
+
+// Set in the serialized threshold of a share whose header is followed
+// by a namespace.
+const NAMESPACE_FLAG: u32 = 1 << 31;
+
 /// Obtains a byte vector from a `Share` instance
 impl From<&Share> for Vec<u8> {
     fn from(s: &Share) -> Vec<u8> {
-        let mut bytes: Vec<u8> = Vec::with_capacity(s.y.len() + FIELD_ELEMENT_LEN);
+        let mut bytes: Vec<u8> = Vec::with_capacity(
+            SHARE_HEADER_LEN + NAMESPACE_LEN + (s.y.len() + 1) * FIELD_ELEMENT_LEN,
+        );
+        match &s.namespace {
+            Some(namespace) => {
+                bytes.extend((s.threshold | NAMESPACE_FLAG).to_le_bytes());
+                bytes.extend(s.version.to_le_bytes());
+                bytes.extend(namespace);
+            }
+            None => {
+                bytes.extend(s.threshold.to_le_bytes());
+                bytes.extend(s.version.to_le_bytes());
+            }
+        }
         let repr = s.x.to_repr();
         let x_coord = repr.as_ref().to_vec();
         let y_coords: Vec<u8> =
@@ -141,38 +809,87 @@ impl core::convert::TryFrom<&[u8]> for Share {
     type Error = &'static str;
 
     fn try_from(s: &[u8]) -> Result<Share, Self::Error> {
-        if s.len() < 2 {
-            Err("A Share must be at least 2 bytes long")
-        } else {
-            let x = Fp::from_repr(FpRepr(
-                s[..FIELD_ELEMENT_LEN]
-                    .try_into()
-                    .expect("Failed to parse bytes for x coordinate"),
-            ))
-            .unwrap();
-            let y_coords_bytes = s[FIELD_ELEMENT_LEN..].to_vec();
-            let total_y_coords_len = y_coords_bytes.len();
-            let mut y = Vec::with_capacity(total_y_coords_len / FIELD_ELEMENT_LEN);
-            for i in 0..total_y_coords_len / FIELD_ELEMENT_LEN {
-                y.push(
-                    Fp::from_repr(FpRepr(
-                        y_coords_bytes[i * FIELD_ELEMENT_LEN..(i + 1) * FIELD_ELEMENT_LEN]
-                            .try_into()
-                            .expect("Failed to parse bytes for y coordinates"),
-                    ))
-                    .unwrap(),
-                )
-            }
-            Ok(Share { x, y })
+        parse_share(s, false)
+    }
+}
+
+impl Share {
+    /// Like `try_from`, but parses a share whose coordinates were
+    /// serialized in big-endian byte order, as some ports of this crate
+    /// do. The header is still little-endian. See
+    /// `Sharks::recover_mixed_endian` for recovering from shares whose
+    /// byte order is unknown.
+    pub fn from_be_bytes(s: &[u8]) -> Result<Share, &'static str> {
+        parse_share(s, true)
+    }
+}
+
+// Parses a serialized share, with the coordinates in big-endian byte
+// order if `big_endian` is set. Non-canonical coordinates, which are
+// usually a sign of the wrong byte order, are rejected, as is any
+// partial coordinate at the end.
+fn parse_share(s: &[u8], big_endian: bool) -> Result<Share, &'static str> {
+    if s.len() < SHARE_HEADER_LEN + FIELD_ELEMENT_LEN {
+        return Err("A Share must be at least 40 bytes long");
+    }
+    let threshold = u32::from_le_bytes(
+        s[..4]
+            .try_into()
+            .expect("Failed to parse bytes for threshold"),
+    );
+    let version = u32::from_le_bytes(
+        s[4..SHARE_HEADER_LEN]
+            .try_into()
+            .expect("Failed to parse bytes for version"),
+    );
+    let (threshold, namespace, s) = if threshold & NAMESPACE_FLAG != 0 {
+        if s.len() < SHARE_HEADER_LEN + NAMESPACE_LEN + FIELD_ELEMENT_LEN {
+            return Err("A namespaced Share must be at least 72 bytes long");
         }
+        let namespace = s[SHARE_HEADER_LEN..SHARE_HEADER_LEN + NAMESPACE_LEN]
+            .try_into()
+            .unwrap();
+        (
+            threshold & !NAMESPACE_FLAG,
+            Some(namespace),
+            &s[SHARE_HEADER_LEN + NAMESPACE_LEN..],
+        )
+    } else {
+        (threshold, None, &s[SHARE_HEADER_LEN..])
+    };
+    if !s.chunks_exact(FIELD_ELEMENT_LEN).remainder().is_empty() {
+        return Err("A Share must hold a whole number of field elements");
     }
+    let mut coords = s.chunks(FIELD_ELEMENT_LEN).map(|bytes| {
+        let mut repr = FpRepr(bytes.try_into().unwrap());
+        if big_endian {
+            repr.0.reverse();
+        }
+        Fp::from_repr(repr).ok_or("Share coordinates must be canonical field elements")
+    });
+    let x = coords.next().unwrap()?;
+    let y = coords.collect::<Result<Vec<Fp>, _>>()?;
+    Ok(Share {
+        x,
+        y,
+        threshold,
+        version,
+        namespace,
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{get_evaluator, interpolate, random_polynomial};
-    use super::{Fp, Share};
-    use crate::ff::Field;
+    use super::random_polynomial;
+    use super::{decode_secret, decode_secret_array, encode_secret};
+    use super::{eval_polynomial, get_evaluator, interpolate};
+    use super::{interpolate_at, interpolate_checked, FpRepr, FIELD_ELEMENT_LEN, SHARE_HEADER_LEN};
+    use super::{
+        interpolate_elements, interpolate_refs, interpolate_with_ctx, lagrange_coefficients, Fp,
+        InterpolationCtx, Share, SECRET_CHUNK_LEN,
+    };
+    use crate::ff::{Field, PrimeField};
+    use crate::RecoverError;
     use alloc::{vec, vec::Vec};
     use core::convert::TryFrom;
     use rand_chacha::rand_core::SeedableRng;
@@ -213,6 +930,24 @@ mod tests {
         assert_eq!(poly[2], fp_one());
     }
 
+    #[test]
+    fn eval_polynomial_works() {
+        // 3x^2 + 2x + 5
+        let poly = [Fp::from(3), Fp::from(2), Fp::from(5)];
+        for &(x, y) in &[(0, 5), (1, 10), (2, 21), (10, 325)] {
+            assert_eq!(eval_polynomial(&poly, Fp::from(x)), Fp::from(y));
+        }
+        assert_eq!(eval_polynomial(&poly, -Fp::one()), Fp::from(6));
+        assert_eq!(eval_polynomial(&[], Fp::from(7)), Fp::zero());
+
+        // The shares of an evaluator are its polynomials' values
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let poly = random_polynomial(fp_three(), 4, &mut rng);
+        let share = get_evaluator(vec![poly.clone()]).nth(4).unwrap();
+        assert_eq!(share.y, vec![eval_polynomial(&poly, Fp::from(5))]);
+        assert_eq!(eval_polynomial(&poly, Fp::zero()), fp_three());
+    }
+
     #[test]
     fn evaluator_works() {
         let iter = get_evaluator(vec![vec![fp_three(), fp_two(), fp_three() + fp_two()]]);
@@ -242,16 +977,380 @@ mod tests {
         );
     }
 
+    #[test]
+    fn evaluator_degree_works() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        for threshold in 1..10 {
+            let polys = vec![
+                random_polynomial(fp_one(), threshold, &mut rng),
+                random_polynomial(fp_two(), threshold, &mut rng),
+            ];
+            let evaluator = get_evaluator(polys);
+            assert_eq!(evaluator.degree(), Some((threshold - 1) as usize));
+        }
+    }
+
+    #[test]
+    fn evaluator_degree_invalid() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let polys = vec![
+            random_polynomial(fp_one(), 3, &mut rng),
+            random_polynomial(fp_two(), 4, &mut rng),
+        ];
+        assert_eq!(get_evaluator(polys).degree(), None);
+        assert_eq!(get_evaluator(vec![]).degree(), None);
+        assert_eq!(get_evaluator(vec![vec![], vec![]]).degree(), None);
+        assert_eq!(get_evaluator(vec![vec![fp_one()]]).degree(), Some(0));
+    }
+
     #[test]
     fn interpolate_works() {
         let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
-        let poly = random_polynomial(fp_one(), 5, &mut rng);
+        let secret = encode_secret(&[7, 8, 9]);
+        let poly = random_polynomial(secret[0], 5, &mut rng);
         let iter = get_evaluator(vec![poly]);
         let shares: Vec<Share> = iter.take(5).collect();
-        let root = interpolate(&shares);
-        let mut chk = vec![0u8; 32];
-        chk[0] = 1u8;
-        assert_eq!(root, chk);
+        assert_eq!(interpolate(&shares), Ok(vec![7, 8, 9]));
+
+        // Field elements which don't encode a secret aren't returned
+        let poly = random_polynomial(Fp::from(u64::MAX), 5, &mut rng);
+        let shares: Vec<Share> = get_evaluator(vec![poly]).take(5).collect();
+        assert_eq!(interpolate(&shares), Err(RecoverError::InvalidEncoding));
+        assert_eq!(
+            interpolate_at(&shares, Fp::zero()),
+            Ok(vec![Fp::from(u64::MAX)])
+        );
+    }
+
+    #[test]
+    fn lagrange_coefficients_match_interpolate() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let polys = (0..3)
+            .map(|_| random_polynomial(Fp::random(&mut rng), 4, &mut rng))
+            .collect();
+        let shares: Vec<Share> = get_evaluator(polys).skip(2).take(4).collect();
+        let xs: Vec<Fp> = shares.iter().map(|s| s.x).collect();
+
+        for &at in &[Fp::zero(), Fp::from(9)] {
+            let coefficients = lagrange_coefficients(&xs, at).unwrap();
+            let combined: Vec<Fp> = (0..3)
+                .map(|chunk| {
+                    shares
+                        .iter()
+                        .zip(&coefficients)
+                        .fold(Fp::zero(), |acc, (s, l)| acc + *l * s.y[chunk])
+                })
+                .collect();
+            assert_eq!(combined, interpolate_at(&shares, at).unwrap());
+        }
+        let secret = lagrange_coefficients(&xs, Fp::zero()).unwrap();
+        assert_eq!(secret.iter().fold(Fp::zero(), |acc, l| acc + l), Fp::one());
+
+        assert_eq!(
+            lagrange_coefficients(&[Fp::one(), Fp::from(2), Fp::one()], Fp::zero()),
+            Err(RecoverError::DuplicateShareX)
+        );
+        assert_eq!(lagrange_coefficients(&[], Fp::zero()), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn interpolate_with_ctx_reuse() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let mut ctx = InterpolationCtx::new();
+        for (k, chunks) in [(5, 3), (2, 1), (8, 4)] {
+            let secrets: Vec<Fp> = (0..chunks).map(|_| Fp::random(&mut rng)).collect();
+            let polys = secrets
+                .iter()
+                .map(|s| random_polynomial(*s, k, &mut rng))
+                .collect();
+            let shares: Vec<Share> = get_evaluator(polys).take(k as usize).collect();
+            assert_eq!(interpolate_with_ctx(&mut ctx, &shares), Ok(&secrets[..]));
+        }
+    }
+
+    #[test]
+    fn from_bytes_mod_order_works() {
+        // The modulus plus five, which `from_repr` rejects
+        let mut p_plus_5 = [0u8; FIELD_ELEMENT_LEN];
+        p_plus_5.copy_from_slice((-Fp::one()).to_repr().as_ref());
+        p_plus_5[0] += 6;
+        assert!(Fp::from_repr(FpRepr(p_plus_5)).is_none());
+        assert_eq!(Fp::from_bytes_mod_order(&p_plus_5), Fp::from(5));
+        assert_eq!(Fp::from_bytes_mod_order(&[42, 1]), Fp::from(298));
+        assert_eq!(Fp::from_bytes_mod_order(&[]), Fp::zero());
+
+        // Inputs differing by a multiple of the modulus in the high
+        // half reduce to the same element
+        let mut wide = [0u8; 64];
+        wide[..32].copy_from_slice(&p_plus_5);
+        let mut shifted = wide;
+        shifted[32..].copy_from_slice(&p_plus_5);
+        shifted[32] -= 5;
+        assert_eq!(Fp::from_bytes_wide(&wide), Fp::from(5));
+        assert_eq!(Fp::from_bytes_wide(&shifted), Fp::from(5));
+    }
+
+    #[test]
+    fn repr_byte_order() {
+        // The field order minus one, as a big-endian hex string
+        let be_hex = "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000000";
+        let be: Vec<u8> = (0..be_hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&be_hex[i..i + 2], 16).unwrap())
+            .collect();
+        let minus_one = -Fp::one();
+        let repr = minus_one.to_repr();
+        assert_eq!(repr.to_be_bytes().to_vec(), be);
+        let mut le = be.clone();
+        le.reverse();
+        assert_eq!(repr.as_ref(), le.as_slice());
+
+        let mut be_bytes = [0u8; FIELD_ELEMENT_LEN];
+        be_bytes.copy_from_slice(&be);
+        assert_eq!(
+            Fp::from_repr(FpRepr::from_be_bytes(be_bytes)).unwrap(),
+            minus_one
+        );
+        // The field order itself is not canonical
+        be_bytes[FIELD_ELEMENT_LEN - 1] = 1;
+        assert!(Fp::from_repr(FpRepr::from_be_bytes(be_bytes)).is_none());
+    }
+
+    #[test]
+    fn gen_for_id_works() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Fp::random(&mut rng);
+        let evaluator = get_evaluator(vec![random_polynomial(secret, 3, &mut rng)]);
+        let ids: [&[u8]; 4] = [b"alice", b"bob", b"carol", b"dave"];
+        let shares = evaluator.gen_for_ids(&ids).unwrap();
+
+        // Each party's share is re-derivable from its id alone
+        for (id, share) in ids.iter().zip(&shares) {
+            assert_eq!(&evaluator.gen_for_id(id), share);
+        }
+        assert_eq!(interpolate_elements(&shares[1..]), Ok(vec![secret]));
+        assert_eq!(
+            evaluator.gen_for_ids(&[b"alice", b"bob", b"alice"]),
+            Err("Party ids map to the same x coordinate")
+        );
+    }
+
+    #[test]
+    fn gen_range_works() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Fp::random(&mut rng);
+        let evaluator = get_evaluator(vec![random_polynomial(secret, 5, &mut rng)]);
+
+        // Two dealers sharing the polynomials, one for x in 1..=500 and
+        // one for x in 501..=1000
+        let first: Vec<Share> = evaluator.gen_range(Fp::one(), 500).unwrap().collect();
+        let second: Vec<Share> = evaluator.gen_range(Fp::from(501), 500).unwrap().collect();
+        assert_eq!(first[0].x, Fp::one());
+        assert_eq!(second[499].x, Fp::from(1000));
+        let sequential: Vec<Share> = get_evaluator(evaluator.polys.clone()).take(1000).collect();
+        assert_eq!([first.clone(), second.clone()].concat(), sequential);
+
+        let mixed = [&first[10..12], &second[100..103]].concat();
+        assert_eq!(interpolate_elements(&mixed), Ok(vec![secret]));
+
+        assert!(evaluator.gen_range(Fp::zero(), 1).is_err());
+        assert!(evaluator.gen_range(-Fp::from(3), 4).is_err());
+        assert_eq!(evaluator.gen_range(-Fp::from(3), 3).unwrap().count(), 3);
+        assert_eq!(evaluator.gen_range(Fp::zero(), 0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn evaluator_never_reuses_x() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let mut evaluator = get_evaluator(vec![random_polynomial(Fp::one(), 2, &mut rng)]);
+        assert_eq!(evaluator.remaining_shares(), None);
+        let mut seen = hashbrown::HashSet::new();
+        for share in evaluator.by_ref().take(100_000) {
+            assert!(!share.x.is_zero());
+            assert!(seen.insert(share.x.to_repr().as_ref().to_vec()));
+        }
+        assert_eq!(seen.len(), 100_000);
+
+        // Near the end of the field, the iterator stops before reaching
+        // the secret at zero
+        evaluator.x = -Fp::from(4);
+        assert_eq!(evaluator.remaining_shares(), Some(3));
+        let last: Vec<Share> = evaluator.by_ref().collect();
+        assert_eq!(last.len(), 3);
+        assert_eq!(last[2].x, -Fp::one());
+        assert_eq!(evaluator.remaining_shares(), Some(0));
+        assert!(evaluator.next().is_none());
+        evaluator.x = -Fp::from(u64::MAX) - Fp::from(u64::MAX);
+        assert_eq!(evaluator.remaining_shares(), Some(2 * u64::MAX as u128 - 1));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn gen_many_parallel_matches_serial() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Fp::random(&mut rng);
+        let evaluator = get_evaluator(vec![random_polynomial(secret, 10, &mut rng); 3]);
+        let xs: Vec<Fp> = (0..2000).map(|_| Fp::random(&mut rng)).collect();
+        let serial: Vec<Share> = xs.iter().map(|&x| evaluator.gen_at(x).unwrap()).collect();
+        assert_eq!(evaluator.gen_many_parallel(&xs), Ok(serial));
+        assert_eq!(evaluator.gen_many_parallel(&[]), Ok(vec![]));
+
+        let mut bad = xs[..10].to_vec();
+        bad.push(Fp::zero());
+        assert!(evaluator.gen_many_parallel(&bad).is_err());
+        bad[10] = xs[3];
+        assert!(evaluator.gen_many_parallel(&bad).is_err());
+    }
+
+    #[test]
+    fn gen_shuffled_works() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Fp::random(&mut rng);
+        let evaluator = get_evaluator(vec![random_polynomial(secret, 3, &mut rng)]);
+        let shares = evaluator.gen_shuffled(10, &mut rng);
+
+        // The shares are those at 1..=10, but not in order
+        let sequential: Vec<Share> = get_evaluator(evaluator.polys.clone()).take(10).collect();
+        assert_ne!(shares, sequential);
+        assert!(sequential.iter().all(|s| shares.contains(s)));
+        assert_eq!(interpolate_elements(&shares[..3]), Ok(vec![secret]));
+        assert_eq!(interpolate_elements(&shares[7..]), Ok(vec![secret]));
+    }
+
+    #[test]
+    fn interpolate_checked_rejects_undersized() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let polys = vec![random_polynomial(encode_secret(b"abc")[0], 3, &mut rng)];
+        let shares: Vec<Share> = get_evaluator(polys).take(4).collect();
+        assert_eq!(interpolate(&shares[..3]), Ok(b"abc".to_vec()));
+
+        // Too few shares silently give a different secret
+        assert_ne!(interpolate(&shares[..2]), interpolate(&shares[..3]));
+        assert_eq!(
+            interpolate_checked(&shares[..2]),
+            Err(RecoverError::NotEnoughShares)
+        );
+        let repeated = [&shares[..2], &shares[..1]].concat();
+        assert_eq!(
+            interpolate_checked(&repeated),
+            Err(RecoverError::NotEnoughShares)
+        );
+        assert_eq!(interpolate_checked(&[]), Err(RecoverError::NotEnoughShares));
+        assert_eq!(interpolate_checked(&shares[1..]), interpolate(&shares[..3]));
+    }
+
+    #[test]
+    fn interpolate_rejects_duplicate_x() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let polys = vec![random_polynomial(Fp::random(&mut rng), 3, &mut rng)];
+        let shares: Vec<Share> = get_evaluator(polys).take(3).collect();
+
+        // A repeated share, or another share at the same x, would
+        // otherwise give a wrong secret
+        for y in [shares[0].y.clone(), vec![Fp::one()]] {
+            let mut duplicate = shares.clone();
+            duplicate[2].x = shares[0].x;
+            duplicate[2].y = y;
+            assert_eq!(interpolate(&duplicate), Err(RecoverError::DuplicateShareX));
+            assert_eq!(
+                interpolate_at(&duplicate, Fp::from(7)),
+                Err(RecoverError::DuplicateShareX)
+            );
+        }
+        assert_eq!(interpolate(&[]), Err(RecoverError::NotEnoughShares));
+    }
+
+    #[test]
+    fn interpolate_refs_matches_interpolate() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        let polys = encode_secret(&[0xab; 80])
+            .into_iter()
+            .map(|s| random_polynomial(s, 4, &mut rng))
+            .collect();
+        let shares: Vec<Share> = get_evaluator(polys).take(4).collect();
+        let refs: Vec<&Share> = shares.iter().collect();
+        assert_eq!(interpolate_refs(&refs), Ok(vec![0xab; 80]));
+        assert_eq!(interpolate_refs(&refs), interpolate(&shares));
+    }
+
+    #[test]
+    fn max_secret_bytes_works() {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+        for chunks in 1..5 {
+            let polys = encode_secret(&vec![0xff; chunks * SECRET_CHUNK_LEN - 4])
+                .into_iter()
+                .map(|s| random_polynomial(s, 2, &mut rng))
+                .collect();
+            let evaluator = get_evaluator(polys);
+            let max = evaluator.max_secret_bytes();
+
+            // A secret at the maximum length fits in the same chunks and
+            // recovers, while one byte more needs another chunk
+            let secret = vec![0xff; max];
+            let elements = encode_secret(&secret);
+            assert_eq!(elements.len(), chunks);
+            let polys = elements
+                .into_iter()
+                .map(|s| random_polynomial(s, 2, &mut rng))
+                .collect();
+            let shares: Vec<Share> = get_evaluator(polys).take(2).collect();
+            assert_eq!(
+                decode_secret(&interpolate_elements(&shares).unwrap()),
+                Some(secret)
+            );
+            assert_eq!(encode_secret(&vec![0xff; max + 1]).len(), chunks + 1);
+        }
+    }
+
+    #[test]
+    fn secret_encoding_roundtrip() {
+        for len in 0..100 {
+            let secret: Vec<u8> = (0..len).map(|i| (i as u8).wrapping_mul(151)).collect();
+            let elements = encode_secret(&secret);
+            assert_eq!(elements.len(), (len + 3) / SECRET_CHUNK_LEN + 1);
+            assert_eq!(decode_secret(&elements), Some(secret));
+        }
+    }
+
+    #[test]
+    fn secret_encoding_invalid() {
+        assert_eq!(decode_secret(&[]), None);
+        // length prefix larger than the encoded data
+        assert_eq!(decode_secret(&[Fp::from(100)]), None);
+        // non-zero most significant byte
+        assert_eq!(decode_secret(&[-Fp::one()]), None);
+        // non-zero padding
+        let mut elements = encode_secret(&[1, 2, 3]);
+        elements[0] += Fp::from(1 << 60);
+        assert_eq!(decode_secret(&elements), None);
+    }
+
+    #[test]
+    fn secret_encoding_array() {
+        let secret: Vec<u8> = (0..40).collect();
+        let elements = encode_secret(&secret);
+        assert_eq!(
+            decode_secret_array::<40>(&elements).unwrap()[..],
+            secret[..]
+        );
+        assert_eq!(
+            decode_secret_array::<39>(&elements),
+            Err(RecoverError::SecretLengthMismatch {
+                expected: 39,
+                found: 40
+            })
+        );
+        assert_eq!(decode_secret_array::<0>(&encode_secret(&[])), Ok([]));
+
+        // The same encodings as `decode_secret` rejects
+        let mut padded = encode_secret(&[1, 2, 3]);
+        padded[0] += Fp::from(1 << 60);
+        for elements in [vec![], vec![Fp::from(100)], vec![-Fp::one()], padded] {
+            assert_eq!(
+                decode_secret_array::<3>(&elements),
+                Err(RecoverError::InvalidEncoding)
+            );
+        }
     }
 
     #[test]
@@ -259,6 +1358,9 @@ mod tests {
         let share = Share {
             x: fp_one(),
             y: vec![fp_two(), fp_three()],
+            threshold: 2,
+            version: 7,
+            namespace: None,
         };
         let bytes = Vec::from(&share);
         let chk_bytes = get_test_bytes();
@@ -268,13 +1370,46 @@ mod tests {
     #[test]
     fn share_from_u8_slice_works() {
         let share = Share::try_from(&get_test_bytes()[..]).unwrap();
+        assert_eq!(share.threshold, 2);
+        assert_eq!(share.version, 7);
         assert_eq!(share.x, fp_one());
         assert_eq!(share.y, vec![fp_two(), fp_three()]);
     }
 
+    #[test]
+    fn share_from_u8_slice_rejects_bad_coordinates() {
+        let share = Share {
+            x: Fp::from(0x80),
+            y: vec![Fp::from(0x90), Fp::from(0xa0)],
+            threshold: 2,
+            version: 7,
+            namespace: None,
+        };
+        let bytes = Vec::from(&share);
+        let mut be = bytes.clone();
+        for element in be[SHARE_HEADER_LEN..].chunks_mut(FIELD_ELEMENT_LEN) {
+            element.reverse();
+        }
+        assert_eq!(Share::from_be_bytes(&be), Ok(share.clone()));
+
+        // Read with the wrong byte order, the coordinates are larger than
+        // the field order
+        assert_eq!(
+            Share::try_from(&be[..]),
+            Err("Share coordinates must be canonical field elements")
+        );
+        assert!(Share::from_be_bytes(&bytes).is_err());
+        assert_eq!(
+            Share::try_from(&bytes[..bytes.len() - 1]),
+            Err("A Share must hold a whole number of field elements")
+        );
+    }
+
     fn get_test_bytes() -> Vec<u8> {
         let suffix = vec![0u8; 31];
-        let mut bytes = vec![1u8; 1];
+        let mut bytes = vec![2u8, 0, 0, 0]; // threshold
+        bytes.extend(vec![7u8, 0, 0, 0]); // version
+        bytes.extend(vec![1u8; 1]);
         bytes.extend(suffix.clone()); // x coord
         bytes.extend(vec![2u8; 1]);
         bytes.extend(suffix.clone()); // y coord #1
@@ -0,0 +1,188 @@
+//! Sharing of secrets which are a single field element.
+//!
+//! Splitting a single key is the most common use of the crate, so it has
+//! its own path: the dealer evaluates one polynomial directly rather than
+//! a `Vec` of them, and recovery accumulates the Lagrange interpolation
+//! as a single fraction, with one field inversion and no intermediate
+//! buffers. Shares are the same as those of the generic path, and can be
+//! recovered by either.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::ff::Field;
+use crate::share_ff::{eval_polynomial, invert, mul, random_polynomial, Fp, Share};
+use crate::{RecoverError, Sharks};
+
+/// Generates the shares of a single field element secret, created by
+/// `Sharks::dealer_single_rng`. The shares are identical to those of
+/// `get_evaluator` with the same polynomial.
+#[derive(Debug)]
+pub struct SingleEvaluator {
+    poly: Vec<Fp>,
+    x: Fp,
+    threshold: u32,
+}
+
+impl Iterator for SingleEvaluator {
+    type Item = Share;
+
+    fn next(&mut self) -> Option<Share> {
+        self.x += Fp::one();
+        let x = self.x;
+        let y = eval_polynomial(&self.poly, x);
+        Some(Share {
+            x,
+            y: vec![y],
+            threshold: self.threshold,
+            version: 0,
+            namespace: None,
+        })
+    }
+}
+
+impl Sharks {
+    /// Like `dealer_rng`, but shares a single field element. Consumes
+    /// the same randomness as `get_evaluator(vec![random_polynomial(secret,
+    /// threshold, rng)])`, and so deals identical shares.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Fp, Sharks, Share };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// # let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let shares: Vec<Share> = sharks.dealer_single_rng(Fp::from(7), &mut rng).take(3).collect();
+    /// assert_eq!(sharks.recover_single(&shares).unwrap(), Fp::from(7));
+    pub fn dealer_single_rng<R: rand::Rng>(&self, secret: Fp, rng: &mut R) -> SingleEvaluator {
+        SingleEvaluator {
+            poly: random_polynomial(secret, self.0, rng),
+            x: Fp::zero(),
+            threshold: self.0,
+        }
+    }
+
+    /// Recovers a single field element secret, as dealt by
+    /// `dealer_single_rng`. Returns the same result as the first element
+    /// of `recover_fp`, and the same errors, along with
+    /// `InconsistentShareLength` if the shares have more than one chunk
+    /// and `InvalidShareIndex` if one of them is at zero.
+    pub fn recover_single<'a, T>(&self, shares: T) -> Result<Fp, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let threshold = self.0 as usize;
+        let mut values: Vec<&Share> = Vec::with_capacity(threshold);
+        for share in shares {
+            if share.threshold != self.0 {
+                return Err(RecoverError::ThresholdMismatch {
+                    expected: self.0,
+                    found: share.threshold,
+                });
+            } else if share.y.len() != 1 {
+                return Err(RecoverError::InconsistentShareLength);
+            } else if let Some(first) = values.first() {
+                if share.version != first.version {
+                    return Err(RecoverError::VersionMismatch {
+                        expected: first.version,
+                        found: share.version,
+                    });
+                } else if share.namespace != first.namespace {
+                    return Err(RecoverError::NamespaceMismatch);
+                }
+            }
+            if values.len() < threshold && values.iter().all(|s| s.x != share.x) {
+                values.push(share);
+            }
+        }
+        if values.is_empty() || values.len() < threshold {
+            return Err(RecoverError::NotEnoughShares);
+        }
+
+        // At zero, the numerator of each basis polynomial is the product
+        // of all of the x coordinates but its own, so the secret is
+        // `prod_j x_j * sum_i y_i / (x_i * prod_{j != i} (x_j - x_i))`.
+        // The sum is accumulated as the fraction `sum / den`, leaving only
+        // one multiplication per pair of shares and a single inversion.
+        let prod = values.iter().fold(Fp::one(), |acc, s| mul(acc, s.x));
+        let (sum, den) =
+            values
+                .iter()
+                .enumerate()
+                .fold((Fp::zero(), Fp::one()), |(sum, den), (i, s_i)| {
+                    let den_i = values
+                        .iter()
+                        .enumerate()
+                        .filter(|&(j, _)| j != i)
+                        .fold(s_i.x, |acc, (_, s_j)| mul(acc, s_j.x - s_i.x));
+                    (mul(sum, den_i) + mul(s_i.y[0], den), mul(den, den_i))
+                });
+        // The shares have distinct x coordinates, so the denominator
+        // is only zero if one of them is at zero.
+        let den = invert(&den).map_err(|_| RecoverError::InvalidShareIndex)?;
+        Ok(mul(mul(prod, sum), den))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ff::Field;
+    use crate::{get_evaluator, random_polynomial, Fp, RecoverError, Share, Sharks};
+    use alloc::{vec, vec::Vec};
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_single_matches_generic() {
+        for &threshold in &[1, 2, 7, 30] {
+            let sharks = Sharks(threshold);
+            let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+            let secret = Fp::random(&mut rng);
+
+            let mut single_rng = rng.clone();
+            let single: Vec<Share> = sharks
+                .dealer_single_rng(secret, &mut single_rng)
+                .take(threshold as usize + 2)
+                .collect();
+            let generic: Vec<Share> =
+                get_evaluator(vec![random_polynomial(secret, threshold, &mut rng)])
+                    .take(threshold as usize + 2)
+                    .collect();
+            assert_eq!(single, generic);
+
+            assert_eq!(sharks.recover_single(&single), Ok(secret));
+            assert_eq!(sharks.recover_single(&single[2..]), Ok(secret));
+            assert_eq!(sharks.recover_fp(&single).unwrap(), vec![secret]);
+        }
+    }
+
+    #[test]
+    fn test_recover_single_errors() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let shares: Vec<Share> = sharks
+            .dealer_single_rng(Fp::one(), &mut rng)
+            .take(3)
+            .collect();
+
+        let repeated = [&shares[..2], &shares[..2]].concat();
+        assert_eq!(
+            sharks.recover_single(&repeated),
+            Err(RecoverError::NotEnoughShares)
+        );
+        let chunked: Vec<Share> = sharks
+            .dealer_rng(&[0; 40][..].into(), &mut rng)
+            .take(3)
+            .collect();
+        assert_eq!(
+            sharks.recover_single(&chunked),
+            Err(RecoverError::InconsistentShareLength)
+        );
+        let mut at_zero = shares.clone();
+        at_zero[1].x = Fp::zero();
+        assert_eq!(
+            sharks.recover_single(&at_zero),
+            Err(RecoverError::InvalidShareIndex)
+        );
+    }
+}
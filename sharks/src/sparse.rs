@@ -0,0 +1,250 @@
+//! Smaller shares for secrets with many all-zero chunks, such as
+//! fixed-layout structures that are mostly empty.
+//!
+//! `sparse_dealer_rng` only shares the chunks of the encoded secret
+//! which aren't zero, and records which chunks are present in a bitmap
+//! carried by every share. `recover_sparse` puts zeros back in place of
+//! the absent chunks. The first chunk, which holds the secret length,
+//! is always shared.
+//!
+//! The bitmap is not secret: anyone who sees a single share learns which
+//! `SECRET_CHUNK_LEN` byte chunks of the secret are all zero, as well as
+//! roughly how long it is. Only use this mode when the layout of the
+//! secret is already public.
+
+use alloc::vec::Vec;
+use core::convert::{TryFrom, TryInto};
+
+use crate::ff::Field;
+use crate::share_ff::{decode_secret, encode_secret, Fp, Share};
+use crate::{RecoverError, Secret, Sharks};
+
+/// A share of a sparse secret, dealt by `Sharks::sparse_dealer_rng`.
+///
+/// The serialized form is the number of chunks of the secret as a
+/// little-endian `u32`, followed by the bitmap of present chunks, one
+/// bit per chunk starting from the least significant bit of the first
+/// byte, and then the serialized `share`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseShare {
+    chunks: u32,
+    present: Vec<u8>,
+    /// The share of the present chunks, in order.
+    pub share: Share,
+}
+
+impl SparseShare {
+    /// The number of chunks of the secret, present or not.
+    pub fn chunks(&self) -> usize {
+        self.chunks as usize
+    }
+
+    /// Whether chunk `i` of the secret is shared, rather than known to
+    /// be zero.
+    pub fn is_present(&self, i: usize) -> bool {
+        i < self.chunks() && self.present[i / 8] >> (i % 8) & 1 == 1
+    }
+}
+
+/// Obtains a byte vector from a `SparseShare` instance
+impl From<&SparseShare> for Vec<u8> {
+    fn from(s: &SparseShare) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(s.chunks.to_le_bytes());
+        bytes.extend(&s.present);
+        bytes.extend(Vec::from(&s.share));
+        bytes
+    }
+}
+
+/// Obtains a `SparseShare` instance from a byte slice
+impl TryFrom<&[u8]> for SparseShare {
+    type Error = &'static str;
+
+    fn try_from(s: &[u8]) -> Result<SparseShare, Self::Error> {
+        if s.len() < 4 {
+            return Err("SparseShare is too short");
+        }
+        let chunks = u32::from_le_bytes(s[..4].try_into().unwrap());
+        let bitmap_len = bitmap_len(chunks as usize);
+        if s.len() < 4 + bitmap_len {
+            return Err("SparseShare is too short");
+        }
+        let present = s[4..4 + bitmap_len].to_vec();
+        let share = Share::try_from(&s[4 + bitmap_len..])?;
+        let sparse = SparseShare {
+            chunks,
+            present,
+            share,
+        };
+        let count = (0..sparse.chunks())
+            .filter(|&i| sparse.is_present(i))
+            .count();
+        let used_bits = sparse.present.iter().map(|b| b.count_ones()).sum::<u32>();
+        if count != sparse.share.y.len() || count as u32 != used_bits || !sparse.is_present(0) {
+            return Err("SparseShare bitmap does not match its share");
+        }
+        Ok(sparse)
+    }
+}
+
+impl Sharks {
+    /// Like `dealer_rng`, but only shares the chunks of the encoded
+    /// secret which aren't zero. See the `sparse` module for what this
+    /// reveals about the secret.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks, SparseShare };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let mut secret = vec![0u8; 1000];
+    /// secret[500] = 1;
+    /// let shares: Vec<SparseShare> =
+    ///     sharks.sparse_dealer_rng(&Secret::from_bytes(&secret), &mut rng).take(3).collect();
+    /// assert_eq!(shares[0].share.y.len(), 2);
+    /// assert_eq!(sharks.recover_sparse(&shares).unwrap().as_bytes(), secret);
+    pub fn sparse_dealer_rng<R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        rng: &mut R,
+    ) -> impl Iterator<Item = SparseShare> {
+        let elements = encode_secret(secret.as_bytes());
+        let mut present = alloc::vec![0u8; bitmap_len(elements.len())];
+        let mut shared = Vec::new();
+        for (i, element) in elements.iter().enumerate() {
+            if i == 0 || *element != Fp::zero() {
+                present[i / 8] |= 1 << (i % 8);
+                shared.push(*element);
+            }
+        }
+        let chunks = elements.len() as u32;
+        self.deal_elements(shared, rng)
+            .map(move |share| SparseShare {
+                chunks,
+                present: present.clone(),
+                share,
+            })
+    }
+
+    /// Recovers a secret from shares dealt by `sparse_dealer_rng`,
+    /// filling in zeros for the chunks which weren't shared. Returns an
+    /// `InconsistentShareLength` error if the shares have different
+    /// bitmaps, as well as the errors of `recover`.
+    pub fn recover_sparse(&self, shares: &[SparseShare]) -> Result<Secret, RecoverError> {
+        let first = shares.first().ok_or(RecoverError::NotEnoughShares)?;
+        if shares
+            .iter()
+            .any(|s| s.chunks != first.chunks || s.present != first.present)
+        {
+            return Err(RecoverError::InconsistentShareLength);
+        }
+        let mut shared = self
+            .recover_fp(shares.iter().map(|s| &s.share))?
+            .into_iter();
+        let elements: Vec<Fp> = (0..first.chunks())
+            .map(|i| {
+                if first.is_present(i) {
+                    shared.next().ok_or(RecoverError::InconsistentShareLength)
+                } else {
+                    Ok(Fp::zero())
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        if shared.next().is_some() {
+            return Err(RecoverError::InconsistentShareLength);
+        }
+        decode_secret(&elements)
+            .map(Secret::from)
+            .ok_or(RecoverError::InvalidEncoding)
+    }
+}
+
+// The number of bytes in the bitmap of a secret with `chunks` chunks.
+fn bitmap_len(chunks: usize) -> usize {
+    (chunks + 7) >> 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseShare;
+    use crate::{RecoverError, Secret, Share, Sharks};
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_sparse_shares_are_smaller() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        // 20 chunks, of which the first and two others are non-zero
+        let mut secret = alloc::vec![0u8; 20 * 31 - 4];
+        secret[100] = 0xab;
+        secret[500] = 0xcd;
+        let secret = Secret::from_bytes(&secret);
+
+        let dense: Vec<Share> = sharks.dealer_rng(&secret, &mut rng).take(3).collect();
+        let sparse: Vec<SparseShare> = sharks
+            .sparse_dealer_rng(&secret, &mut rng)
+            .take(3)
+            .collect();
+        assert_eq!(sparse[0].chunks(), 20);
+        assert_eq!(sparse[0].share.y.len(), 3);
+        assert!(sparse[0].is_present(0) && !sparse[0].is_present(1));
+        let dense_len = Vec::from(&dense[0]).len();
+        let sparse_len = Vec::from(&sparse[0]).len();
+        assert!(sparse_len < dense_len / 4);
+
+        assert_eq!(
+            sharks.recover_sparse(&sparse).unwrap().as_bytes(),
+            secret.as_bytes()
+        );
+        let parsed: Vec<SparseShare> = sparse
+            .iter()
+            .map(|s| SparseShare::try_from(Vec::from(s).as_slice()).unwrap())
+            .collect();
+        assert_eq!(parsed, sparse);
+
+        // Secrets without zero chunks, and the empty secret, still work
+        for secret in &[&[7u8; 100][..], &[]] {
+            let shares: Vec<SparseShare> = sharks
+                .sparse_dealer_rng(&Secret::from_bytes(secret), &mut rng)
+                .take(3)
+                .collect();
+            assert_eq!(sharks.recover_sparse(&shares).unwrap().as_bytes(), *secret);
+        }
+    }
+
+    #[test]
+    fn test_sparse_errors() {
+        let sharks = Sharks(2);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let mut secret = alloc::vec![0u8; 100];
+        secret[50] = 1;
+        let shares: Vec<SparseShare> = sharks
+            .sparse_dealer_rng(&Secret::from_bytes(&secret), &mut rng)
+            .take(2)
+            .collect();
+        secret[50] = 0;
+        secret[80] = 1;
+        let other: Vec<SparseShare> = sharks
+            .sparse_dealer_rng(&Secret::from_bytes(&secret), &mut rng)
+            .take(2)
+            .collect();
+        assert_eq!(
+            sharks.recover_sparse(&[shares[0].clone(), other[1].clone()]),
+            Err(RecoverError::InconsistentShareLength)
+        );
+        assert_eq!(
+            sharks.recover_sparse(&[]),
+            Err(RecoverError::NotEnoughShares)
+        );
+
+        let mut bytes = Vec::from(&shares[0]);
+        assert!(SparseShare::try_from(&bytes[..5]).is_err());
+        // A bitmap claiming more chunks than the share holds
+        bytes[4] |= 0x80;
+        assert!(SparseShare::try_from(bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! Feldman verifiable secret sharing.
+//!
+//! The field used by `sharks` is the scalar field of BLS12-381, so the
+//! dealer can commit to each polynomial coefficient `a` by publishing
+//! `a * G` for the G1 generator `G`. Any holder can then check that its
+//! share lies on the committed polynomials without learning anything
+//! more about the secret.
+//!
+//! Because the fields are the same, an `Fp` converts exactly to and from
+//! a `bls12_381::Scalar`, which is what threshold BLS signatures need.
+//! A signing key `s` is dealt with `dealer_single_rng`, and each holder
+//! signs a message with its share's `y` as its key, giving the partial
+//! signature `y_i * H(m)`. Any threshold of partial signatures combine
+//! into the signature `s * H(m)` by weighting each one with the
+//! `lagrange_coefficients` of the signers' `x` coordinates at zero,
+//! converted to scalars, so the key is never reconstructed.
+//! `recover_single` and `recover_fp` give the key itself as an `Fp`,
+//! whose `Scalar` is the public key's discrete logarithm.
+
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use bls12_381::{G1Affine, G1Projective, Scalar};
+use group::Curve;
+
+use crate::ff::PrimeField;
+use crate::share_ff::{decode_secret, Evaluator, Fp, FpRepr, Share};
+use crate::{RecoverError, Secret, Sharks};
+
+/// Length of a serialized commitment to a single coefficient, which is
+/// a compressed G1 point.
+pub const COMMITMENT_LEN: usize = 48;
+
+/// Public commitments to the polynomials of a verifiable dealing.
+///
+/// There is one G1 point per polynomial coefficient, for each chunk of
+/// the secret. Commitments are stored in the same order as the
+/// coefficients returned by `random_polynomial`, so the last commitment
+/// of each chunk is the commitment to the secret chunk itself.
+///
+/// The serialized form starts with the `threshold` (as a little-endian
+/// `u32`), followed by the compressed points of each chunk in turn.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Commitments {
+    pub(crate) threshold: u32,
+    pub(crate) points: Vec<Vec<G1Affine>>,
+}
+
+impl Commitments {
+    // Commits to each coefficient of the polynomials of `evaluator`.
+    pub(crate) fn from_evaluator(evaluator: &Evaluator) -> Self {
+        let points = evaluator
+            .polys
+            .iter()
+            .map(|poly| {
+                poly.iter()
+                    .map(|c| (G1Affine::generator() * to_scalar(c)).to_affine())
+                    .collect()
+            })
+            .collect();
+        Commitments {
+            threshold: evaluator.threshold,
+            points,
+        }
+    }
+
+    /// The threshold of the committed dealing.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The number of secret chunks committed to.
+    pub fn chunks(&self) -> usize {
+        self.points.len()
+    }
+
+    // Evaluates the committed polynomial of `chunk` at `x` "in the
+    // exponent", giving the commitment to the expected share.
+    fn evaluate(&self, chunk: usize, x: &Scalar) -> G1Projective {
+        self.points[chunk]
+            .iter()
+            .fold(G1Projective::identity(), |acc, c| acc * x + c)
+    }
+}
+
+/// Obtains a byte vector from a `Commitments` instance
+impl From<&Commitments> for Vec<u8> {
+    fn from(c: &Commitments) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(4 + c.points.len() * c.threshold as usize * COMMITMENT_LEN);
+        bytes.extend(c.threshold.to_le_bytes());
+        for point in c.points.iter().flatten() {
+            bytes.extend(point.to_compressed());
+        }
+        bytes
+    }
+}
+
+/// Obtains a `Commitments` instance from a byte slice
+impl core::convert::TryFrom<&[u8]> for Commitments {
+    type Error = &'static str;
+
+    fn try_from(s: &[u8]) -> Result<Commitments, Self::Error> {
+        if s.len() < 4 {
+            return Err("Commitments must be at least 4 bytes long");
+        }
+        let threshold = u32::from_le_bytes(s[..4].try_into().unwrap());
+        let chunk_len = threshold as usize * COMMITMENT_LEN;
+        let s = &s[4..];
+        if chunk_len == 0 || s.is_empty() || !s.chunks_exact(chunk_len).remainder().is_empty() {
+            return Err("Commitments length does not match threshold");
+        }
+        let points = s
+            .chunks(chunk_len)
+            .map(|chunk| {
+                chunk
+                    .chunks(COMMITMENT_LEN)
+                    .map(|p| {
+                        Option::from(G1Affine::from_compressed(p.try_into().unwrap()))
+                            .ok_or("Invalid commitment point")
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Commitments { threshold, points })
+    }
+}
+
+impl Share {
+    /// Checks that this share lies on the polynomials committed to by
+    /// `commitments`, i.e. that the dealer handed out a consistent share.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Secret, Sharks };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let (mut shares, commitments) =
+    ///     sharks.verifiable_dealer_rng(&Secret::from_bytes(&[1, 2]), 5, &mut rng);
+    /// assert!(shares[0].verify(&commitments));
+    /// // A share which is off the committed polynomial is rejected
+    /// shares[0].y[0] = shares[1].y[0];
+    /// assert!(!shares[0].verify(&commitments));
+    pub fn verify(&self, commitments: &Commitments) -> bool {
+        if self.threshold != commitments.threshold || self.y.len() != commitments.chunks() {
+            return false;
+        }
+        let x = to_scalar(&self.x);
+        self.y.iter().enumerate().all(|(chunk, y)| {
+            G1Affine::generator() * to_scalar(y) == commitments.evaluate(chunk, &x)
+        })
+    }
+}
+
+impl Evaluator {
+    /// Returns public `Commitments` to the polynomials of this
+    /// evaluator, as `verifiable_dealer_rng` does, for shares which are
+    /// generated some other way, such as at random points with
+    /// `Randomized::with_rng`.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ Randomized, Secret, Sharks };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let evaluator = sharks.dealer_rng(&Secret::from_bytes(&[1, 2]), &mut rng);
+    /// let commitments = evaluator.commitments();
+    /// assert!(evaluator.with_rng(&mut rng).verify(&commitments));
+    pub fn commitments(&self) -> Commitments {
+        Commitments::from_evaluator(self)
+    }
+}
+
+impl Sharks {
+    /// Like `dealer_rng`, but returns `n` shares together with public
+    /// `Commitments` to the dealt polynomials, so that each holder can
+    /// check its share with `Share::verify`. The commitments can be
+    /// serialized and published independently of the shares.
+    pub fn verifiable_dealer_rng<R: rand::Rng>(
+        &self,
+        secret: &Secret,
+        n: usize,
+        rng: &mut R,
+    ) -> (Vec<Share>, Commitments) {
+        let evaluator = self.dealer_rng(secret, rng);
+        let commitments = evaluator.commitments();
+        (evaluator.take(n).collect(), commitments)
+    }
+
+    /// Like `recover`, but also checks the recovered secret against the
+    /// dealer's `commitments`, returning a `CommitmentMismatch` error if
+    /// any chunk `s` of the secret doesn't satisfy `s * G` being the
+    /// committed point. This catches corrupt shares which would otherwise
+    /// recover a wrong, but validly encoded, secret.
+    ///
+    /// Example:
+    /// ```
+    /// # use sharks::{ RecoverError, Secret, Sharks };
+    /// # use rand_chacha::rand_core::SeedableRng;
+    /// # let sharks = Sharks(3);
+    /// let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    /// let (mut shares, commitments) =
+    ///     sharks.verifiable_dealer_rng(&Secret::from_bytes(&[1, 2]), 3, &mut rng);
+    /// assert_eq!(sharks.recover_verified(&shares, &commitments).unwrap().as_bytes(), vec![1, 2]);
+    /// shares[0].y[0] = shares[1].y[0];
+    /// assert_eq!(
+    ///     sharks.recover_verified(&shares, &commitments),
+    ///     Err(RecoverError::CommitmentMismatch)
+    /// );
+    pub fn recover_verified<'a, T>(
+        &self,
+        shares: T,
+        commitments: &Commitments,
+    ) -> Result<Secret, RecoverError>
+    where
+        T: IntoIterator<Item = &'a Share>,
+        T::IntoIter: Iterator<Item = &'a Share>,
+    {
+        let elements = self.recover_fp(shares)?;
+        if commitments.threshold != self.0 || elements.len() != commitments.chunks() {
+            return Err(RecoverError::CommitmentMismatch);
+        }
+        // The last commitment of each chunk is to the secret chunk
+        let matches = elements.iter().zip(&commitments.points).all(|(s, points)| {
+            Some(&(G1Affine::generator() * to_scalar(s)).to_affine()) == points.last()
+        });
+        if !matches {
+            return Err(RecoverError::CommitmentMismatch);
+        }
+        decode_secret(&elements)
+            .map(Secret::from)
+            .ok_or(RecoverError::InvalidEncoding)
+    }
+}
+
+/// Converts a field element to the BLS12-381 scalar with the same
+/// value. The two are the same field.
+impl From<Fp> for Scalar {
+    fn from(x: Fp) -> Scalar {
+        to_scalar(&x)
+    }
+}
+
+/// Converts a BLS12-381 scalar to the field element with the same
+/// value.
+impl From<Scalar> for Fp {
+    fn from(s: Scalar) -> Fp {
+        let mut repr = FpRepr::default();
+        repr.as_mut().copy_from_slice(&s.to_bytes());
+        Fp::from_repr(repr).unwrap()
+    }
+}
+
+// Both fields have the same modulus and little-endian representation,
+// so the conversion always succeeds.
+fn to_scalar(x: &Fp) -> Scalar {
+    let repr = x.to_repr();
+    Scalar::from_bytes(repr.as_ref().try_into().unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Commitments, RecoverError, Secret, Sharks};
+    use crate::ff::Field;
+    use crate::share_ff::{lagrange_coefficients, Fp, Share};
+    use alloc::vec::Vec;
+    use bls12_381::{G1Projective, Scalar};
+    use core::convert::TryFrom;
+    use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+    #[test]
+    fn test_verify_with_published_commitments() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&[0xab; 40]);
+        let (shares, commitments) = sharks.verifiable_dealer_rng(&secret, 5, &mut rng);
+
+        // A holder who only sees the published bytes and its own share
+        let published = Vec::from(&commitments);
+        let received = Share::try_from(Vec::from(&shares[3]).as_slice()).unwrap();
+        let public = Commitments::try_from(published.as_slice()).unwrap();
+        assert_eq!(public, commitments);
+        assert!(received.verify(&public));
+
+        assert!(shares.iter().all(|s| s.verify(&public)));
+        assert_eq!(
+            sharks.recover(&shares).unwrap().as_bytes(),
+            secret.into_bytes()
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_shares() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let (shares, commitments) =
+            sharks.verifiable_dealer_rng(&Secret::from_bytes(&[1, 2, 3]), 3, &mut rng);
+
+        let mut tampered = shares[0].clone();
+        tampered.y[0] += crate::Fp::one();
+        assert!(!tampered.verify(&commitments));
+
+        let mut moved = shares[0].clone();
+        moved.x = shares[1].x;
+        assert!(!moved.verify(&commitments));
+
+        // Shares from a different dealing don't verify either
+        let (other, _) = sharks.verifiable_dealer_rng(&Secret::from_bytes(&[1, 2, 3]), 1, &mut rng);
+        assert!(!other[0].verify(&commitments));
+    }
+
+    #[test]
+    fn test_recover_verified() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let secret = Secret::from_bytes(&[0xab; 40]);
+        let (shares, commitments) = sharks.verifiable_dealer_rng(&secret, 4, &mut rng);
+        assert_eq!(sharks.recover_verified(&shares, &commitments), Ok(secret));
+
+        // A tampered share still interpolates, but not to the committed
+        // secret
+        let mut tampered = shares.clone();
+        tampered[1].y[1] += crate::Fp::one();
+        assert!(sharks.recover_fp(&tampered).is_ok());
+        assert_eq!(
+            sharks.recover_verified(&tampered, &commitments),
+            Err(RecoverError::CommitmentMismatch)
+        );
+
+        let (_, other) =
+            sharks.verifiable_dealer_rng(&Secret::from_bytes(&[0xcd; 40]), 1, &mut rng);
+        assert_eq!(
+            sharks.recover_verified(&shares, &other),
+            Err(RecoverError::CommitmentMismatch)
+        );
+    }
+
+    #[test]
+    fn test_threshold_bls_combination() {
+        let sharks = Sharks(3);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let key = Fp::random(&mut rng);
+        let shares: Vec<Share> = sharks.dealer_single_rng(key, &mut rng).take(5).collect();
+        // A stand-in for the hash of the message to the curve
+        let hashed = G1Projective::generator() * Scalar::from(Fp::random(&mut rng));
+
+        // The recovered key is the dealt scalar
+        assert_eq!(
+            Scalar::from(sharks.recover_single(&shares).unwrap()),
+            Scalar::from(key)
+        );
+        assert_eq!(sharks.recover_fp(&shares[2..]).unwrap(), [key]);
+        assert_eq!(Fp::from(Scalar::from(key)), key);
+
+        // Partial signatures from any three holders combine to the
+        // signature under the key
+        let signers = [&shares[4], &shares[0], &shares[2]];
+        let partials: Vec<G1Projective> = signers
+            .iter()
+            .map(|s| hashed * Scalar::from(s.y[0]))
+            .collect();
+        let xs: Vec<Fp> = signers.iter().map(|s| s.x).collect();
+        let coefficients = lagrange_coefficients(&xs, Fp::zero()).unwrap();
+        let signature = partials
+            .iter()
+            .zip(coefficients)
+            .fold(G1Projective::identity(), |acc, (p, l)| {
+                acc + p * Scalar::from(l)
+            });
+        assert_eq!(signature, hashed * Scalar::from(key));
+    }
+
+    #[test]
+    fn test_commitments_bad_bytes() {
+        let sharks = Sharks(2);
+        let mut rng = ChaCha8Rng::from_seed([0x90; 32]);
+        let (_, commitments) = sharks.verifiable_dealer_rng(&Secret::from_bytes(&[1]), 1, &mut rng);
+        let bytes = Vec::from(&commitments);
+
+        assert!(Commitments::try_from(&bytes[..3]).is_err());
+        assert!(Commitments::try_from(&bytes[..bytes.len() - 1]).is_err());
+        let mut corrupt = bytes.clone();
+        corrupt[5] ^= 0xff;
+        assert!(Commitments::try_from(corrupt.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+// Round trips shares through a directory of files.
+#![cfg(feature = "std")]
+
+use rand_chacha::rand_core::SeedableRng;
+use sharks::{RecoverError, Secret, Sharks};
+use std::fs;
+use std::path::PathBuf;
+
+// A fresh directory under the system temporary directory, removed when
+// dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("sharks-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn split_and_recover_from_dir() {
+    let dir = TempDir::new("split-and-recover");
+    let sharks = Sharks(3);
+    let secret = Secret::from_bytes(&(0..100).collect::<Vec<u8>>());
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    sharks
+        .split_into_dir_rng(&secret, 5, &dir.0, &mut rng)
+        .unwrap();
+    for i in 1..=5 {
+        assert!(dir.0.join(format!("share-{}.bin", i)).is_file());
+    }
+
+    let recovery = sharks.recover_from_dir(&dir.0).unwrap();
+    assert_eq!(recovery.secret, Ok(secret.clone()));
+    assert_eq!((recovery.valid, recovery.skipped), (5, 0));
+
+    // Missing, truncated and unrelated files
+    fs::remove_file(dir.0.join("share-1.bin")).unwrap();
+    let truncated = fs::read(dir.0.join("share-2.bin")).unwrap();
+    fs::write(dir.0.join("share-2.bin"), &truncated[..20]).unwrap();
+    fs::write(dir.0.join("notes.txt"), b"not a share").unwrap();
+    let recovery = sharks.recover_from_dir(&dir.0).unwrap();
+    assert_eq!(recovery.secret, Ok(secret));
+    assert_eq!((recovery.valid, recovery.skipped), (3, 1));
+
+    fs::remove_file(dir.0.join("share-3.bin")).unwrap();
+    let recovery = sharks.recover_from_dir(&dir.0).unwrap();
+    assert_eq!(recovery.secret, Err(RecoverError::NotEnoughShares));
+    assert_eq!(recovery.valid, 2);
+
+    assert!(sharks.recover_from_dir(dir.0.join("missing")).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn split_into_dir_owner_only() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new("owner-only");
+    // An existing file with wider permissions is tightened too
+    let existing = dir.0.join("share-1.bin");
+    fs::write(&existing, b"old").unwrap();
+    fs::set_permissions(&existing, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0x90; 32]);
+    Sharks(2)
+        .split_into_dir_rng(&Secret::from_bytes(&[1, 2]), 3, &dir.0, &mut rng)
+        .unwrap();
+    for i in 1..=3 {
+        let metadata = fs::metadata(dir.0.join(format!("share-{}.bin", i))).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,100 @@
+// Checks the dealer and recovery against the interoperability test
+// vectors in `test_vectors.json`, which pin the share wire format.
+// Regenerate them with `cargo run --example generate_test_vectors`.
+
+use rand_chacha::rand_core::SeedableRng;
+use serde_json::Value;
+use sharks::{Secret, Share, Sharks};
+use std::convert::TryFrom;
+
+const TEST_VECTORS: &str = include_str!("test_vectors.json");
+
+struct TestVector {
+    name: String,
+    threshold: u32,
+    seed: [u8; 32],
+    secret: Vec<u8>,
+    shares: Vec<Vec<u8>>,
+    recovered: Vec<u8>,
+}
+
+impl TestVector {
+    fn from_test_vector(value: &Value) -> TestVector {
+        let string = |key: &str| value[key].as_str().expect(key).to_string();
+        let bytes = |key: &str| unhex(&string(key));
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&bytes("seed"));
+        TestVector {
+            name: string("name"),
+            threshold: value["threshold"].as_u64().expect("threshold") as u32,
+            seed,
+            secret: bytes("secret"),
+            shares: value["shares"]
+                .as_array()
+                .expect("shares")
+                .iter()
+                .map(|s| unhex(s.as_str().expect("share")))
+                .collect(),
+            recovered: bytes("recovered"),
+        }
+    }
+}
+
+fn load_test_vectors() -> Vec<TestVector> {
+    let json: Value = serde_json::from_str(TEST_VECTORS).unwrap();
+    json["vectors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(TestVector::from_test_vector)
+        .collect()
+}
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn dealer_matches_test_vectors() {
+    let vectors = load_test_vectors();
+    assert!(vectors.len() >= 3);
+    for v in vectors {
+        let mut rng = rand_chacha::ChaCha8Rng::from_seed(v.seed);
+        let shares: Vec<Vec<u8>> = Sharks(v.threshold)
+            .dealer_rng(&Secret::from_bytes(&v.secret), &mut rng)
+            .take(v.shares.len())
+            .map(|s| Vec::from(&s))
+            .collect();
+        assert_eq!(shares, v.shares, "{}", v.name);
+    }
+}
+
+#[test]
+fn recover_matches_test_vectors() {
+    for v in load_test_vectors() {
+        let shares: Vec<Share> = v
+            .shares
+            .iter()
+            .map(|s| Share::try_from(s.as_slice()).unwrap())
+            .collect();
+        let sharks = Sharks(v.threshold);
+        assert_eq!(
+            sharks.recover(&shares).unwrap().as_bytes(),
+            v.recovered,
+            "{}",
+            v.name
+        );
+        // Any threshold of the shares gives the same output
+        let tail = &shares[shares.len() - v.threshold as usize..];
+        assert_eq!(
+            sharks.recover(tail).unwrap().as_bytes(),
+            v.recovered,
+            "{}",
+            v.name
+        );
+        assert_eq!(v.recovered, v.secret, "{}", v.name);
+    }
+}
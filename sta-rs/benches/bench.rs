@@ -1,6 +1,6 @@
 use core::iter;
 use criterion::{criterion_group, criterion_main, Criterion, PlotConfiguration};
-use rand::Rng;
+use rand::{rngs::OsRng, Rng};
 
 use sta_rs_test_utils::*;
 
@@ -35,7 +35,7 @@ fn benchmark_client_triple_generation(c: &mut Criterion) {
     c.bench_function("Client generate triple (local)", |b| {
         let client = client_zipf(10000, 1.03, 2, "t", None);
         b.iter(|| {
-            Triple::generate(&client, None);
+            Triple::generate_rng(&client, None, &mut OsRng);
         });
     });
 
@@ -44,7 +44,7 @@ fn benchmark_client_triple_generation(c: &mut Criterion) {
         let client = client_zipf(10000, 1.03, 2, "t", None);
         let ppoprf_server = PPOPRFServer::new(&[b"t".to_vec()]);
         b.iter(|| {
-            Triple::generate(&client, Some(&ppoprf_server));
+            Triple::generate_rng(&client, Some(&ppoprf_server), &mut OsRng);
         });
     });
 
@@ -52,7 +52,7 @@ fn benchmark_client_triple_generation(c: &mut Criterion) {
         let random_bytes = rand::thread_rng().gen::<[u8; 32]>();
         let client = client_zipf(10000, 1.03, 2, "t", Some(random_bytes.to_vec()));
         b.iter(|| {
-            Triple::generate(&client, None);
+            Triple::generate_rng(&client, None, &mut OsRng);
         });
     });
 
@@ -62,16 +62,17 @@ fn benchmark_client_triple_generation(c: &mut Criterion) {
         let client = client_zipf(10000, 1.03, 2, "t", Some(random_bytes.to_vec()));
         let ppoprf_server = PPOPRFServer::new(&[b"t".to_vec()]);
         b.iter(|| {
-            Triple::generate(&client, Some(&ppoprf_server));
+            Triple::generate_rng(&client, Some(&ppoprf_server), &mut OsRng);
         });
     });
 }
 
 fn benchmark_server_retrieval(c: &mut Criterion) {
-    let triples: Vec<Triple> =
-        iter::repeat_with(|| Triple::generate(&client_zipf(10000, 1.03, 50, "t", None), None))
-            .take(1000)
-            .collect();
+    let triples: Vec<Triple> = iter::repeat_with(|| {
+        Triple::generate_rng(&client_zipf(10000, 1.03, 50, "t", None), None, &mut OsRng)
+    })
+    .take(1000)
+    .collect();
     c.bench_function("Server retrieve outputs", |b| {
         let agg_server = AggregationServer::new(50, "t");
         b.iter(|| {
@@ -102,7 +103,7 @@ fn benchmark_end_to_end(c: &mut Criterion) {
     ].iter().for_each(|params| {
         let epoch = "t";
         let triples = get_triples(params, epoch);
-        group.bench_function(&format!("E2E server (n={}, s={}, clients={}, threshold={}, local_randomness={}, aux_data={})", params.n, params.s, params.clients, params.threshold, params.local, params.aux_data), |b| {
+        group.bench_function(format!("E2E server (n={}, s={}, clients={}, threshold={}, local_randomness={}, aux_data={})", params.n, params.s, params.clients, params.threshold, params.local, params.aux_data), |b| {
             let agg_server = AggregationServer::new(params.threshold, epoch);
             b.iter(|| {
                 let _o = agg_server.retrieve_outputs(&triples);
@@ -111,6 +112,7 @@ fn benchmark_end_to_end(c: &mut Criterion) {
     });
 }
 
+#[allow(clippy::needless_late_init)]
 fn get_triples(params: &Params, epoch: &str) -> Vec<Triple> {
     let triples: Vec<Triple>;
     if !params.local {
@@ -120,7 +122,7 @@ fn get_triples(params: &Params, epoch: &str) -> Vec<Triple> {
         {
             let mut ppoprf_server = PPOPRFServer::new(&[b"t".to_vec()]);
             triples = iter::repeat_with(|| {
-                Triple::generate(
+                Triple::generate_rng(
                     &client_zipf(
                         params.n,
                         params.s,
@@ -129,6 +131,7 @@ fn get_triples(params: &Params, epoch: &str) -> Vec<Triple> {
                         get_aux_data(params.aux_data),
                     ),
                     Some(&ppoprf_server),
+                    &mut OsRng,
                 )
             })
             .take(params.clients)
@@ -137,7 +140,7 @@ fn get_triples(params: &Params, epoch: &str) -> Vec<Triple> {
         }
     } else {
         triples = iter::repeat_with(|| {
-            Triple::generate(
+            Triple::generate_rng(
                 &client_zipf(
                     params.n,
                     params.s,
@@ -146,6 +149,7 @@ fn get_triples(params: &Params, epoch: &str) -> Vec<Triple> {
                     get_aux_data(params.aux_data),
                 ),
                 None,
+                &mut OsRng,
             )
         })
         .take(params.clients)
@@ -44,7 +44,7 @@
 //!   key,
 //!   share,
 //!   tag,
-//! } = client.share_with_local_randomness();
+//! } = client.share_with_local_randomness_rng(&mut rand::rngs::OsRng);
 //! ```
 //!
 //! # Example (server)
@@ -62,7 +62,10 @@
 //! # for i in 0..3 {
 //! #     clients.push(Client::new(measurement.as_bytes(), threshold, epoch, None));
 //! # }
-//! # let triples: Vec<Triple> = clients.into_iter().map(|c| Triple::generate(&c, None)).collect();
+//! # let triples: Vec<Triple> = clients
+//! #     .into_iter()
+//! #     .map(|c| Triple::generate_rng(&c, None, &mut rand::rngs::OsRng))
+//! #     .collect();
 //! # let shares: Vec<Share> = triples.iter().map(|triple| triple.share.clone()).collect();
 //! let message = share_recover(&shares).unwrap().get_message();
 //!
@@ -71,18 +74,26 @@
 //! derive_ske_key(&message, epoch.as_bytes(), &mut enc_key);
 //! ```
 
+pub mod prelude;
+
 use std::error::Error;
 use std::str;
 
-use rand_core::RngCore;
+use rand_core::{CryptoRng, RngCore};
+use ring::hkdf;
 use strobe_rng::StrobeRng;
 use strobe_rs::{SecParam, Strobe};
 
-use adss_rs::{recover, Commune};
+use adss_rs::{recover, recover_with_ctx, Commune};
+#[cfg(feature = "vss")]
+pub use sharks::Commitments;
 pub use {adss_rs::load_bytes, adss_rs::store_bytes, adss_rs::Share};
 
 #[cfg(feature = "star2")]
-use ppoprf::ppoprf::{end_to_end_evaluation, Server as PPOPRFServer};
+use ppoprf::ppoprf::{
+    end_to_end_evaluation, end_to_end_evaluation_with_proof, EvaluationProof,
+    Server as PPOPRFServer,
+};
 
 pub const AES_BLOCK_LEN: usize = 24;
 // FIXME
@@ -185,12 +196,16 @@ pub struct ClientSharingMaterial {
 // In the STAR1 protocol, the `Client` samples randomness locally: derived
 // straight from the `Measurement` itself. In the STAR2 protocol, the
 // `Client` derives its randomness from an exchange with a
-// specifically-defined server that runs a POPRF.
+// specifically-defined server that runs a POPRF. If the POPRF is
+// evaluated elsewhere, the finalized output can instead be supplied with
+// `set_oprf_output`.
 pub struct Client {
     pub x: Measurement,
     threshold: u32,
     epoch: String,
     pub aux: Option<AssociatedData>,
+    oprf_output: Option<[u8; DIGEST_LEN]>,
+    expected_aux_len: Option<usize>,
 }
 impl Client {
     pub fn new(x: &[u8], threshold: u32, epoch: &str, aux: Option<Vec<u8>>) -> Self {
@@ -200,38 +215,160 @@ impl Client {
             threshold,
             epoch: epoch.to_string(),
             aux: aux.map(|x| AssociatedData::new(&x)),
+            oprf_output: None,
+            expected_aux_len: None,
         }
     }
 
+    // Requires the `AssociatedData` of the client to be exactly `len`
+    // bytes long, for protocols with fixed-size associated data. The
+    // length is checked when a `Triple` is generated for the client.
+    pub fn set_expected_aux_len(&mut self, len: usize) {
+        self.expected_aux_len = Some(len);
+    }
+
+    pub fn expected_aux_len(&self) -> Option<usize> {
+        self.expected_aux_len
+    }
+
+    pub fn epoch(&self) -> &str {
+        &self.epoch
+    }
+
+    // Sets a finalized OPRF output that was computed outside of this
+    // process, to be used by `share_with_oprf_output`.
+    pub fn set_oprf_output(&mut self, out: [u8; DIGEST_LEN]) {
+        self.oprf_output = Some(out);
+    }
+
+    pub fn has_oprf_output(&self) -> bool {
+        self.oprf_output.is_some()
+    }
+
     // Share with OPRF randomness (STAR1)
+    #[cfg(not(feature = "no-thread-rng"))]
     pub fn share_with_local_randomness(&self) -> ClientSharingMaterial {
+        self.share_with_local_randomness_rng(&mut rand::thread_rng())
+    }
+
+    // As `share_with_local_randomness`, but samples the share point
+    // from `rng` rather than the thread random number generator.
+    pub fn share_with_local_randomness_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> ClientSharingMaterial {
         let mut rnd = vec![0u8; 32];
         self.sample_local_randomness(&mut rnd);
-        let r = self.derive_random_values(&rnd);
-
-        // key is then used for encrypting measurement and associated
-        // data
-        let key = self.derive_key(&r[0]);
-        let share = self.share(&r[0], &r[1]);
-        let tag = r[2].clone();
-        ClientSharingMaterial { key, share, tag }
+        self.share_with_randomness(&rnd, rng)
     }
 
-    #[cfg(feature = "star2")]
+    #[cfg(all(feature = "star2", not(feature = "no-thread-rng")))]
     // Share with OPRF randomness (STAR2)
     pub fn share_with_oprf_randomness(&self, oprf_server: &PPOPRFServer) -> ClientSharingMaterial {
+        self.share_with_oprf_randomness_rng(oprf_server, &mut rand::thread_rng())
+    }
+
+    #[cfg(feature = "star2")]
+    // As `share_with_oprf_randomness`, but samples the share point from
+    // `rng`.
+    pub fn share_with_oprf_randomness_rng<R: RngCore + CryptoRng>(
+        &self,
+        oprf_server: &PPOPRFServer,
+        rng: &mut R,
+    ) -> ClientSharingMaterial {
         let mut rnd = vec![0u8; 32];
         self.sample_oprf_randomness(oprf_server, &mut rnd);
-        let r = self.derive_random_values(&rnd);
+        self.share_with_randomness(&rnd, rng)
+    }
+
+    // Share with the OPRF output supplied by `set_oprf_output` (STAR2).
+    // Panics if no output has been set.
+    #[cfg(not(feature = "no-thread-rng"))]
+    pub fn share_with_oprf_output(&self) -> ClientSharingMaterial {
+        self.share_with_oprf_output_rng(&mut rand::thread_rng())
+    }
+
+    // As `share_with_oprf_output`, but samples the share point from
+    // `rng`.
+    pub fn share_with_oprf_output_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> ClientSharingMaterial {
+        let rnd = self.oprf_output.expect("No OPRF output has been set");
+        self.share_with_randomness(&rnd, rng)
+    }
+
+    // As `share_with_local_randomness_rng`, but also returns
+    // commitments that make the share verifiable, see
+    // `share_with_randomness_verifiable`.
+    #[cfg(feature = "vss")]
+    pub fn share_with_local_randomness_verifiable_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> (ClientSharingMaterial, Commitments) {
+        let mut rnd = vec![0u8; 32];
+        self.sample_local_randomness(&mut rnd);
+        self.share_with_randomness_verifiable(&rnd, rng)
+    }
+
+    // As `share_with_oprf_randomness_rng`, but also returns
+    // commitments that make the share verifiable.
+    #[cfg(all(feature = "star2", feature = "vss"))]
+    pub fn share_with_oprf_randomness_verifiable_rng<R: RngCore + CryptoRng>(
+        &self,
+        oprf_server: &PPOPRFServer,
+        rng: &mut R,
+    ) -> (ClientSharingMaterial, Commitments) {
+        let mut rnd = vec![0u8; 32];
+        self.sample_oprf_randomness(oprf_server, &mut rnd);
+        self.share_with_randomness_verifiable(&rnd, rng)
+    }
+
+    // As `share_with_oprf_output_rng`, but also returns commitments
+    // that make the share verifiable.
+    #[cfg(feature = "vss")]
+    pub fn share_with_oprf_output_verifiable_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> (ClientSharingMaterial, Commitments) {
+        let rnd = self.oprf_output.expect("No OPRF output has been set");
+        self.share_with_randomness_verifiable(&rnd, rng)
+    }
+
+    fn share_with_randomness<R: RngCore + CryptoRng>(
+        &self,
+        rnd: &[u8],
+        rng: &mut R,
+    ) -> ClientSharingMaterial {
+        let r = self.derive_random_values(rnd);
 
         // key is then used for encrypting measurement and associated
         // data
         let key = self.derive_key(&r[0]);
-        let share = self.share(&r[0], &r[1]);
+        let share = self.share_rng(&r[0], &r[1], rng);
         let tag = r[2].clone();
         ClientSharingMaterial { key, share, tag }
     }
 
+    // Shares as `share_with_randomness` does, and also returns Feldman
+    // commitments to the polynomial the share lies on. The polynomial
+    // is derived from the randomness, like the tag, so clients with the
+    // same measurement return the same commitments, which reveal no
+    // more about it than the tag does. A share that doesn't lie on the
+    // committed polynomial fails `Share::verify`.
+    #[cfg(feature = "vss")]
+    fn share_with_randomness_verifiable<R: RngCore + CryptoRng>(
+        &self,
+        rnd: &[u8],
+        rng: &mut R,
+    ) -> (ClientSharingMaterial, Commitments) {
+        let r = self.derive_random_values(rnd);
+        let key = self.derive_key(&r[0]);
+        let (share, commitments) = self.commune(&r[0], &r[1]).share_verifiable_rng(rng);
+        let tag = r[2].clone();
+        (ClientSharingMaterial { key, share, tag }, commitments)
+    }
+
     fn derive_random_values(&self, randomness: &[u8]) -> Vec<Vec<u8>> {
         let mut output = Vec::new();
         for i in 0..3 {
@@ -253,9 +390,12 @@ impl Client {
         enc_key
     }
 
-    fn share(&self, r1: &[u8], r2: &[u8]) -> Share {
-        let c = Commune::new(self.threshold, r1.to_vec(), r2.to_vec(), None);
-        c.share()
+    fn share_rng<R: RngCore + CryptoRng>(&self, r1: &[u8], r2: &[u8], rng: &mut R) -> Share {
+        self.commune(r1, r2).share_rng(rng)
+    }
+
+    fn commune(&self, r1: &[u8], r2: &[u8]) -> Commune {
+        Commune::new(self.threshold, r1.to_vec(), r2.to_vec(), None)
     }
 
     pub fn sample_local_randomness(&self, out: &mut [u8]) {
@@ -278,7 +418,29 @@ impl Client {
     pub fn sample_oprf_randomness(&self, oprf_server: &PPOPRFServer, out: &mut [u8]) {
         let mds = oprf_server.get_valid_metadata_tags();
         let index = mds.iter().position(|r| r == self.epoch.as_bytes()).unwrap();
-        end_to_end_evaluation(oprf_server, self.x.as_slice(), index, true, out);
+        end_to_end_evaluation(oprf_server, self.x.as_slice(), index, true, out).unwrap();
+    }
+
+    // As `sample_oprf_randomness`, but also returns the proof that the
+    // server evaluated the PPOPRF with the key it published, which
+    // `sample_oprf_randomness` checks and then discards. Keeping the
+    // proof lets a client show others that the server behaved, or
+    // check it again against a public key obtained out of band.
+    //
+    // Checking a proof costs the client a handful of group operations,
+    // about as much as the evaluation itself. This is worth it whenever
+    // the randomness server isn't trusted to be honest: a server using
+    // a different key for some clients could otherwise partition them,
+    // and link their measurements, without being noticed.
+    #[cfg(feature = "star2")]
+    pub fn sample_oprf_randomness_verifiable(
+        &self,
+        oprf_server: &PPOPRFServer,
+        out: &mut [u8],
+    ) -> EvaluationProof {
+        let mds = oprf_server.get_valid_metadata_tags();
+        let index = mds.iter().position(|r| r == self.epoch.as_bytes()).unwrap();
+        end_to_end_evaluation_with_proof(oprf_server, self.x.as_slice(), index, out).unwrap()
     }
 }
 
@@ -287,6 +449,15 @@ pub fn share_recover(shares: &[Share]) -> Result<Commune, Box<dyn Error>> {
     recover(shares)
 }
 
+// As `share_recover`, but borrows the interpolation scratch space from
+// `ctx`, so that an aggregation server can reuse it across groups.
+pub fn share_recover_with_ctx(
+    ctx: &mut sharks::InterpolationCtx,
+    shares: &[Share],
+) -> Result<Commune, Box<dyn Error>> {
+    recover_with_ctx(ctx, shares)
+}
+
 // The `derive_ske_key` helper function derives symmetric encryption
 // keys that are used for encrypting/decrypting `Ciphertext` objects
 // during the STAR protocol.
@@ -296,6 +467,47 @@ pub fn derive_ske_key(r1: &[u8], epoch: &[u8], key_out: &mut [u8]) {
     key_out.copy_from_slice(&to_fill[..16]);
 }
 
+// Salt for extracting keys from an OPRF output in `finalize_oprf_keys`.
+const OPRF_KEYS_SALT: &[u8] = b"star_finalize_oprf_keys";
+
+// Derives one 32-byte key for each of `contexts` from the finalized
+// OPRF `output`, so that a client can use the output for more than the
+// randomness of its share, such as for keys to encrypt associated data
+// or to tag reports. The keys are HKDF-SHA256 (RFC 5869) outputs, with
+// the OPRF output as input keying material, a fixed salt, and each
+// context as the info string. The same output and context always give
+// the same key, and keys for different contexts are independent, so
+// learning some of them reveals nothing about the others or the output.
+// Repeating a context repeats its key.
+//
+// The randomness STAR derives from the output itself uses STROBE
+// rather than HKDF, so keys from this function are independent of it
+// whatever the contexts. For reference, the internal derivations are
+// `strobe_digest` with the labels:
+//
+// - `star_derive_randoms`, with additional data `[0]` for the
+//   randomness that seeds the share and the encryption key, `[1]` for
+//   the randomness of the share, and `[2]` for the tag,
+// - `star_derive_ske_key`, with the epoch as additional data, for the
+//   key encrypting the measurement, derived from the first of those.
+//
+// Contexts should still name the application and purpose of each key,
+// such as `b"myapp aux encryption v1"`, to keep them apart from keys
+// derived by other code from the same output.
+pub fn finalize_oprf_keys(output: &[u8; DIGEST_LEN], contexts: &[&[u8]]) -> Vec<[u8; 32]> {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, OPRF_KEYS_SALT).extract(output);
+    contexts
+        .iter()
+        .map(|context| {
+            let mut key = [0u8; 32];
+            prk.expand(&[context], hkdf::HKDF_SHA256)
+                .and_then(|okm| okm.fill(&mut key))
+                .expect("HKDF-SHA256 can expand to 32 bytes");
+            key
+        })
+        .collect()
+}
+
 pub fn strobe_digest(key: &[u8], ad: &[&[u8]], label: &str, out: &mut [u8]) {
     if out.len() != DIGEST_LEN {
         panic!(
@@ -314,3 +526,18 @@ pub fn strobe_digest(key: &[u8], ad: &[&[u8]], label: &str, out: &mut [u8]) {
     let mut rng: StrobeRng = t.into();
     rng.fill_bytes(out);
 }
+
+// Checks that the sharing methods which implicitly use `thread_rng` are
+// absent with the `no-thread-rng` feature.
+///
+/// ```compile_fail
+/// sta_rs::Client::new(b"foo", 2, "t", None).share_with_local_randomness();
+/// ```
+///
+/// ```compile_fail
+/// let mut client = sta_rs::Client::new(b"foo", 2, "t", None);
+/// client.set_oprf_output([0; sta_rs::DIGEST_LEN]);
+/// client.share_with_oprf_output();
+/// ```
+#[cfg(feature = "no-thread-rng")]
+mod no_thread_rng {}
@@ -0,0 +1,33 @@
+//! Re-exports of the protocol types, and of the field and curve types
+//! that appear in their interfaces, with the traits needed to use them.
+//!
+//! The `x` coordinates of shares are `sharks::Fp` elements, and the
+//! PPOPRF used by STAR2 works with `curve25519-dalek` Ristretto points.
+//! Code that uses these types through a separate dependency on `ff` or
+//! `curve25519-dalek` only compiles if cargo resolves it to the same
+//! version as this crate. Importing them from here always gives the
+//! versions this crate is built with.
+//!
+//! ```
+//! use sta_rs::prelude::*;
+//!
+//! let client = Client::new(b"hello world", 2, "t", None);
+//! let ClientSharingMaterial { share, .. } =
+//!     client.share_with_local_randomness_rng(&mut rand::rngs::OsRng);
+//! // The share `x` coordinate round trips through its encoding
+//! let x: Fp = share.x();
+//! assert_eq!(Fp::from_repr(x.to_repr()).unwrap(), x);
+//!
+//! // As do Ristretto points, through their compressed form
+//! let point = RISTRETTO_BASEPOINT_POINT * Scalar::from(7u64);
+//! let compressed: CompressedRistretto = point.compress();
+//! assert_eq!(compressed.decompress(), Some(point));
+//! ```
+
+pub use crate::{derive_ske_key, share_recover, share_recover_with_ctx};
+pub use crate::{AssociatedData, Client, ClientSharingMaterial, Measurement, Share};
+pub use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+pub use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+pub use curve25519_dalek::scalar::Scalar;
+pub use sharks::prelude::{Field, Fp, FpRepr, PrimeField};
+pub use sharks::InterpolationCtx;
@@ -0,0 +1,164 @@
+// Canonical CBOR encodings of `Triple` and `Output`.
+//
+// Each type is encoded as a map with text keys, in the canonical key
+// order of RFC 7049 section 3.9 (shorter keys first, then bytewise).
+// Byte fields are encoded as byte strings, using the same encodings as
+// `to_bytes` for the nested `Share`. The `commitment` and
+// `share_commitments` of a triple are only present in the map if they
+// are set.
+//
+// Decoding only accepts the canonical encoding: the input must be a
+// single data item, which encodes back to exactly the same bytes, so
+// trailing data, indefinite lengths, and non-minimal integers are all
+// rejected.
+
+use std::convert::TryFrom;
+
+use ciborium::value::Value;
+
+use crate::{Ciphertext, Output, Triple};
+use sta_rs::{AssociatedData, Measurement, Share};
+
+impl Triple {
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut fields = vec![
+            text_field("tag", Value::Bytes(self.tag.clone())),
+            text_field("epoch", Value::Text(self.epoch.clone())),
+        ];
+        fields.push(text_field("share", Value::Bytes(self.share.to_bytes())));
+        fields.push(text_field(
+            "ciphertext",
+            Value::Bytes(self.ciphertext.to_bytes()),
+        ));
+        if let Some(commitment) = &self.commitment {
+            fields.push(text_field("commitment", Value::Bytes(commitment.clone())));
+        }
+        if let Some(commitments) = &self.share_commitments {
+            fields.push(text_field(
+                "share_commitments",
+                Value::Bytes(commitments.clone()),
+            ));
+        }
+        encode(Value::Map(fields))
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Option<Triple> {
+        const KEYS: [&str; 6] = [
+            "tag",
+            "epoch",
+            "share",
+            "ciphertext",
+            "commitment",
+            "share_commitments",
+        ];
+        const OPTIONAL: [&str; 2] = ["commitment", "share_commitments"];
+        let mut fields = decode_map_with_optional(bytes, &KEYS, &OPTIONAL)?.into_iter();
+        let tag = fields.next()??.into_bytes().ok()?;
+        let epoch = fields.next()??.into_text().ok()?;
+        let share = Share::from_bytes(&fields.next()??.into_bytes().ok()?)?;
+        let ciphertext = Ciphertext::from_bytes(&fields.next()??.into_bytes().ok()?);
+        let mut triple = Triple::new(ciphertext, share, &tag, &epoch);
+        if let Some(commitment) = fields.next()? {
+            triple.commitment = Some(commitment.into_bytes().ok()?);
+        }
+        if let Some(commitments) = fields.next()? {
+            triple.share_commitments = Some(commitments.into_bytes().ok()?);
+        }
+        Some(triple)
+    }
+}
+
+impl Output {
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let aux = self
+            .aux
+            .iter()
+            .map(|a| match a {
+                Some(a) => Value::Bytes(a.as_vec()),
+                None => Value::Null,
+            })
+            .collect();
+        encode(Value::Map(vec![
+            text_field("x", Value::Bytes(self.x.as_vec())),
+            text_field("aux", Value::Array(aux)),
+            text_field("threshold", Value::Integer(self.threshold.into())),
+            text_field(
+                "contributors",
+                Value::Integer((self.contributors as u64).into()),
+            ),
+        ]))
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Option<Output> {
+        let mut fields = decode_map(bytes, &["x", "aux", "threshold", "contributors"])?.into_iter();
+        let x = fields.next()?.into_bytes().ok()?;
+        if x.len() > sta_rs::MEASUREMENT_MAX_LEN {
+            return None;
+        }
+        let aux = fields
+            .next()?
+            .into_array()
+            .ok()?
+            .into_iter()
+            .map(|a| match a {
+                Value::Null => Some(None),
+                Value::Bytes(b) => Some(Some(AssociatedData::new(&b))),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        let threshold = u32::try_from(fields.next()?.into_integer().ok()?).ok()?;
+        let contributors = usize::try_from(fields.next()?.into_integer().ok()?).ok()?;
+        Some(Output {
+            x: Measurement::new(&x),
+            aux,
+            threshold,
+            contributors,
+        })
+    }
+}
+
+fn text_field(key: &str, value: Value) -> (Value, Value) {
+    (Value::Text(key.to_string()), value)
+}
+
+fn encode(value: Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(&value, &mut out).expect("CBOR encoding failed");
+    out
+}
+
+// Decodes a canonically encoded map with exactly the text `keys`, in
+// order, returning the values.
+fn decode_map(bytes: &[u8], keys: &[&str]) -> Option<Vec<Value>> {
+    decode_map_with_optional(bytes, keys, &[])?
+        .into_iter()
+        .collect()
+}
+
+// As `decode_map`, but the keys in `optional` may be missing from the
+// map, and their values are then `None`.
+fn decode_map_with_optional(
+    bytes: &[u8],
+    keys: &[&str],
+    optional: &[&str],
+) -> Option<Vec<Option<Value>>> {
+    let value: Value = ciborium::de::from_reader(bytes).ok()?;
+    if encode(value.clone()) != bytes {
+        return None;
+    }
+    let mut entries = value.into_map().ok()?.into_iter().peekable();
+    let mut values = Vec::with_capacity(keys.len());
+    for key in keys {
+        match entries.peek() {
+            Some((k, _)) if k.as_text() == Some(*key) => {
+                values.push(entries.next().map(|(_, v)| v));
+            }
+            _ if optional.contains(key) => values.push(None),
+            _ => return None,
+        }
+    }
+    if entries.next().is_some() {
+        return None;
+    }
+    Some(values)
+}
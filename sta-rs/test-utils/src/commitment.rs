@@ -0,0 +1,97 @@
+// Commitments to the measurement of a triple, for later audits.
+//
+// `Triple::generate_committed` attaches a hiding commitment to the
+// client's measurement and the epoch, and returns the
+// `CommitmentOpening` that the client must store to open it. The
+// commitment is `strobe_digest` keyed by a random nonce, over the
+// epoch and the measurement. Without the nonce it reveals nothing about
+// the measurement, so the `AggregationServer` stores it along with the
+// rest of the triple, but can't check it against a measurement, even
+// one it has guessed, until the client discloses the opening. If the
+// group meets the `threshold`, or the measurement is otherwise known,
+// `Triple::verify_commitment` then shows whether the client submitted
+// it.
+//
+// Commitments are opt-in, and have privacy implications. A client that
+// keeps its opening can later be compelled to prove what it sent, which
+// the STAR protocol alone never allows, and anyone who obtains an
+// opening can test guesses of the measurement against the triple,
+// whatever the `threshold`. Each commitment is unique, so it also
+// identifies its triple wherever it is logged. Only commit when
+// submissions may need to be attributed, and store openings as
+// carefully as the measurements themselves.
+
+use std::convert::TryFrom;
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{PPOPRFServer, Triple, TripleError};
+use sta_rs::{strobe_digest, Client, Measurement, DIGEST_LEN};
+
+// The secret nonce needed to open the commitment of a triple.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitmentOpening([u8; DIGEST_LEN]);
+
+impl CommitmentOpening {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<CommitmentOpening> {
+        <[u8; DIGEST_LEN]>::try_from(bytes).ok().map(Self)
+    }
+}
+
+impl Triple {
+    // As `generate_rng`, but commits to the measurement of the client,
+    // and returns the opening along with the triple. The nonce of the
+    // commitment is sampled from `rng` too. Panics if the associated
+    // data is not the expected length.
+    pub fn generate_committed<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> (Self, CommitmentOpening) {
+        match Self::try_generate_committed(client, oprf_server, rng) {
+            Ok(committed) => committed,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // As `try_generate_rng`, but commits to the measurement of the
+    // client, and returns the opening along with the triple.
+    pub fn try_generate_committed<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> Result<(Self, CommitmentOpening), TripleError> {
+        let mut triple = Self::try_generate_rng(client, oprf_server, rng)?;
+        let mut nonce = [0u8; DIGEST_LEN];
+        rng.fill_bytes(&mut nonce);
+        let opening = CommitmentOpening(nonce);
+        triple.commitment = Some(commit(&opening, &triple.epoch, client.x.as_slice()));
+        Ok((triple, opening))
+    }
+
+    // Checks that the triple commits to `measurement` under `opening`.
+    // Returns `false` for triples without a commitment.
+    pub fn verify_commitment(
+        &self,
+        measurement: &Measurement,
+        opening: &CommitmentOpening,
+    ) -> bool {
+        self.commitment.as_deref()
+            == Some(&commit(opening, &self.epoch, measurement.as_slice())[..])
+    }
+}
+
+fn commit(opening: &CommitmentOpening, epoch: &str, measurement: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; DIGEST_LEN];
+    strobe_digest(
+        &opening.0,
+        &[epoch.as_bytes(), measurement],
+        "star_measurement_commitment",
+        &mut out,
+    );
+    out
+}
@@ -0,0 +1,74 @@
+// Compression of the associated data in triples.
+//
+// `Triple::generate_compressed` compresses the associated data of the
+// client with raw DEFLATE (RFC 1951) before it is encrypted, and the
+// `AggregationServer` decompresses it after recovery, so that
+// `Output::aux` holds the original data. Only the associated data is
+// compressed: the measurement, key derivation and sharing are the same
+// as for `Triple::generate`, and compressed and uncompressed triples
+// from different clients are aggregated together. A flag stored after
+// the compressed data in the plaintext marks it as compressed. Servers
+// built without the `compress` feature treat such triples as
+// malformed, so that the group fails to recover rather than returning
+// compressed data.
+//
+// The length of a ciphertext is visible to the server even when the
+// threshold isn't met, and with compression it depends on the content
+// of the associated data, not just its length. Don't compress data that
+// mixes secrets with values an attacker can influence, and note that
+// `Client::set_expected_aux_len` no longer fixes the length of the
+// ciphertext.
+//
+// Decompression stops at `MAX_DECOMPRESSED_AUX_LEN` bytes, so that a
+// small malicious triple can't make the server allocate without bound.
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{PPOPRFServer, Triple, TripleError};
+use sta_rs::Client;
+
+// The largest associated data that the server will decompress, in
+// bytes. Triples whose data decompresses to more are malformed.
+pub const MAX_DECOMPRESSED_AUX_LEN: usize = 1 << 20;
+
+// Flag for associated data that is compressed with raw DEFLATE.
+pub(crate) const AUX_DEFLATE: u8 = 1;
+
+// The DEFLATE compression level, from 0 to 10. Level 6 is the zlib
+// default.
+const COMPRESSION_LEVEL: u8 = 6;
+
+impl Triple {
+    // As `generate_rng`, but compresses the associated data of the
+    // client. Panics if the associated data is not the expected length.
+    pub fn generate_compressed<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> Self {
+        match Self::try_generate_compressed(client, oprf_server, rng) {
+            Ok(triple) => triple,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // As `try_generate_rng`, but compresses the associated data of the
+    // client. The expected length is that of the uncompressed data.
+    pub fn try_generate_compressed<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> Result<Self, TripleError> {
+        Self::try_generate_with_aux_flags(client, oprf_server, &[AUX_DEFLATE], rng)
+    }
+}
+
+pub(crate) fn compress_aux(aux: &[u8]) -> Vec<u8> {
+    compress_to_vec(aux, COMPRESSION_LEVEL)
+}
+
+pub(crate) fn decompress_aux(compressed: &[u8]) -> Option<Vec<u8>> {
+    decompress_to_vec_with_limit(compressed, MAX_DECOMPRESSED_AUX_LEN).ok()
+}
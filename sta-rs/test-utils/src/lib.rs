@@ -1,14 +1,41 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+#[cfg(any(feature = "vss", not(feature = "no-thread-rng")))]
+use std::convert::TryFrom;
 use std::fmt;
+use std::io::{self, Write};
+use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use rand::distributions::Distribution;
+#[cfg(not(feature = "no-thread-rng"))]
+use rand_core::OsRng;
+use rand_core::{CryptoRng, RngCore};
 use rayon::prelude::*;
+use sharks::InterpolationCtx;
 
 use strobe_rs::{SecParam, Strobe};
 
 use zipf::ZipfDistribution;
 
+#[cfg(feature = "cbor")]
+mod cbor;
+mod commitment;
+#[cfg(feature = "compress")]
+mod compress;
+mod multi_epoch;
+#[cfg(feature = "async")]
+mod stream;
+#[cfg(feature = "vss")]
+mod verifiable;
+
+pub use commitment::CommitmentOpening;
+#[cfg(feature = "compress")]
+pub use compress::MAX_DECOMPRESSED_AUX_LEN;
+pub use multi_epoch::MultiEpochAggregationServer;
+#[cfg(feature = "async")]
+pub use stream::AsyncAggregator;
+
 #[cfg(feature = "star2")]
 pub use ppoprf::ppoprf::Server as PPOPRFServer;
 
@@ -37,6 +64,118 @@ pub fn client_zipf(n: usize, s: f64, threshold: u32, epoch: &str, aux: Option<Ve
     Client::new(x.as_slice(), threshold, epoch, aux)
 }
 
+// The `ZipfPopulation` describes a population of `clients`, each
+// sending a measurement sampled with `measurement_zipf(n, s)`.
+#[derive(Debug, Clone, Copy)]
+pub struct ZipfPopulation {
+    pub n: usize,
+    pub s: f64,
+    pub clients: usize,
+}
+
+// The `round_trip` function runs the whole pipeline of sharing a
+// `secret`: it deals `total` shares with the given `threshold`,
+// serializes each of them to bytes, parses them back as a recipient
+// would, and recovers the secret from the parsed shares. Panics if any
+// step fails.
+#[cfg(not(feature = "no-thread-rng"))]
+pub fn round_trip(secret: &[u8], threshold: u32, total: usize) -> Vec<u8> {
+    let sharks = sharks::Sharks(threshold);
+    let dealer = sharks.dealer_rng(&sharks::Secret::from_bytes(secret), &mut OsRng);
+    let transported: Vec<Vec<u8>> = dealer.take(total).map(|s| Vec::from(&s)).collect();
+    let received: Vec<sharks::Share> = transported
+        .iter()
+        .map(|bytes| sharks::Share::try_from(bytes.as_slice()).unwrap())
+        .collect();
+    sharks.recover(&received).unwrap().into_bytes()
+}
+
+// The `tamper` function returns a copy of `share` with the `y`
+// coordinate of its first chunk changed, as a cheating dealer would
+// hand to a single holder. The result is off the dealt polynomials, so
+// with the `vss` feature, `Share::verify` rejects it against the
+// dealer's commitments:
+//
+//     let (shares, commitments) = sharks.verifiable_dealer_rng(&secret, n, &mut rng);
+//     assert!(shares[0].verify(&commitments));
+//     assert!(!tamper(&shares[0]).verify(&commitments));
+pub fn tamper(share: &sharks::Share) -> sharks::Share {
+    let mut tampered = share.clone();
+    tampered.y[0] += sharks::Fp::from(1);
+    tampered
+}
+
+// The `estimate_recoverable` function returns the expected number of
+// distinct measurements that are sent by at least `threshold` clients
+// of the `distribution`, and so would be revealed by the
+// `AggregationServer`.
+//
+// The number of clients sending the measurement of rank `k` follows a
+// binomial distribution with `p_k = k^-s / H_{n,s}`, so the estimate is
+// the sum over all ranks of `P[X_k >= threshold]`.
+pub fn estimate_recoverable(threshold: u32, distribution: &ZipfPopulation) -> f64 {
+    let ZipfPopulation { n, s, clients } = *distribution;
+    let weights: Vec<f64> = (1..=n).map(|k| (k as f64).powf(-s)).collect();
+    let total: f64 = weights.iter().sum();
+    weights
+        .iter()
+        .map(|w| binomial_at_least(clients, w / total, threshold as usize))
+        .sum()
+}
+
+// The `sweep_thresholds` function returns, for each of `thresholds`,
+// the number of groups of `triples` with the same tag that have at
+// least that many triples, which is how many measurements an
+// `AggregationServer` with that threshold would reveal. The triples
+// are grouped once, and the group sizes are compared against each
+// threshold in turn.
+//
+// Nothing is recovered, so groups that would fail to recover, such as
+// groups smaller than the threshold the clients shared with, are still
+// counted. The triples should all be for the same epoch.
+pub fn sweep_thresholds(triples: &[Triple], thresholds: &[u32]) -> Vec<(u32, usize)> {
+    let sizes = count_tags(triples);
+    thresholds
+        .iter()
+        .map(|&threshold| {
+            let recovered = sizes
+                .values()
+                .filter(|&&size| size >= threshold as usize)
+                .count();
+            (threshold, recovered)
+        })
+        .collect()
+}
+
+// Counts the triples with each tag.
+fn count_tags<'a, I: IntoIterator<Item = &'a Triple>>(triples: I) -> HashMap<&'a [u8], usize> {
+    let mut sizes: HashMap<&[u8], usize> = HashMap::new();
+    for triple in triples {
+        *sizes.entry(&triple.tag).or_default() += 1;
+    }
+    sizes
+}
+
+// Returns `P[X >= t]` for `X ~ Binomial(trials, p)`, summing the
+// probability mass function below `t` in log space to avoid underflow.
+fn binomial_at_least(trials: usize, p: f64, t: usize) -> f64 {
+    if t == 0 {
+        return 1.0;
+    } else if t > trials {
+        return 0.0;
+    } else if p >= 1.0 {
+        return 1.0;
+    }
+    let odds = (p / (1.0 - p)).ln();
+    let mut log_pmf = trials as f64 * (-p).ln_1p();
+    let mut below = 0.0;
+    for j in 0..t {
+        below += log_pmf.exp();
+        log_pmf += ((trials - j) as f64 / (j + 1) as f64).ln() + odds;
+    }
+    (1.0 - below).max(0.0)
+}
+
 // The `Ciphertext` struct holds the symmetrically encrypted data that
 // corresponds to the concatenation of `Measurement` and any optional
 // `AssociatedData`.
@@ -75,46 +214,225 @@ impl Ciphertext {
     }
 }
 
+// Errors that can occur when generating a `Triple` for a `Client`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TripleError {
+    // The associated data of the client is not the length set with
+    // `Client::set_expected_aux_len`. Missing data counts as empty.
+    AuxLengthMismatch { expected: usize, found: usize },
+    // The share doesn't parse back from its encoding, or is at `x = 0`.
+    MalformedShare,
+    // The tag is not `DIGEST_LEN` bytes long.
+    TagLengthMismatch { expected: usize, found: usize },
+    // The ciphertext is too short to hold an encrypted measurement.
+    MalformedCiphertext,
+    // The commitment is not `DIGEST_LEN` bytes long.
+    MalformedCommitment,
+    // The share commitments are not commitments to a single polynomial.
+    MalformedShareCommitments,
+    // OPRF randomness was requested, but the `star2` feature is not
+    // enabled.
+    Star2Unsupported,
+}
+
+impl fmt::Display for TripleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TripleError::AuxLengthMismatch { expected, found } => write!(
+                f,
+                "Associated data is {} bytes long, but {} bytes were expected",
+                found, expected
+            ),
+            TripleError::MalformedShare => write!(f, "Share is malformed"),
+            TripleError::TagLengthMismatch { expected, found } => write!(
+                f,
+                "Tag is {} bytes long, but {} bytes were expected",
+                found, expected
+            ),
+            TripleError::MalformedCiphertext => {
+                write!(f, "Ciphertext is too short to hold a measurement")
+            }
+            TripleError::MalformedCommitment => write!(f, "Commitment is malformed"),
+            TripleError::MalformedShareCommitments => write!(f, "Share commitments are malformed"),
+            TripleError::Star2Unsupported => {
+                write!(f, "OPRF randomness requires the star2 feature")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TripleError {}
+
 // A `Triple` is the message that a client sends to the server during
 // the STAR protocol. Consisting of a `Ciphertext`, a `Share`, and a
 // `tag`. The `Ciphertext`can only be decrypted if a `threshold` number
-// of clients possess the same measurement.
+// of clients possess the same measurement. The `epoch` the client
+// generated the triple for is also recorded, so that triples from
+// different epochs are never aggregated together.
+//
+// Clients may opt in to attaching a `commitment` to their measurement,
+// see `generate_committed`, and `share_commitments` that make their
+// share verifiable, see `generate_verifiable`.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Triple {
     pub ciphertext: Ciphertext,
     pub share: Share,
     pub tag: Vec<u8>,
+    pub epoch: String,
+    pub commitment: Option<Vec<u8>>,
+    pub share_commitments: Option<Vec<u8>>,
 }
 
 impl Triple {
-    fn new(c: Ciphertext, share: Share, tag: &[u8]) -> Self {
+    fn new(c: Ciphertext, share: Share, tag: &[u8], epoch: &str) -> Self {
         Self {
             ciphertext: c,
             share,
             tag: tag.to_vec(),
+            epoch: epoch.to_string(),
+            commitment: None,
+            share_commitments: None,
         }
     }
 
-    // Generates a triple that is used in the aggregation phase
+    // Generates a triple that is used in the aggregation phase. Without
+    // an `oprf_server`, the OPRF output set on the client is used if
+    // there is one, and local randomness otherwise. Panics if the
+    // client's associated data is not the expected length, see
+    // `try_generate`.
+    #[cfg(not(feature = "no-thread-rng"))]
     pub fn generate(client: &Client, oprf_server: Option<&PPOPRFServer>) -> Self {
+        Self::generate_rng(client, oprf_server, &mut OsRng)
+    }
+
+    // As `generate`, but samples the share point from `rng`, so that
+    // the triple is reproducible with a seeded generator.
+    pub fn generate_rng<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> Self {
+        match Self::try_generate_rng(client, oprf_server, rng) {
+            Ok(triple) => triple,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // As `generate`, but returns an error rather than sharing associated
+    // data whose length doesn't match `Client::expected_aux_len`.
+    #[cfg(not(feature = "no-thread-rng"))]
+    pub fn try_generate(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+    ) -> Result<Self, TripleError> {
+        Self::try_generate_rng(client, oprf_server, &mut OsRng)
+    }
+
+    // As `try_generate`, but samples the share point from `rng`.
+    pub fn try_generate_rng<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> Result<Self, TripleError> {
+        Self::try_generate_with_aux_flags(client, oprf_server, &[], rng)
+    }
+
+    // Generates a triple whose associated data, if there is any, is
+    // encoded as `aux_flags` say. Non-empty flags are stored after the
+    // associated data in the plaintext, for `decode_aux`.
+    fn try_generate_with_aux_flags<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        aux_flags: &[u8],
+        rng: &mut R,
+    ) -> Result<Self, TripleError> {
+        Self::check_aux_len(client)?;
         // Adding '_' in as prefix of 'oprf' because when star2 is disabled then Clippy complains.
-        let ClientSharingMaterial { key, share, tag } = if let Some(_oprf) = oprf_server {
+        let material = if let Some(_oprf) = oprf_server {
             #[cfg(not(feature = "star2"))]
-            unimplemented!();
+            return Err(TripleError::Star2Unsupported);
             #[cfg(feature = "star2")]
-            client.share_with_oprf_randomness(_oprf)
+            client.share_with_oprf_randomness_rng(_oprf, rng)
+        } else if client.has_oprf_output() {
+            client.share_with_oprf_output_rng(rng)
         } else {
-            client.share_with_local_randomness()
+            client.share_with_local_randomness_rng(rng)
         };
+        Ok(Self::from_material(client, material, aux_flags))
+    }
+
+    // Checks that the associated data of `client` is the length set with
+    // `Client::set_expected_aux_len`, if any.
+    fn check_aux_len(client: &Client) -> Result<(), TripleError> {
+        if let Some(expected) = client.expected_aux_len() {
+            let found = client.aux.as_ref().map_or(0, |aux| aux.as_slice().len());
+            if found != expected {
+                return Err(TripleError::AuxLengthMismatch { expected, found });
+            }
+        }
+        Ok(())
+    }
 
+    // Encrypts the measurement and associated data of `client` with the
+    // key of its sharing `material`, into a triple with its share and
+    // tag.
+    fn from_material(client: &Client, material: ClientSharingMaterial, aux_flags: &[u8]) -> Self {
+        let ClientSharingMaterial { key, share, tag } = material;
         let mut data: Vec<u8> = Vec::new();
         store_bytes(client.x.as_slice(), &mut data);
         if let Some(aux) = &client.aux {
-            store_bytes(aux.as_slice(), &mut data);
+            store_bytes(&encode_aux(aux.as_slice(), aux_flags), &mut data);
+            if !aux_flags.is_empty() {
+                store_bytes(aux_flags, &mut data);
+            }
         }
         let ciphertext = Ciphertext::new(&key, &data);
 
-        Triple::new(ciphertext, share, &tag)
+        Triple::new(ciphertext, share, &tag, client.epoch())
+    }
+
+    // Checks the parts of the triple that the aggregation server can
+    // see before recovery, so that a client can catch bugs before
+    // sending it: the share must parse back from its encoding, which
+    // rejects non-canonical field elements, and must not be at `x = 0`,
+    // the tag and any commitment must be `DIGEST_LEN` bytes long, the
+    // ciphertext must be long enough to hold the length of the
+    // measurement, and with the `vss` feature, any share commitments
+    // must parse as commitments to a single polynomial. Whether the
+    // share lies on the committed polynomial is checked by
+    // `verify_share`.
+    //
+    // The measurement and associated data are encrypted, so constraints
+    // on them such as `Client::set_expected_aux_len` can't be checked
+    // here. `try_generate` enforces those when the triple is generated.
+    pub fn validate(&self) -> Result<(), TripleError> {
+        let share = &self.share;
+        if Share::from_bytes(&share.to_bytes()).as_ref() != Some(share)
+            || share.x() == sharks::Fp::from(0)
+        {
+            return Err(TripleError::MalformedShare);
+        }
+        if self.tag.len() != DIGEST_LEN {
+            return Err(TripleError::TagLengthMismatch {
+                expected: DIGEST_LEN,
+                found: self.tag.len(),
+            });
+        }
+        // The length prefix of the measurement, see `try_generate`
+        if self.ciphertext.bytes.len() < 4 {
+            return Err(TripleError::MalformedCiphertext);
+        }
+        if matches!(&self.commitment, Some(c) if c.len() != DIGEST_LEN) {
+            return Err(TripleError::MalformedCommitment);
+        }
+        #[cfg(feature = "vss")]
+        if let Some(commitments) = &self.share_commitments {
+            match Commitments::try_from(commitments.as_slice()) {
+                Ok(c) if c.chunks() == 1 => {}
+                _ => return Err(TripleError::MalformedShareCommitments),
+            }
+        }
+        Ok(())
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -129,6 +447,22 @@ impl Triple {
         // tag: Vec<u8>
         store_bytes(&self.tag, &mut out);
 
+        // epoch: String
+        store_bytes(self.epoch.as_bytes(), &mut out);
+
+        // commitment: Option<Vec<u8>>, omitted if `None`, or empty if
+        // `None` and followed by share commitments
+        if let Some(commitment) = &self.commitment {
+            store_bytes(commitment, &mut out);
+        } else if self.share_commitments.is_some() {
+            store_bytes(&[], &mut out);
+        }
+
+        // share_commitments: Option<Vec<u8>>, omitted if `None`
+        if let Some(commitments) = &self.share_commitments {
+            store_bytes(commitments, &mut out);
+        }
+
         out
     }
 
@@ -147,28 +481,75 @@ impl Triple {
 
         // tag: Vec<u8>
         let tag = load_bytes(slice)?;
+        slice = &slice[4 + tag.len()..];
+
+        // epoch: String
+        let eb = load_bytes(slice)?;
+        let epoch = str::from_utf8(eb).ok()?;
+        slice = &slice[4 + eb.len()..];
+
+        // commitment: Option<Vec<u8>>
+        let mut triple = Triple::new(ciphertext, share, tag, epoch);
+        if !slice.is_empty() {
+            let cb = load_bytes(slice)?;
+            if !cb.is_empty() {
+                triple.commitment = Some(cb.to_vec());
+            }
+            slice = &slice[4 + cb.len()..];
+        }
 
-        Some(Triple {
-            ciphertext,
-            share,
-            tag: tag.to_vec(),
-        })
+        // share_commitments: Option<Vec<u8>>
+        if !slice.is_empty() {
+            triple.share_commitments = Some(load_bytes(slice)?.to_vec());
+        }
+        Some(triple)
+    }
+}
+
+// Encodes the associated data of a client as `flags` say, before it is
+// encrypted. Empty flags leave it as it is.
+fn encode_aux(aux: &[u8], flags: &[u8]) -> Vec<u8> {
+    match flags {
+        #[cfg(feature = "compress")]
+        [compress::AUX_DEFLATE] => compress::compress_aux(aux),
+        _ => aux.to_vec(),
+    }
+}
+
+// Reverses `encode_aux` after decryption. Returns `None` for unknown
+// flags, or flags for an encoding this build doesn't support, as well
+// as for data that doesn't decode.
+#[cfg_attr(not(feature = "compress"), allow(unused_variables))]
+fn decode_aux(aux: &[u8], flags: &[u8]) -> Option<Vec<u8>> {
+    match flags {
+        #[cfg(feature = "compress")]
+        [compress::AUX_DEFLATE] => compress::decompress_aux(aux),
+        _ => None,
     }
 }
 
 // An `Output` corresponds to a single client `Measurement` sent to the
 // `AggregationServer` that satisfied the `threshold` check. Such
 // structs contain the `Measurement` value itself, along with a vector
-// of all the optional `AssociatedData` values sent by clients.
+// of all the optional `AssociatedData` values sent by clients, and the
+// `threshold` that was enforced before the value was released.
+//
+// `contributors` is the number of distinct shares in the group. When
+// it equals `threshold` there was no redundancy in the recovery, so a
+// single corrupt share would have gone unnoticed.
 pub struct Output {
     pub x: Measurement,
     pub aux: Vec<Option<AssociatedData>>,
+    pub threshold: u32,
+    pub contributors: usize,
 }
 impl fmt::Debug for Output {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Output")
             .field("tag", &self.x)
             .field("aux", &self.aux)
+            .field("threshold", &self.threshold)
+            .field("contributors", &self.contributors)
             .finish()
     }
 }
@@ -176,38 +557,321 @@ impl fmt::Debug for Output {
 #[derive(Debug)]
 enum AggServerError {
     PossibleShareCollision,
+    // A ciphertext in the group didn't decrypt to a measurement.
+    MalformedCiphertext,
+    // The ciphertexts in the group decrypted to different measurements.
+    MeasurementMismatch,
 }
 
 // The `AggregationServer` is the entity that processes `Client`
 // messages and learns `Measurement` values and `AssociatedData` if the
 // `threshold` is met. These servers possess no secret data.
+//
+// Only triples generated for the server's `epoch` are aggregated.
+// Triples for any other epoch are skipped, and counted in
+// `cross_epoch_skipped`. With the `vss` feature, triples whose share
+// doesn't verify are rejected as well, see
+// `with_verifiable_shares_required`, and triples are only grouped with
+// triples carrying the same share commitments.
+//
+// Triples can either be processed all at once using
+// `retrieve_outputs`, or accumulated over time with `ingest` and
+// processed with `finalize`. Accumulated triples can be persisted with
+// `snapshot`, and loaded again using `restore`.
 pub struct AggregationServer {
     pub threshold: u32,
     pub epoch: String,
+    pending: HashMap<String, Vec<Triple>>,
+    cross_epoch_skipped: AtomicUsize,
+    max_tags: Option<usize>,
+    tag_limit_dropped: AtomicUsize,
+    unrecoverable_groups: AtomicUsize,
+    #[cfg(feature = "vss")]
+    verifiable_shares_required: bool,
+    #[cfg(feature = "vss")]
+    unverified_shares: AtomicUsize,
 }
 impl AggregationServer {
     pub fn new(threshold: u32, epoch: &str) -> Self {
         AggregationServer {
             threshold,
             epoch: epoch.to_string(),
+            pending: HashMap::new(),
+            cross_epoch_skipped: AtomicUsize::new(0),
+            max_tags: None,
+            tag_limit_dropped: AtomicUsize::new(0),
+            unrecoverable_groups: AtomicUsize::new(0),
+            #[cfg(feature = "vss")]
+            verifiable_shares_required: false,
+            #[cfg(feature = "vss")]
+            unverified_shares: AtomicUsize::new(0),
         }
     }
 
+    // Limits the number of distinct tags the server tracks at once to
+    // `limit`. Each tag is held in memory until its group is recovered,
+    // and tags are unlinkable by design, so without a limit clients
+    // sending triples with unique tags could grow the server's memory
+    // without bound. Once the limit is reached, triples with a tag that
+    // isn't already tracked are dropped and counted, while tracked groups
+    // continue to accumulate. The limit applies to the state built by
+    // `ingest` and to each call of `retrieve_outputs` and `write_ndjson`.
+    // It is not included in snapshots.
+    //
+    // Note that tags are tracked in the order triples arrive, so an
+    // attacker who floods the server early can still keep out later
+    // legitimate tags: the limit bounds memory, not the damage done.
+    pub fn with_max_tags(mut self, limit: usize) -> Self {
+        self.max_tags = Some(limit);
+        self
+    }
+
+    // The number of triples that have been dropped because their tag
+    // wasn't tracked and the limit set by `with_max_tags` was reached.
+    pub fn tag_limit_dropped(&self) -> usize {
+        self.tag_limit_dropped.load(Ordering::Relaxed)
+    }
+
+    // The number of groups which met the `threshold`, but whose key
+    // couldn't be recovered, and so were skipped. Unless verifiable
+    // shares are required, a client can send a triple with the tag of a
+    // popular measurement and a bogus share, and spoil the recovery of
+    // that group, and even verified shares don't cover the ciphertext.
+    // Skipping the group keeps the other groups recoverable.
+    pub fn unrecoverable_groups(&self) -> usize {
+        self.unrecoverable_groups.load(Ordering::Relaxed)
+    }
+
+    // Accumulates `triples` into the server state, grouped by tag.
+    pub fn ingest(&mut self, triples: &[Triple]) {
+        let triples = self.same_epoch(triples);
+        let dropped = Self::group_triples(&mut self.pending, &triples, self.max_tags);
+        self.tag_limit_dropped.fetch_add(dropped, Ordering::Relaxed);
+    }
+
+    // The number of triples for other epochs that have been skipped by
+    // `ingest`, `retrieve_outputs` and `write_ndjson`.
+    pub fn cross_epoch_skipped(&self) -> usize {
+        self.cross_epoch_skipped.load(Ordering::Relaxed)
+    }
+
+    // Returns the `triples` for the server's epoch, counting the others
+    // as skipped. With the `vss` feature, triples whose share doesn't
+    // verify are rejected too.
+    fn same_epoch(&self, triples: &[Triple]) -> Vec<Triple> {
+        let (same, other): (Vec<&Triple>, Vec<&Triple>) =
+            triples.iter().partition(|t| t.epoch == self.epoch);
+        self.cross_epoch_skipped
+            .fetch_add(other.len(), Ordering::Relaxed);
+        #[cfg(feature = "vss")]
+        let same = self.verified(same);
+        same.into_iter().cloned().collect()
+    }
+
+    // Recovers outputs for all accumulated groups that meet the
+    // `threshold`, in the same order as `retrieve_outputs`.
+    pub fn finalize(&self) -> Vec<Output> {
+        let filtered: Vec<Vec<Triple>> = self
+            .pending
+            .values()
+            .filter(|bucket| bucket.len() >= (self.threshold as usize))
+            .cloned()
+            .collect();
+        self.recover_outputs(filtered)
+    }
+
+    // Serializes the accumulated state of the server. Only the
+    // threshold, epoch and received triples are stored, nothing is
+    // recovered, so groups below the threshold remain unrecoverable.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+
+        // threshold: u32
+        out.extend(self.threshold.to_le_bytes());
+
+        // epoch: String
+        store_bytes(self.epoch.as_bytes(), &mut out);
+
+        // pending: Vec<Triple>
+        for triple in self.pending.values().flatten() {
+            store_bytes(&triple.to_bytes(), &mut out);
+        }
+
+        out
+    }
+
+    pub fn restore(bytes: &[u8]) -> Option<AggregationServer> {
+        if bytes.len() < 4 {
+            return None;
+        }
+
+        // threshold: u32
+        let mut threshold_bytes = [0u8; 4];
+        threshold_bytes.copy_from_slice(&bytes[..4]);
+        let threshold = u32::from_le_bytes(threshold_bytes);
+        let mut slice = &bytes[4..];
+
+        // epoch: String
+        let eb = load_bytes(slice)?;
+        let epoch = str::from_utf8(eb).ok()?;
+        slice = &slice[4 + eb.len()..];
+
+        // pending: Vec<Triple>
+        let mut triples = Vec::new();
+        while !slice.is_empty() {
+            let tb = load_bytes(slice)?;
+            triples.push(Triple::from_bytes(tb)?);
+            slice = &slice[4 + tb.len()..];
+        }
+
+        let mut server = AggregationServer::new(threshold, epoch);
+        server.ingest(&triples);
+        Some(server)
+    }
+
+    // Recovers outputs for all groups of `all_triples` that meet the
+    // `threshold`. Outputs are sorted by their measurement bytes, so
+    // that the result does not depend on the order in which groups are
+    // processed.
     pub fn retrieve_outputs(&self, all_triples: &[Triple]) -> Vec<Output> {
         let filtered = self.filter_triples(all_triples);
-        filtered
+        self.recover_outputs(filtered)
+    }
+
+    // Recovers outputs as in `retrieve_outputs`, along with the distinct
+    // share x coordinates of the triples that contributed to each one,
+    // so that an audit can look for Sybil patterns such as one sender
+    // making up most of a group.
+    //
+    // This is for controlled audits only. Each client samples a new x
+    // coordinate for every triple, so the coordinates don't link
+    // clients across groups by themselves, but they do identify which
+    // received triples made up each recovered value. Combined with any
+    // transport metadata kept for those triples, that attributes the
+    // value to its senders, undoing the anonymity the `threshold`
+    // otherwise gives within a group.
+    pub fn retrieve_outputs_audited(
+        &self,
+        all_triples: &[Triple],
+    ) -> Vec<(Output, Vec<sharks::Fp>)> {
+        let mut outputs: Vec<(Output, Vec<sharks::Fp>)> = self
+            .filter_triples(all_triples)
             .into_par_iter()
-            .map(|triples| self.recover_measurements(&triples))
-            .map(|output| output.unwrap())
-            .collect()
+            .map_init(InterpolationCtx::new, |ctx, triples| {
+                let output = self.recover_group(ctx, &triples)?;
+                let mut seen: HashSet<Vec<u8>> = HashSet::new();
+                let contributors = triples
+                    .iter()
+                    .map(|t| t.share.x())
+                    .filter(|&x| seen.insert(Vec::from(x)))
+                    .collect();
+                Some((output, contributors))
+            })
+            .flatten()
+            .collect();
+        outputs.sort_by(|a, b| a.0.x.as_slice().cmp(b.0.x.as_slice()));
+        outputs
     }
 
-    fn recover_measurements(&self, triples: &[Triple]) -> Result<Output, AggServerError> {
-        let mut enc_key_buf = vec![0u8; 16];
-        let res = self.key_recover(triples, &mut enc_key_buf);
-        if let Err(e) = res {
-            return Err(e);
+    // Counts the triples for this server's `epoch` in each group of
+    // `triples`, as pairs of the tag and the group size, sorted by
+    // decreasing size and then by tag. Groups of every size are
+    // included. Nothing is recovered or decrypted, so this exposes no
+    // measurement, and the distribution of group sizes can be inspected
+    // to tune the `threshold` before committing to recovery. Like
+    // `sweep_thresholds`, it counts triples rather than distinct shares.
+    pub fn group_sizes(&self, triples: &[Triple]) -> Vec<(Vec<u8>, usize)> {
+        let same_epoch = triples.iter().filter(|t| t.epoch == self.epoch);
+        let mut sizes: Vec<(Vec<u8>, usize)> = count_tags(same_epoch)
+            .into_iter()
+            .map(|(tag, size)| (tag.to_vec(), size))
+            .collect();
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sizes
+    }
+
+    // Recovers the measurements of `triples` that were sent at least
+    // `min_count` times, along with their counts, sorted by decreasing
+    // count and then by measurement. Unlike the `threshold`, which
+    // protects clients, `min_count` only serves to drop the long tail of
+    // rarer values, which are then never decrypted. Groups below the
+    // `threshold` are never returned, whatever the `min_count`.
+    pub fn heavy_hitters(&self, triples: &[Triple], min_count: usize) -> Vec<(Vec<u8>, usize)> {
+        let groups = self
+            .filter_triples(triples)
+            .into_iter()
+            .filter(|group| group.len() >= min_count)
+            .collect();
+        let mut hitters: Vec<(Vec<u8>, usize)> = self
+            .recover_outputs(groups)
+            .into_iter()
+            .map(|output| (output.x.as_vec(), output.aux.len()))
+            .collect();
+        hitters.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hitters
+    }
+
+    // Writes a JSON object for each group of `triples` that meets the
+    // `threshold` to `w`, one per line (NDJSON), as soon as the group is
+    // recovered, so that large histograms need not be held in memory.
+    // Each object holds the measurement `value` (hex encoded), the
+    // `count` of triples received for it, and the `threshold`. Groups
+    // are written in no particular order.
+    pub fn write_ndjson<W: Write>(&self, triples: &[Triple], mut w: W) -> io::Result<()> {
+        let mut ctx = InterpolationCtx::new();
+        for group in self.filter_triples(triples) {
+            let output = match self.recover_group(&mut ctx, &group) {
+                Some(output) => output,
+                None => continue,
+            };
+            let value: String = output
+                .x
+                .as_slice()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            writeln!(
+                w,
+                "{{\"value\":\"{}\",\"count\":{},\"threshold\":{}}}",
+                value,
+                output.aux.len(),
+                output.threshold
+            )?;
         }
+        Ok(())
+    }
+
+    // Recovers the outputs of `groups` in parallel. Each worker thread
+    // reuses one `InterpolationCtx` across the groups it recovers.
+    fn recover_outputs(&self, groups: Vec<Vec<Triple>>) -> Vec<Output> {
+        let mut outputs: Vec<Output> = groups
+            .into_par_iter()
+            .map_init(InterpolationCtx::new, |ctx, triples| {
+                self.recover_group(ctx, &triples)
+            })
+            .flatten()
+            .collect();
+        outputs.sort_by(|a, b| a.x.as_slice().cmp(b.x.as_slice()));
+        outputs
+    }
+
+    // Recovers the output of a group of triples, counting it as
+    // unrecoverable on failure.
+    fn recover_group(&self, ctx: &mut InterpolationCtx, triples: &[Triple]) -> Option<Output> {
+        let output = self.recover_measurements(ctx, triples).ok();
+        if output.is_none() {
+            self.unrecoverable_groups.fetch_add(1, Ordering::Relaxed);
+        }
+        output
+    }
+
+    fn recover_measurements(
+        &self,
+        ctx: &mut InterpolationCtx,
+        triples: &[Triple],
+    ) -> Result<Output, AggServerError> {
+        let mut enc_key_buf = vec![0u8; 16];
+        self.key_recover(ctx, triples, &mut enc_key_buf)?;
 
         let ciphertexts = triples.iter().map(|t| t.ciphertext.clone());
         let plaintexts = ciphertexts.map(|c| c.decrypt(&enc_key_buf));
@@ -216,35 +880,50 @@ impl AggregationServer {
             .map(|p| {
                 let mut slice = &p[..];
 
-                let measurement_bytes = load_bytes(slice).unwrap();
+                let measurement_bytes =
+                    load_bytes(slice).ok_or(AggServerError::MalformedCiphertext)?;
                 slice = &slice[4 + measurement_bytes.len() as usize..];
                 if !slice.is_empty() {
-                    let aux_bytes = load_bytes(slice).unwrap();
-                    if !aux_bytes.is_empty() {
-                        return (
-                            measurement_bytes.to_vec(),
-                            Some(AssociatedData::new(aux_bytes)),
-                        );
+                    let aux_bytes = load_bytes(slice).ok_or(AggServerError::MalformedCiphertext)?;
+                    slice = &slice[4 + aux_bytes.len()..];
+                    let aux = if slice.is_empty() {
+                        aux_bytes.to_vec()
+                    } else {
+                        let flags = load_bytes(slice).ok_or(AggServerError::MalformedCiphertext)?;
+                        decode_aux(aux_bytes, flags).ok_or(AggServerError::MalformedCiphertext)?
+                    };
+                    if !aux.is_empty() {
+                        return Ok((measurement_bytes.to_vec(), Some(AssociatedData::new(&aux))));
                     }
                 }
-                (measurement_bytes.to_vec(), None)
+                Ok((measurement_bytes.to_vec(), None))
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
         let tag = &splits[0].0;
-        for new_tag in splits.iter().skip(1) {
-            if &new_tag.0 != tag {
-                panic!("tag mismatch ({:?} != {:?})", tag, new_tag.0);
-            }
+        if splits.iter().skip(1).any(|new_tag| &new_tag.0 != tag) {
+            return Err(AggServerError::MeasurementMismatch);
         }
+        let contributors = triples
+            .iter()
+            .map(|t| t.share.to_bytes())
+            .collect::<HashSet<_>>()
+            .len();
         Ok(Output {
             x: Measurement::new(tag),
             aux: splits.into_iter().map(|val| val.1).collect(),
+            threshold: self.threshold,
+            contributors,
         })
     }
 
-    fn key_recover(&self, triples: &[Triple], enc_key: &mut [u8]) -> Result<(), AggServerError> {
+    fn key_recover(
+        &self,
+        ctx: &mut InterpolationCtx,
+        triples: &[Triple],
+        enc_key: &mut [u8],
+    ) -> Result<(), AggServerError> {
         let shares: Vec<Share> = triples.iter().map(|triple| triple.share.clone()).collect();
-        let res = share_recover(&shares);
+        let res = share_recover_with_ctx(ctx, &shares);
         if res.is_err() {
             return Err(AggServerError::PossibleShareCollision);
         }
@@ -254,7 +933,7 @@ impl AggregationServer {
     }
 
     fn filter_triples(&self, triples: &[Triple]) -> Vec<Vec<Triple>> {
-        let collected = self.collect_triples(triples);
+        let collected = self.collect_triples(&self.same_epoch(triples));
         collected
             .into_iter()
             .filter(|bucket| bucket.len() >= (self.threshold as usize))
@@ -263,9 +942,27 @@ impl AggregationServer {
 
     fn collect_triples(&self, triples: &[Triple]) -> Vec<Vec<Triple>> {
         let mut collected_triples: HashMap<String, Vec<Triple>> = HashMap::new();
+        let dropped = Self::group_triples(&mut collected_triples, triples, self.max_tags);
+        self.tag_limit_dropped.fetch_add(dropped, Ordering::Relaxed);
+        collected_triples.values().cloned().collect()
+    }
+
+    // Groups `triples` by tag and share commitments into `collected_triples`,
+    // tracking at most `max_tags` groups. Returns the number of triples
+    // dropped because of the limit.
+    fn group_triples(
+        collected_triples: &mut HashMap<String, Vec<Triple>>,
+        triples: &[Triple],
+        max_tags: Option<usize>,
+    ) -> usize {
+        let mut dropped = 0;
         for triple in triples {
-            let s = format!("{:x?}", triple.tag);
+            let s = format!("{:x?}{:x?}", triple.tag, triple.share_commitments);
+            let len = collected_triples.len();
             match collected_triples.entry(s) {
+                Entry::Vacant(_) if matches!(max_tags, Some(max) if len >= max) => {
+                    dropped += 1;
+                }
                 Entry::Vacant(e) => {
                     e.insert(vec![triple.clone()]);
                 }
@@ -274,6 +971,25 @@ impl AggregationServer {
                 }
             }
         }
-        collected_triples.values().cloned().collect()
+        dropped
     }
 }
+
+// Checks that the methods which implicitly use `OsRng` are absent with
+// the `no-thread-rng` feature.
+///
+/// ```compile_fail
+/// let client = sta_rs::Client::new(b"foo", 2, "t", None);
+/// sta_rs_test_utils::Triple::generate(&client, None);
+/// ```
+///
+/// ```compile_fail
+/// let client = sta_rs::Client::new(b"foo", 2, "t", None);
+/// sta_rs_test_utils::Triple::try_generate(&client, None);
+/// ```
+///
+/// ```compile_fail
+/// sta_rs_test_utils::round_trip(b"secret", 2, 3);
+/// ```
+#[cfg(feature = "no-thread-rng")]
+mod no_thread_rng {}
@@ -0,0 +1,151 @@
+// Aggregation of triples from several epochs by a single server.
+//
+// A `MultiEpochAggregationServer` holds an `AggregationServer` for
+// each active epoch. Triples are bucketed by their embedded epoch
+// first, and then by tag within each epoch by that epoch's server, so
+// the `threshold` applies to each (epoch, tag) pair: triples for the
+// same measurement in different epochs never count towards each other,
+// and are encrypted under different keys anyway.
+//
+// Memory scales with the number of active epochs: each one keeps its
+// own table of tags, and every triple ingested for it, until the epoch
+// is closed with `close_epoch`. The `with_max_tags` limit applies to
+// each epoch separately, so the bound on the number of tracked tags is
+// the limit times the number of active epochs. Only epochs that have
+// been opened are accepted, so that clients can't make the server
+// track arbitrary epochs; triples for any other epoch are skipped.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{AggregationServer, Output, Triple};
+
+pub struct MultiEpochAggregationServer {
+    pub threshold: u32,
+    epochs: BTreeMap<String, AggregationServer>,
+    max_tags: Option<usize>,
+    inactive_epoch_skipped: AtomicUsize,
+}
+
+impl MultiEpochAggregationServer {
+    pub fn new(threshold: u32, epochs: &[&str]) -> Self {
+        let mut server = MultiEpochAggregationServer {
+            threshold,
+            epochs: BTreeMap::new(),
+            max_tags: None,
+            inactive_epoch_skipped: AtomicUsize::new(0),
+        };
+        for epoch in epochs {
+            server.open_epoch(epoch);
+        }
+        server
+    }
+
+    // Limits the number of distinct tags tracked in each epoch, as
+    // `AggregationServer::with_max_tags`.
+    pub fn with_max_tags(mut self, limit: usize) -> Self {
+        self.max_tags = Some(limit);
+        self.epochs = std::mem::take(&mut self.epochs)
+            .into_iter()
+            .map(|(epoch, server)| (epoch, server.with_max_tags(limit)))
+            .collect();
+        self
+    }
+
+    // Starts accepting triples for `epoch`. Does nothing if it is
+    // already active.
+    pub fn open_epoch(&mut self, epoch: &str) {
+        let (threshold, max_tags) = (self.threshold, self.max_tags);
+        self.epochs.entry(epoch.to_string()).or_insert_with(|| {
+            let server = AggregationServer::new(threshold, epoch);
+            match max_tags {
+                Some(limit) => server.with_max_tags(limit),
+                None => server,
+            }
+        });
+    }
+
+    // Stops accepting triples for `epoch`, and releases its state,
+    // returning the outputs recovered from the triples ingested for it.
+    // Returns `None` if the epoch isn't active.
+    pub fn close_epoch(&mut self, epoch: &str) -> Option<Vec<Output>> {
+        self.epochs.remove(epoch).map(|server| server.finalize())
+    }
+
+    // The active epochs, in sorted order.
+    pub fn active_epochs(&self) -> Vec<String> {
+        self.epochs.keys().cloned().collect()
+    }
+
+    // Accumulates `triples` into the state of their epochs.
+    pub fn ingest(&mut self, triples: &[Triple]) {
+        for (epoch, triples) in self.by_epoch(triples) {
+            self.epochs.get_mut(&epoch).unwrap().ingest(&triples);
+        }
+    }
+
+    // Recovers outputs for all accumulated groups that meet the
+    // `threshold`, keyed by epoch. Outputs are sorted by epoch, and then
+    // by measurement within each epoch.
+    pub fn finalize(&self) -> Vec<(String, Output)> {
+        self.epochs
+            .iter()
+            .flat_map(|(epoch, server)| {
+                server
+                    .finalize()
+                    .into_iter()
+                    .map(move |output| (epoch.clone(), output))
+            })
+            .collect()
+    }
+
+    // Recovers outputs for all groups of `triples` that meet the
+    // `threshold` in each active epoch, in the same order as
+    // `finalize`, without accumulating them.
+    pub fn retrieve_outputs(&self, triples: &[Triple]) -> Vec<(String, Output)> {
+        self.by_epoch(triples)
+            .into_iter()
+            .flat_map(|(epoch, triples)| {
+                self.epochs[&epoch]
+                    .retrieve_outputs(&triples)
+                    .into_iter()
+                    .map(move |output| (epoch.clone(), output))
+            })
+            .collect()
+    }
+
+    // The number of triples for epochs that aren't active that have
+    // been skipped.
+    pub fn inactive_epoch_skipped(&self) -> usize {
+        self.inactive_epoch_skipped.load(Ordering::Relaxed)
+    }
+
+    // The number of triples dropped by the `with_max_tags` limit, across
+    // the active epochs.
+    pub fn tag_limit_dropped(&self) -> usize {
+        self.epochs.values().map(|s| s.tag_limit_dropped()).sum()
+    }
+
+    // The number of groups that met the `threshold` but couldn't be
+    // recovered, across the active epochs.
+    pub fn unrecoverable_groups(&self) -> usize {
+        self.epochs.values().map(|s| s.unrecoverable_groups()).sum()
+    }
+
+    // Buckets `triples` by epoch, in sorted epoch order, counting those
+    // for inactive epochs as skipped.
+    fn by_epoch(&self, triples: &[Triple]) -> BTreeMap<String, Vec<Triple>> {
+        let mut buckets: BTreeMap<String, Vec<Triple>> = BTreeMap::new();
+        for triple in triples {
+            if self.epochs.contains_key(&triple.epoch) {
+                buckets
+                    .entry(triple.epoch.clone())
+                    .or_default()
+                    .push(triple.clone());
+            } else {
+                self.inactive_epoch_skipped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        buckets
+    }
+}
@@ -0,0 +1,69 @@
+// Asynchronous aggregation of triples streamed in over a channel.
+//
+// An `AsyncAggregator` owns an `AggregationServer` and the receiving end
+// of a `futures` mpsc channel of triples. Polling it ingests every
+// triple that is ready, so aggregation proceeds as triples arrive
+// without blocking the executor, and it works under any executor,
+// including Tokio.
+//
+// It is cancellation safe: each triple is ingested in the same poll in
+// which it is received, so dropping a `run` future part way through an
+// epoch loses nothing. The state stays in the `AsyncAggregator`, and
+// calling `run` again resumes from the next triple on the channel.
+// Groups below the threshold are never recovered by ingesting, so
+// cancelling never reveals anything either; outputs are only recovered
+// by an explicit `finalize`.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::Receiver;
+use futures::future;
+use futures::stream::Stream;
+
+use crate::{AggregationServer, Output, Triple};
+
+pub struct AsyncAggregator {
+    server: AggregationServer,
+    triples: Receiver<Triple>,
+}
+
+impl AsyncAggregator {
+    pub fn new(server: AggregationServer, triples: Receiver<Triple>) -> Self {
+        AsyncAggregator { server, triples }
+    }
+
+    // Ingests all of the triples that are ready on the channel. Returns
+    // `Poll::Ready` once the channel is closed and drained, or
+    // `Poll::Pending` when it is waiting for more triples.
+    pub fn poll_ingest(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            match Pin::new(&mut self.triples).poll_next(cx) {
+                Poll::Ready(Some(triple)) => self.server.ingest(&[triple]),
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    // Ingests triples until the channel is closed. Can be cancelled and
+    // called again at any point.
+    pub async fn run(&mut self) {
+        future::poll_fn(|cx| self.poll_ingest(cx)).await
+    }
+
+    // The server holding the state ingested so far.
+    pub fn server(&self) -> &AggregationServer {
+        &self.server
+    }
+
+    // Recovers the outputs for the groups ingested so far that meet the
+    // threshold, as `AggregationServer::finalize`.
+    pub fn finalize(&self) -> Vec<Output> {
+        self.server.finalize()
+    }
+
+    pub fn into_server(self) -> AggregationServer {
+        self.server
+    }
+}
@@ -0,0 +1,124 @@
+// Verifiable shares, so that the `AggregationServer` can reject triples
+// whose share is off the polynomial of their group before grouping.
+// Only available with the `vss` feature.
+//
+// The share of a client is a point on a polynomial whose coefficients
+// are derived from the client's randomness, and so from its
+// measurement. `Triple::generate_verifiable` attaches Feldman
+// commitments to that polynomial, one compressed BLS12-381 G1 point
+// `a * G` per coefficient `a`, as the `share_commitments`. The server
+// checks with `Triple::verify_share` that the share lies on the
+// committed polynomial, by evaluating it "in the exponent" at the
+// share's `x` coordinate, and only groups triples which carry the same
+// commitments. A group of verified triples therefore only contains
+// shares of one polynomial, so a single bogus share sent with the tag
+// of a popular measurement no longer spoils the recovery of its group.
+//
+// This is share verifiability, not a proof that the triple was derived
+// from a single measurement. The commitments cover neither the tag nor
+// the ciphertext: a bogus tag only puts the triple in a group of its
+// own, but a client that knows the measurement can still send a bad
+// ciphertext, and the group is then skipped and counted in
+// `AggregationServer::unrecoverable_groups`. Binding all three to the
+// measurement would take a general purpose zero-knowledge proof of the
+// STROBE derivations.
+//
+// Nor are the commitments zero-knowledge. They are deterministic, like
+// the tag, so clients with the same measurement send the same
+// commitments, and a server that guesses the measurement can check its
+// guess against them, as it can against the tag.
+//
+// The commitments are `4 + 48 * threshold` bytes long. Generating them
+// costs the client `threshold` G1 scalar multiplications, one per
+// coefficient, and verifying a share costs the server as many again
+// for each triple.
+
+use std::convert::TryFrom;
+use std::sync::atomic::Ordering;
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{AggregationServer, PPOPRFServer, Triple, TripleError};
+use sta_rs::{Client, Commitments};
+
+impl Triple {
+    // As `generate_rng`, but attaches commitments that make the share
+    // verifiable. Panics if the associated data is not the expected
+    // length.
+    pub fn generate_verifiable<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> Self {
+        match Self::try_generate_verifiable(client, oprf_server, rng) {
+            Ok(triple) => triple,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // As `try_generate_rng`, but attaches commitments that make the
+    // share verifiable.
+    pub fn try_generate_verifiable<R: RngCore + CryptoRng>(
+        client: &Client,
+        oprf_server: Option<&PPOPRFServer>,
+        rng: &mut R,
+    ) -> Result<Self, TripleError> {
+        Self::check_aux_len(client)?;
+        // Adding '_' in as prefix of 'oprf' because when star2 is disabled then Clippy complains.
+        let (material, commitments) = if let Some(_oprf) = oprf_server {
+            #[cfg(not(feature = "star2"))]
+            return Err(TripleError::Star2Unsupported);
+            #[cfg(feature = "star2")]
+            client.share_with_oprf_randomness_verifiable_rng(_oprf, rng)
+        } else if client.has_oprf_output() {
+            client.share_with_oprf_output_verifiable_rng(rng)
+        } else {
+            client.share_with_local_randomness_verifiable_rng(rng)
+        };
+        let mut triple = Self::from_material(client, material, &[]);
+        triple.share_commitments = Some(Vec::from(&commitments));
+        Ok(triple)
+    }
+
+    // Checks that the share of the triple lies on the polynomial of its
+    // `share_commitments`. Returns `false` for triples without
+    // commitments.
+    pub fn verify_share(&self) -> bool {
+        match self.share_commitments.as_deref().map(Commitments::try_from) {
+            Some(Ok(commitments)) => commitments.chunks() == 1 && self.share.verify(&commitments),
+            _ => false,
+        }
+    }
+}
+
+impl AggregationServer {
+    // Rejects triples without share commitments, as well as those whose
+    // share doesn't verify. By default, only triples that carry
+    // commitments are checked. It is not included in snapshots.
+    pub fn with_verifiable_shares_required(mut self) -> Self {
+        self.verifiable_shares_required = true;
+        self
+    }
+
+    // The number of triples that have been rejected because their share
+    // didn't verify, or had no commitments when they are required.
+    pub fn unverified_shares(&self) -> usize {
+        self.unverified_shares.load(Ordering::Relaxed)
+    }
+
+    // Returns the `triples` whose share verifies, along with those
+    // without commitments unless they are required, counting the
+    // others as unverified.
+    pub(crate) fn verified<'a>(&self, triples: Vec<&'a Triple>) -> Vec<&'a Triple> {
+        let (valid, invalid): (Vec<&Triple>, Vec<&Triple>) =
+            triples
+                .into_iter()
+                .partition(|t| match t.share_commitments {
+                    Some(_) => t.verify_share(),
+                    None => !self.verifiable_shares_required,
+                });
+        self.unverified_shares
+            .fetch_add(invalid.len(), Ordering::Relaxed);
+        valid
+    }
+}
@@ -1,3 +1,9 @@
+// The tests generate triples with the methods which implicitly use
+// `OsRng`, and which the `no-thread-rng` feature removes.
+#![cfg(not(feature = "no-thread-rng"))]
+
+use rand_core::OsRng;
+
 use sta_rs::*;
 use sta_rs_test_utils::*;
 
@@ -17,6 +23,267 @@ fn serialize_triple() {
     assert_eq!(Triple::from_bytes(&bytes), Some(triple));
 }
 
+#[test]
+fn triple_aux_length_checked() {
+    let mut client = Client::new(b"foobar", 2, "epoch", Some(vec![1; 15]));
+    client.set_expected_aux_len(16);
+    assert_eq!(
+        Triple::try_generate(&client, None),
+        Err(TripleError::AuxLengthMismatch {
+            expected: 16,
+            found: 15
+        })
+    );
+
+    let mut client = Client::new(b"foobar", 2, "epoch", None);
+    client.set_expected_aux_len(16);
+    assert!(Triple::try_generate(&client, None).is_err());
+
+    let mut client = Client::new(b"foobar", 2, "epoch", Some(vec![1; 16]));
+    client.set_expected_aux_len(16);
+    let triple = Triple::try_generate(&client, None).unwrap();
+    let agg_server = AggregationServer::new(2, "epoch");
+    let outputs = agg_server.retrieve_outputs(&[triple, Triple::generate(&client, None)]);
+    assert_eq!(outputs[0].aux[0].as_ref().unwrap().as_slice(), &[1; 16]);
+}
+
+#[test]
+#[should_panic(expected = "Associated data is 3 bytes long, but 4 bytes were expected")]
+fn triple_generate_panics_on_aux_length() {
+    let mut client = Client::new(b"foobar", 2, "epoch", Some(vec![1; 3]));
+    client.set_expected_aux_len(4);
+    Triple::generate(&client, None);
+}
+
+#[test]
+fn triple_generate_rng_reproducible() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let client = Client::new(b"foobar", 2, "epoch", None);
+    let first = Triple::generate_rng(&client, None, &mut StdRng::seed_from_u64(7));
+    let again = Triple::generate_rng(&client, None, &mut StdRng::seed_from_u64(7));
+    assert_eq!(first.share, again.share);
+    let other = Triple::generate_rng(&client, None, &mut StdRng::seed_from_u64(8));
+    assert_ne!(first.share, other.share);
+
+    let agg_server = AggregationServer::new(2, "epoch");
+    let outputs = agg_server.retrieve_outputs(&[first, other]);
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].x.as_slice(), b"foobar");
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn compressed_aux_roundtrip() {
+    let aux = |i: usize| {
+        format!(
+            "{{\"os\": \"linux\", \"channel\": \"release\", \"events\": [{}]}}",
+            vec!["{\"kind\": \"page_load\", \"count\": 1}"; 20 + i].join(", ")
+        )
+        .into_bytes()
+    };
+    let clients: Vec<Client> = (0..3)
+        .map(|i| Client::new(b"foobar", 3, "epoch", Some(aux(i))))
+        .collect();
+    let compressed = Triple::generate_compressed(&clients[0], None, &mut OsRng);
+    let plain = Triple::generate(&clients[0], None);
+    assert!(compressed.ciphertext.to_bytes().len() < plain.ciphertext.to_bytes().len() / 4);
+    assert_eq!(compressed.tag, plain.tag);
+
+    // Compressed and uncompressed triples are aggregated together, and
+    // the server returns the original data
+    let triples = vec![
+        compressed,
+        Triple::generate_compressed(&clients[1], None, &mut OsRng),
+        Triple::generate(&clients[2], None),
+    ];
+    let outputs = AggregationServer::new(3, "epoch").retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 1);
+    let mut recovered: Vec<Vec<u8>> = outputs[0]
+        .aux
+        .iter()
+        .map(|a| a.as_ref().unwrap().as_vec())
+        .collect();
+    recovered.sort();
+    let mut expected: Vec<Vec<u8>> = (0..3).map(aux).collect();
+    expected.sort();
+    assert_eq!(recovered, expected);
+
+    // Empty or missing data stays missing
+    let triples: Vec<Triple> = [Some(vec![]), None]
+        .iter()
+        .map(|aux| Client::new(b"foobar", 2, "epoch", aux.clone()))
+        .map(|c| Triple::generate_compressed(&c, None, &mut OsRng))
+        .collect();
+    let outputs = AggregationServer::new(2, "epoch").retrieve_outputs(&triples);
+    assert!(outputs[0].aux.iter().all(|a| a.is_none()));
+}
+
+#[cfg(not(feature = "star2"))]
+#[test]
+fn triple_oprf_requires_star2() {
+    let client = Client::new(b"foobar", 2, "epoch", None);
+    assert_eq!(
+        Triple::try_generate_rng(&client, Some(&PPOPRFServer), &mut OsRng),
+        Err(TripleError::Star2Unsupported)
+    );
+    #[cfg(feature = "vss")]
+    assert_eq!(
+        Triple::try_generate_verifiable(&client, Some(&PPOPRFServer), &mut OsRng),
+        Err(TripleError::Star2Unsupported)
+    );
+}
+
+#[test]
+fn triple_validate() {
+    let client = Client::new(b"foobar", 2, "epoch", Some(vec![1; 16]));
+    let triple = Triple::generate(&client, None);
+    assert_eq!(triple.validate(), Ok(()));
+    let client = Client::new(b"", 2, "epoch", None);
+    assert_eq!(Triple::generate(&client, None).validate(), Ok(()));
+
+    let mut short_tag = triple.clone();
+    short_tag.tag.pop();
+    assert_eq!(
+        short_tag.validate(),
+        Err(TripleError::TagLengthMismatch {
+            expected: 32,
+            found: 31
+        })
+    );
+
+    let mut empty = triple.clone();
+    empty.ciphertext = Ciphertext::from_bytes(&[0; 3]);
+    assert_eq!(empty.validate(), Err(TripleError::MalformedCiphertext));
+
+    // Zero the x coordinate, after the threshold, the length of the
+    // serialized share and its header
+    let mut bytes = triple.share.to_bytes();
+    bytes[16..48].copy_from_slice(&[0; 32]);
+    let mut at_zero = triple;
+    at_zero.share = Share::from_bytes(&bytes).unwrap();
+    assert_eq!(at_zero.validate(), Err(TripleError::MalformedShare));
+}
+
+#[test]
+fn commitment_open_after_recovery() {
+    let threshold = 3;
+    let client = Client::new(b"foobar", threshold, "t", None);
+    let (committed, opening) = Triple::generate_committed(&client, None, &mut OsRng);
+    assert_eq!(committed.validate(), Ok(()));
+    assert_eq!(
+        Triple::from_bytes(&committed.to_bytes()),
+        Some(committed.clone())
+    );
+    let opening = CommitmentOpening::from_bytes(&opening.to_bytes()).unwrap();
+
+    // The server keeps the commitment, but can't recover the group yet
+    let mut server = AggregationServer::new(threshold, "t");
+    server.ingest(&[committed.clone(), Triple::generate(&client, None)]);
+    let server = AggregationServer::restore(&server.snapshot()).unwrap();
+    assert!(server.finalize().is_empty());
+
+    let mut triples = vec![committed.clone(), Triple::generate(&client, None)];
+    triples.push(Triple::generate(&client, None));
+    let outputs = server.retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 1);
+    assert!(committed.verify_commitment(&outputs[0].x, &opening));
+
+    let (_, other) = Triple::generate_committed(&client, None, &mut OsRng);
+    assert!(!committed.verify_commitment(&outputs[0].x, &other));
+    assert!(!committed.verify_commitment(&Measurement::new(b"foobaz"), &opening));
+    assert!(!triples[1].verify_commitment(&outputs[0].x, &opening));
+
+    let mut short = committed;
+    short.commitment.as_mut().unwrap().pop();
+    assert_eq!(short.validate(), Err(TripleError::MalformedCommitment));
+}
+
+#[cfg(feature = "vss")]
+#[test]
+fn vss_rejects_tampered_share() {
+    let sharks = sharks::Sharks(3);
+    let secret = sharks::Secret::from_bytes(b"some secret");
+    let (shares, commitments) = sharks.verifiable_dealer_rng(&secret, 5, &mut OsRng);
+    assert!(shares.iter().all(|s| s.verify(&commitments)));
+
+    // A cheating dealer hands one holder an off-polynomial share
+    let tampered = tamper(&shares[2]);
+    assert_ne!(tampered, shares[2]);
+    assert!(!tampered.verify(&commitments));
+    let mut dealt = shares[..3].to_vec();
+    dealt[2] = tampered;
+    assert_eq!(
+        sharks.recover_verified(&dealt, &commitments),
+        Err(sharks::RecoverError::CommitmentMismatch)
+    );
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_roundtrip() {
+    let threshold = 2;
+    let triples: Vec<Triple> = (0..3)
+        .map(|i| Client::new(b"foobar", threshold, "t", Some(vec![i as u8])))
+        .map(|c| Triple::generate(&c, None))
+        .collect();
+    let bytes = triples[0].to_cbor();
+    assert_eq!(Triple::from_cbor(&bytes), Some(triples[0].clone()));
+    let client = Client::new(b"foobar", threshold, "t", None);
+    let (committed, _) = Triple::generate_committed(&client, None, &mut OsRng);
+    assert_eq!(Triple::from_cbor(&committed.to_cbor()), Some(committed));
+    #[cfg(feature = "vss")]
+    {
+        let mut verifiable = Triple::generate_verifiable(&client, None, &mut OsRng);
+        assert_eq!(
+            Triple::from_cbor(&verifiable.to_cbor()),
+            Some(verifiable.clone())
+        );
+        verifiable.commitment = Some(vec![1; 32]);
+        assert_eq!(
+            Triple::from_cbor(&verifiable.to_cbor()),
+            Some(verifiable.clone())
+        );
+        assert_eq!(Triple::from_bytes(&verifiable.to_bytes()), Some(verifiable));
+    }
+
+    let outputs = AggregationServer::new(threshold, "t").retrieve_outputs(&triples);
+    let bytes = outputs[0].to_cbor();
+    let decoded = Output::from_cbor(&bytes).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", outputs[0]));
+    assert_eq!(decoded.to_cbor(), bytes);
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_rejects_malformed() {
+    let client = Client::new(b"foobar", 2, "t", None);
+    let bytes = Triple::generate(&client, None).to_cbor();
+    assert!(Triple::from_cbor(&bytes[..bytes.len() - 1]).is_none());
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(Triple::from_cbor(&trailing).is_none());
+    // an indefinite-length map is not canonical
+    let mut indefinite = bytes.clone();
+    indefinite[0] = 0xbf;
+    indefinite.push(0xff);
+    assert!(Triple::from_cbor(&indefinite).is_none());
+
+    let triples = [
+        Triple::generate(&client, None),
+        Triple::generate(&client, None),
+    ];
+    let outputs = AggregationServer::new(2, "t").retrieve_outputs(&triples);
+    let bytes = outputs[0].to_cbor();
+    assert!(Output::from_cbor(&bytes).is_some());
+    // encode the final `contributors: 2` with a non-minimal length
+    let mut non_minimal = bytes[..bytes.len() - 1].to_vec();
+    non_minimal.extend([0x18, 0x02]);
+    assert!(Output::from_cbor(&non_minimal).is_none());
+    // a valid encoding of the wrong type
+    assert!(Output::from_cbor(&triples[0].to_cbor()).is_none());
+}
+
 #[test]
 fn roundtrip() {
     let client = Client::new(b"foobar", 1, "epoch", None);
@@ -41,6 +308,619 @@ fn roundtrip() {
     assert_eq!(measurement_bytes, b"foobar");
 }
 
+#[test]
+fn snapshot_restore_finalize() {
+    let threshold = 3;
+    let epoch = "t";
+    let triples: Vec<Triple> = (0..20)
+        .map(|i| {
+            let measurement = [(i % 4) as u8];
+            let client = Client::new(&measurement, threshold, epoch, Some(vec![i as u8]));
+            Triple::generate(&client, None)
+        })
+        .collect();
+    let (first, second) = triples.split_at(9);
+
+    let mut agg_server = AggregationServer::new(threshold, epoch);
+    agg_server.ingest(first);
+    let snapshot = agg_server.snapshot();
+    drop(agg_server);
+
+    let mut restored = AggregationServer::restore(&snapshot).unwrap();
+    assert_eq!(restored.threshold, threshold);
+    assert_eq!(restored.epoch, epoch);
+    restored.ingest(second);
+
+    let uninterrupted = AggregationServer::new(threshold, epoch);
+    let chk_outputs = summarize_outputs(uninterrupted.retrieve_outputs(&triples));
+    let outputs = summarize_outputs(restored.finalize());
+    assert_eq!(outputs.len(), 4);
+    assert_eq!(outputs, chk_outputs);
+}
+
+#[test]
+fn outputs_record_threshold() {
+    let threshold = 4;
+    let epoch = "t";
+    let triples: Vec<Triple> = (0..10)
+        .map(|i| Client::new(&[(i % 2) as u8], threshold, epoch, None))
+        .map(|c| Triple::generate(&c, None))
+        .collect();
+    let agg_server = AggregationServer::new(threshold, epoch);
+    let outputs = agg_server.retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 2);
+    for o in outputs {
+        assert_eq!(o.threshold, threshold);
+        assert!(o.aux.len() >= o.threshold as usize);
+    }
+}
+
+#[test]
+fn outputs_record_contributors() {
+    let threshold = 3;
+    let epoch = "t";
+    let mut triples: Vec<Triple> = (0..7)
+        .map(|i| Client::new(&[(i % 2) as u8], threshold, epoch, None))
+        .map(|c| Triple::generate(&c, None))
+        .collect();
+    // a resubmitted triple is not an extra contributor
+    triples.push(triples[0].clone());
+    let agg_server = AggregationServer::new(threshold, epoch);
+    let outputs = agg_server.retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(outputs[0].x.as_slice(), &[0]);
+    assert_eq!(outputs[0].contributors, 4);
+    assert_eq!(outputs[0].aux.len(), 5);
+    assert_eq!(outputs[1].contributors, 3);
+    assert_eq!(outputs[1].contributors, outputs[1].threshold as usize);
+}
+
+#[test]
+fn cross_epoch_triples_skipped() {
+    let threshold = 2;
+    let generate = |epoch: &str, n: usize| -> Vec<Triple> {
+        (0..n)
+            .map(|_| Client::new(b"foobar", threshold, epoch, None))
+            .map(|c| Triple::generate(&c, None))
+            .collect()
+    };
+    let mut triples = generate("a", 2);
+    triples.extend(generate("b", 3));
+    triples.extend(generate("a", 1));
+
+    let agg_server = AggregationServer::new(threshold, "a");
+    let outputs = agg_server.retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].x.as_slice(), b"foobar");
+    assert_eq!(outputs[0].aux.len(), 3);
+    assert_eq!(agg_server.cross_epoch_skipped(), 3);
+
+    let mut agg_server = AggregationServer::new(threshold, "b");
+    agg_server.ingest(&triples);
+    assert_eq!(agg_server.cross_epoch_skipped(), 3);
+    assert_eq!(agg_server.finalize()[0].aux.len(), 3);
+
+    // the epoch survives serialization
+    let bytes = triples[2].to_bytes();
+    assert_eq!(Triple::from_bytes(&bytes).unwrap().epoch, "b");
+}
+
+#[test]
+fn multi_epoch_results_per_epoch() {
+    let threshold = 3;
+    let generate = |value: &[u8], epoch: &str, n: usize| -> Vec<Triple> {
+        (0..n)
+            .map(|_| Client::new(value, threshold, epoch, None))
+            .map(|c| Triple::generate(&c, None))
+            .collect()
+    };
+    // "foo" meets the threshold in both epochs, "bar" only across them,
+    // which must not count
+    let mut triples = generate(b"foo", "a", 3);
+    triples.extend(generate(b"foo", "b", 4));
+    triples.extend(generate(b"bar", "a", 2));
+    triples.extend(generate(b"bar", "b", 2));
+    triples.extend(generate(b"foo", "c", 3));
+
+    let mut agg_server = MultiEpochAggregationServer::new(threshold, &["b", "a"]);
+    assert_eq!(agg_server.active_epochs(), vec!["a", "b"]);
+    let summarize = |outputs: Vec<(String, Output)>| -> Vec<(String, Vec<u8>, usize)> {
+        outputs
+            .into_iter()
+            .map(|(epoch, o)| (epoch, o.x.as_vec(), o.aux.len()))
+            .collect()
+    };
+    let expected = vec![
+        ("a".to_string(), b"foo".to_vec(), 3),
+        ("b".to_string(), b"foo".to_vec(), 4),
+    ];
+    assert_eq!(summarize(agg_server.retrieve_outputs(&triples)), expected);
+    assert_eq!(agg_server.inactive_epoch_skipped(), 3);
+
+    agg_server.ingest(&triples[..8]);
+    agg_server.ingest(&triples[8..]);
+    assert_eq!(summarize(agg_server.finalize()), expected);
+    assert_eq!(agg_server.inactive_epoch_skipped(), 6);
+
+    // Closing an epoch releases it, and later triples for it are skipped
+    let closed = agg_server.close_epoch("a").unwrap();
+    assert_eq!(closed.len(), 1);
+    assert!(agg_server.close_epoch("a").is_none());
+    agg_server.open_epoch("c");
+    agg_server.ingest(&triples);
+    let epochs: Vec<String> = agg_server
+        .finalize()
+        .into_iter()
+        .map(|(epoch, _)| epoch)
+        .collect();
+    assert_eq!(epochs, vec!["b", "c"]);
+}
+
+#[test]
+fn max_tags_drops_excess() {
+    let threshold = 2;
+    let epoch = "t";
+    let triples: Vec<Triple> = (0..5u8)
+        .flat_map(|i| vec![i; 2])
+        .map(|i| Client::new(&[i], threshold, epoch, None))
+        .map(|c| Triple::generate(&c, None))
+        .collect();
+
+    // Only the first three tags are tracked; the pairs of triples for
+    // the other two are dropped
+    let mut agg_server = AggregationServer::new(threshold, epoch).with_max_tags(3);
+    agg_server.ingest(&triples);
+    assert_eq!(agg_server.tag_limit_dropped(), 4);
+    let outputs = agg_server.finalize();
+    let values: Vec<&[u8]> = outputs.iter().map(|o| o.x.as_slice()).collect();
+    assert_eq!(values, vec![&[0u8][..], &[1], &[2]]);
+
+    // Tracked groups continue to accumulate
+    agg_server.ingest(&[Triple::generate(
+        &Client::new(&[1], threshold, epoch, None),
+        None,
+    )]);
+    agg_server.ingest(&[Triple::generate(
+        &Client::new(&[9], threshold, epoch, None),
+        None,
+    )]);
+    assert_eq!(agg_server.tag_limit_dropped(), 5);
+    assert_eq!(agg_server.finalize()[1].aux.len(), 3);
+
+    let agg_server = AggregationServer::new(threshold, epoch).with_max_tags(1);
+    assert_eq!(agg_server.retrieve_outputs(&triples).len(), 1);
+    assert_eq!(agg_server.tag_limit_dropped(), 8);
+}
+
+#[test]
+fn round_trip_recovers() {
+    for &threshold in &[1, 2, 5, 20] {
+        for &len in &[0, 1, 31, 32, 100, 1000] {
+            let secret: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            assert_eq!(round_trip(&secret, threshold, threshold as usize), secret);
+            assert_eq!(
+                round_trip(&secret, threshold, threshold as usize + 3),
+                secret
+            );
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn round_trip_too_few_shares() {
+    round_trip(b"secret", 3, 2);
+}
+
+#[test]
+fn malformed_triples_spoil_only_their_group() {
+    let threshold = 3;
+    let epoch = "t";
+    let triples_for = |x: &[u8]| -> Vec<Triple> {
+        let client = Client::new(x, threshold, epoch, None);
+        (0..threshold)
+            .map(|_| Triple::generate(&client, None))
+            .collect()
+    };
+    let foo = triples_for(b"foo");
+    let bar = triples_for(b"bar");
+    let mut baz = triples_for(b"baz");
+
+    // A share of another measurement under the tag of foo, and a
+    // ciphertext which doesn't decrypt to a measurement
+    let mut forged = triples_for(b"other").remove(0);
+    forged.tag = foo[0].tag.clone();
+    baz[0].ciphertext = Ciphertext::from_bytes(&[0xff; 40]);
+
+    let triples = [vec![forged], foo, bar, baz].concat();
+    let agg_server = AggregationServer::new(threshold, epoch);
+    let outputs = agg_server.retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].x.as_slice(), b"bar");
+    assert_eq!(agg_server.unrecoverable_groups(), 2);
+}
+
+#[cfg(feature = "vss")]
+#[test]
+fn unverifiable_shares_rejected() {
+    let threshold = 3;
+    let epoch = "t";
+    let triples_for = |x: &[u8]| -> Vec<Triple> {
+        let client = Client::new(x, threshold, epoch, None);
+        (0..threshold)
+            .map(|_| Triple::generate_verifiable(&client, None, &mut OsRng))
+            .collect()
+    };
+    let foo = triples_for(b"foo");
+    assert!(foo.iter().all(|t| t.verify_share() && t.validate().is_ok()));
+    assert!(foo
+        .iter()
+        .all(|t| t.share_commitments == foo[0].share_commitments));
+    assert_eq!(
+        Triple::from_bytes(&foo[0].to_bytes()).as_ref(),
+        Some(&foo[0])
+    );
+
+    // A share of another measurement under the tag and commitments of
+    // foo, which would otherwise spoil the group
+    let mut forged = triples_for(b"other").remove(0);
+    forged.tag = foo[0].tag.clone();
+    forged.share_commitments = foo[0].share_commitments.clone();
+    assert!(!forged.verify_share());
+
+    let triples = [vec![forged.clone()], foo.clone()].concat();
+    let agg_server = AggregationServer::new(threshold, epoch);
+    let outputs = agg_server.retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].x.as_slice(), b"foo");
+    assert_eq!(agg_server.unverified_shares(), 1);
+    assert_eq!(agg_server.unrecoverable_groups(), 0);
+
+    // A triple without commitments is only rejected if verifiable shares
+    // are required, but is never grouped with triples carrying them
+    let mut unverifiable = forged;
+    unverifiable.share_commitments = None;
+    let triples = [vec![unverifiable.clone()], foo.clone()].concat();
+    let outputs = AggregationServer::new(threshold, epoch).retrieve_outputs(&triples);
+    assert_eq!(outputs.len(), 1);
+    let agg_server = AggregationServer::new(threshold, epoch).with_verifiable_shares_required();
+    assert_eq!(agg_server.retrieve_outputs(&triples).len(), 1);
+    assert_eq!(agg_server.unverified_shares(), 1);
+
+    // Commitments that don't parse
+    let mut truncated = foo[0].clone();
+    truncated.share_commitments.as_mut().unwrap().pop();
+    assert_eq!(
+        truncated.validate(),
+        Err(TripleError::MalformedShareCommitments)
+    );
+    assert!(!truncated.verify_share());
+}
+
+#[test]
+fn heavy_hitters_drop_long_tail() {
+    let threshold = 5;
+    let epoch = "t";
+    let clients: Vec<Client> = (0..1000)
+        .map(|_| client_zipf(100, 1.5, threshold, epoch, None))
+        .collect();
+    let mut counts = std::collections::HashMap::new();
+    for client in &clients {
+        *counts.entry(client.x.as_vec()).or_insert(0) += 1;
+    }
+    let triples: Vec<Triple> = clients.iter().map(|c| Triple::generate(c, None)).collect();
+    let agg_server = AggregationServer::new(threshold, epoch);
+
+    // Only the few most popular values are sent 100 times or more
+    let min_count = 100;
+    let hitters = agg_server.heavy_hitters(&triples, min_count);
+    let mut expected: Vec<(Vec<u8>, usize)> = counts
+        .into_iter()
+        .filter(|&(_, count)| count >= min_count)
+        .collect();
+    expected.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    assert_eq!(hitters, expected);
+    assert!(!hitters.is_empty() && hitters.len() <= 3);
+    assert!(hitters.len() < agg_server.retrieve_outputs(&triples).len());
+
+    // Below the threshold, min_count makes no difference
+    assert_eq!(
+        agg_server.heavy_hitters(&triples, 0),
+        agg_server.heavy_hitters(&triples, threshold as usize)
+    );
+}
+
+#[test]
+fn audited_outputs_list_contributors() {
+    let threshold = 3;
+    let epoch = "t";
+    let mut triples: Vec<Triple> = Vec::new();
+    for (x, n) in [(b"a", 4), (b"b", 3), (b"c", 2)] {
+        let client = Client::new(x, threshold, epoch, None);
+        triples.extend((0..n).map(|_| Triple::generate(&client, None)));
+    }
+
+    // A resent triple contributes no new x coordinate
+    triples.push(triples[0].clone());
+    let agg_server = AggregationServer::new(threshold, epoch);
+    let audited = agg_server.retrieve_outputs_audited(&triples);
+    let outputs = agg_server.retrieve_outputs(&triples);
+    assert_eq!(audited.len(), 2);
+    for ((output, contributors), (plain, expected)) in audited
+        .iter()
+        .zip(outputs.iter().zip([&triples[..4], &triples[4..7]]))
+    {
+        assert_eq!(output.x.as_slice(), plain.x.as_slice());
+        let expected: Vec<_> = expected.iter().map(|t| t.share.x()).collect();
+        assert_eq!(contributors, &expected);
+        assert_eq!(contributors.len(), output.contributors);
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_aggregator_cancel_and_resume() {
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::task::{noop_waker, Context};
+    use futures::FutureExt;
+
+    let threshold = 3;
+    let epoch = "t";
+    let triple = |x: &[u8]| Triple::generate(&Client::new(x, threshold, epoch, None), None);
+    let (mut tx, rx) = mpsc::channel(16);
+    let mut aggregator = AsyncAggregator::new(AggregationServer::new(threshold, epoch), rx);
+
+    // Cancel the run part way through the epoch, with a group below the
+    // threshold
+    tx.try_send(triple(b"foo")).unwrap();
+    tx.try_send(triple(b"foo")).unwrap();
+    tx.try_send(triple(b"bar")).unwrap();
+    {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut run = Box::pin(aggregator.run());
+        assert!(run.poll_unpin(&mut cx).is_pending());
+    }
+    assert!(aggregator.finalize().is_empty());
+
+    // Resume, with the state from before the cancellation
+    tx.try_send(triple(b"foo")).unwrap();
+    tx.try_send(triple(b"bar")).unwrap();
+    drop(tx);
+    block_on(aggregator.run());
+    let outputs = aggregator.finalize();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].x.as_slice(), b"foo");
+    assert_eq!(outputs[0].aux.len(), 3);
+}
+
+#[test]
+fn write_ndjson_entries() {
+    let threshold = 2;
+    let epoch = "t";
+    let triples: Vec<Triple> = (0..9)
+        .map(|i| Client::new(&[(i % 4) as u8, 0xab], threshold, epoch, None))
+        .map(|c| Triple::generate(&c, None))
+        .collect();
+    let agg_server = AggregationServer::new(threshold, epoch);
+    let mut out = Vec::new();
+    agg_server.write_ndjson(&triples, &mut out).unwrap();
+
+    let mut entries: Vec<(String, u64, u64)> = std::str::from_utf8(&out)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let v: serde_json::Value = serde_json::from_str(line).unwrap();
+            (
+                v["value"].as_str().unwrap().to_string(),
+                v["count"].as_u64().unwrap(),
+                v["threshold"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    entries.sort();
+    assert_eq!(
+        entries,
+        vec![
+            ("00ab".to_string(), 3, 2),
+            ("01ab".to_string(), 2, 2),
+            ("02ab".to_string(), 2, 2),
+            ("03ab".to_string(), 2, 2),
+        ]
+    );
+}
+
+#[test]
+fn retrieve_outputs_stable_order() {
+    let threshold = 2;
+    let epoch = "t";
+    let triples: Vec<Triple> = (0..40)
+        .map(|i| Client::new(&[(i % 10) as u8], threshold, epoch, Some(vec![i as u8])))
+        .map(|c| Triple::generate(&c, None))
+        .collect();
+    let run = || {
+        let agg_server = AggregationServer::new(threshold, epoch);
+        agg_server
+            .retrieve_outputs(&triples)
+            .into_iter()
+            .map(|o| {
+                let aux: Vec<Vec<u8>> = o.aux.into_iter().flatten().map(|a| a.as_vec()).collect();
+                (o.x.as_vec(), aux)
+            })
+            .collect::<Vec<_>>()
+    };
+    let first = run();
+    assert_eq!(first.len(), 10);
+    assert_eq!(first, run());
+    let measurements: Vec<Vec<u8>> = first.into_iter().map(|(x, _)| x).collect();
+    let expected: Vec<Vec<u8>> = (0..10).map(|i| vec![i as u8]).collect();
+    assert_eq!(measurements, expected);
+}
+
+#[test]
+fn restore_truncated_snapshot() {
+    let mut agg_server = AggregationServer::new(2, "t");
+    let client = Client::new(b"foobar", 2, "t", None);
+    agg_server.ingest(&[Triple::generate(&client, None)]);
+    let snapshot = agg_server.snapshot();
+    assert!(AggregationServer::restore(&snapshot[..snapshot.len() - 1]).is_none());
+}
+
+// Converts outputs into a comparable form, which does not depend on
+// the order in which outputs or aux data were recovered.
+fn summarize_outputs(outputs: Vec<Output>) -> Vec<(Vec<u8>, Vec<Vec<u8>>)> {
+    let mut summary: Vec<(Vec<u8>, Vec<Vec<u8>>)> = outputs
+        .into_iter()
+        .map(|o| {
+            let mut aux: Vec<Vec<u8>> = o.aux.into_iter().flatten().map(|a| a.as_vec()).collect();
+            aux.sort();
+            (o.x.as_vec(), aux)
+        })
+        .collect();
+    summary.sort();
+    summary
+}
+
+#[test]
+fn estimate_recoverable_matches_empirical() {
+    let threshold = 10;
+    let distribution = ZipfPopulation {
+        n: 1000,
+        s: 1.03,
+        clients: 10000,
+    };
+    let estimate = estimate_recoverable(threshold, &distribution);
+
+    let runs = 4;
+    let mut recovered = 0;
+    for _ in 0..runs {
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..distribution.clients {
+            let x = measurement_zipf(distribution.n, distribution.s);
+            *counts.entry(x.as_vec()).or_insert(0) += 1;
+        }
+        recovered += counts.values().filter(|&&c| c >= threshold).count();
+    }
+    let empirical = recovered as f64 / runs as f64;
+    assert!(
+        (estimate - empirical).abs() < 0.1 * estimate,
+        "estimate {} vs empirical {}",
+        estimate,
+        empirical
+    );
+
+    assert_eq!(estimate_recoverable(0, &distribution), 1000.0);
+    assert_eq!(estimate_recoverable(10001, &distribution), 0.0);
+}
+
+#[test]
+fn finalize_oprf_keys_separates_contexts() {
+    let client = Client::new(b"foobar", 2, "t", None);
+    let mut output = [0u8; DIGEST_LEN];
+    client.sample_local_randomness(&mut output);
+    let contexts: &[&[u8]] = &[b"aux encryption", b"tag", b"", b"tag"];
+    let keys = finalize_oprf_keys(&output, contexts);
+    assert_eq!(keys.len(), 4);
+    assert_ne!(keys[0], keys[1]);
+    assert_ne!(keys[0], keys[2]);
+    assert_ne!(keys[1], keys[2]);
+    assert_eq!(keys[1], keys[3]);
+    assert!(keys.iter().all(|k| k != &output));
+
+    // Deterministic for the same output and context
+    assert_eq!(finalize_oprf_keys(&output, &[b"tag"]), vec![keys[1]]);
+    let mut other = output;
+    other[0] ^= 1;
+    assert_ne!(finalize_oprf_keys(&other, &[b"tag"])[0], keys[1]);
+    assert!(finalize_oprf_keys(&output, &[]).is_empty());
+
+    // HKDF-SHA256 with the salt "star_finalize_oprf_keys"
+    let mut output = [0u8; DIGEST_LEN];
+    for (i, b) in output.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let expected = "2ebca22d9df507dbf3f3e3e9fb6d0dab06d90854f31dcaae956ee5447a41e9ff";
+    let key = finalize_oprf_keys(&output, &[b"tag"])[0];
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    assert_eq!(hex, expected);
+}
+
+#[test]
+fn sweep_thresholds_decreases() {
+    // Groups of 5, 15, 25 and 35 clients, shared with threshold 5
+    let triples: Vec<Triple> = [5, 15, 25, 35]
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &size)| {
+            let client = Client::new(format!("m{}", i).as_bytes(), 5, "t", None);
+            (0..size)
+                .map(|_| Triple::generate(&client, None))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let sweep = sweep_thresholds(&triples, &[5, 10, 15, 20, 25, 30, 35, 40]);
+    assert_eq!(
+        sweep,
+        vec![
+            (5, 4),
+            (10, 3),
+            (15, 3),
+            (20, 2),
+            (25, 2),
+            (30, 1),
+            (35, 1),
+            (40, 0)
+        ]
+    );
+    assert!(sweep.windows(2).all(|w| w[1].1 <= w[0].1));
+
+    // Agrees with a server at each threshold that the clients can meet
+    for &(threshold, recovered) in &sweep {
+        let outputs = AggregationServer::new(threshold, "t").retrieve_outputs(&triples);
+        assert_eq!(outputs.len(), recovered);
+    }
+    assert_eq!(sweep_thresholds(&[], &[1]), vec![(1, 0)]);
+}
+
+#[test]
+fn group_sizes_without_recovery() {
+    let sizes = [7, 3, 3, 1];
+    let mut triples: Vec<Triple> = sizes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &size)| {
+            let client = Client::new(format!("m{}", i).as_bytes(), 3, "t", None);
+            (0..size)
+                .map(|_| Triple::generate(&client, None))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    triples.push(Triple::generate(&Client::new(b"m0", 3, "s", None), None));
+    let server = AggregationServer::new(3, "t");
+
+    #[cfg(feature = "profile")]
+    sharks::profile::take_stats();
+    let counted = server.group_sizes(&triples);
+    #[cfg(feature = "profile")]
+    {
+        // Nothing was interpolated, unlike when shares are recovered
+        assert_eq!(sharks::profile::take_stats().multiplications, 0);
+        let shares: Vec<Share> = triples[..3].iter().map(|t| t.share.clone()).collect();
+        share_recover(&shares).unwrap();
+        assert!(sharks::profile::take_stats().multiplications > 0);
+    }
+
+    // Sorted by size, including groups below the threshold, and only
+    // for the server's epoch
+    let found: Vec<usize> = counted.iter().map(|(_, size)| *size).collect();
+    assert_eq!(found, sizes);
+    assert_eq!(counted[0].0, triples[0].tag);
+    assert!(counted[1].0 < counted[2].0);
+    assert_eq!(server.cross_epoch_skipped(), 0);
+    assert_eq!(server.unrecoverable_groups(), 0);
+}
+
 #[test]
 fn star1_no_aux_multiple_block() {
     star_no_aux_multiple_block(None);
@@ -61,32 +941,82 @@ fn star1_rand_with_aux_multiple_block() {
     star_rand_with_aux_multiple_block(None);
 }
 
+#[cfg(feature = "star2")]
+#[test]
+fn star2_verifiable_oprf_randomness() {
+    let mds: &[Vec<u8>] = &[b"s".to_vec(), b"t".to_vec()];
+    let server = PPOPRFServer::new(mds);
+    let client = Client::new(b"foobar", 2, "t", None);
+
+    let mut out = vec![0u8; 32];
+    let proof = client.sample_oprf_randomness_verifiable(&server, &mut out);
+    assert_eq!(proof.md_idx, 1);
+    assert!(proof.evaluation.proof().is_some());
+    assert!(proof.verify(&server.get_public_key()));
+
+    // The randomness is the same as from the non-returning path
+    let mut expected = vec![0u8; 32];
+    client.sample_oprf_randomness(&server, &mut expected);
+    assert_eq!(out, expected);
+
+    // The proof doesn't hold for another server, or another tag
+    assert!(!proof.verify(&PPOPRFServer::new(mds).get_public_key()));
+    let mut wrong_tag = proof.clone();
+    wrong_tag.md_idx = 0;
+    assert!(!wrong_tag.verify(&server.get_public_key()));
+}
+
 #[cfg(feature = "star2")]
 #[test]
 fn star2_no_aux_multiple_block() {
     let mds: &[Vec<u8>] = &[b"t".to_vec()];
-    star_no_aux_multiple_block(Some(PPOPRFServer::new(&mds)));
+    star_no_aux_multiple_block(Some(PPOPRFServer::new(mds)));
 }
 
 #[cfg(feature = "star2")]
 #[test]
 fn star2_no_aux_single_block() {
     let mds: &[Vec<u8>] = &[b"t".to_vec()];
-    star_no_aux_single_block(Some(PPOPRFServer::new(&mds)));
+    star_no_aux_single_block(Some(PPOPRFServer::new(mds)));
 }
 
 #[cfg(feature = "star2")]
 #[test]
 fn star2_with_aux_multiple_block() {
     let mds: &[Vec<u8>] = &[b"t".to_vec()];
-    star_with_aux_multiple_block(Some(PPOPRFServer::new(&mds)));
+    star_with_aux_multiple_block(Some(PPOPRFServer::new(mds)));
 }
 
 #[cfg(feature = "star2")]
 #[test]
 fn star2_rand_with_aux_multiple_block() {
     let mds: &[Vec<u8>] = &[b"t".to_vec()];
-    star_rand_with_aux_multiple_block(Some(PPOPRFServer::new(&mds)));
+    star_rand_with_aux_multiple_block(Some(PPOPRFServer::new(mds)));
+}
+
+#[cfg(feature = "star2")]
+#[test]
+fn star2_external_oprf_output() {
+    let mds: &[Vec<u8>] = &[b"t".to_vec()];
+    let oprf_server = PPOPRFServer::new(mds);
+    let aux = Some(b"aux".to_vec());
+    let in_process = Client::new(b"hello world", 2, "t", aux.clone());
+    let mut out = [0u8; DIGEST_LEN];
+    in_process.sample_oprf_randomness(&oprf_server, &mut out);
+
+    // a client driven by an OPRF output computed elsewhere
+    let mut external = Client::new(b"hello world", 2, "t", aux);
+    external.set_oprf_output(out);
+    let expected = Triple::generate(&in_process, Some(&oprf_server));
+    let triple = Triple::generate(&external, None);
+    assert_eq!(triple.ciphertext, expected.ciphertext);
+    assert_eq!(triple.tag, expected.tag);
+
+    // the shares are of the same secret, so recover together
+    let agg_server = AggregationServer::new(2, "t");
+    let outputs = agg_server.retrieve_outputs(&[expected, triple]);
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].x.as_slice(), b"hello world");
 }
 
 fn star_no_aux_multiple_block(oprf_server: Option<PPOPRFServer>) {
@@ -123,7 +1053,7 @@ fn star_no_aux_multiple_block(oprf_server: Option<PPOPRFServer>) {
             panic!("Unexpected tag: {}", tag_str);
         }
 
-        for b in o.aux.into_iter().flatten() {
+        if let Some(b) = o.aux.into_iter().flatten().next() {
             panic!("Unexpected auxiliary data: {:?}", b);
         }
     }
@@ -163,12 +1093,13 @@ fn star_no_aux_single_block(oprf_server: Option<PPOPRFServer>) {
             panic!("Unexpected tag: {}", tag_str);
         }
 
-        for b in o.aux.into_iter().flatten() {
+        if let Some(b) = o.aux.into_iter().flatten().next() {
             panic!("Unexpected auxiliary data: {:?}", b);
         }
     }
 }
 
+#[allow(clippy::never_loop)]
 fn star_with_aux_multiple_block(oprf_server: Option<PPOPRFServer>) {
     let mut clients = Vec::new();
     let threshold = 2;
@@ -191,12 +1122,7 @@ fn star_with_aux_multiple_block(oprf_server: Option<PPOPRFServer>) {
                 Some(vec![i + 1; 1]),
             ));
         } else {
-            clients.push(Client::new(
-                &[i as u8],
-                threshold,
-                epoch,
-                Some(vec![i + 1; 1]),
-            ));
+            clients.push(Client::new(&[i], threshold, epoch, Some(vec![i + 1; 1])));
         }
     }
     let agg_server = AggregationServer::new(threshold, epoch);
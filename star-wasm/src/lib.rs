@@ -1,6 +1,7 @@
 use wasm_bindgen::prelude::*;
 
 use base64::{decode, encode};
+use rand_core::OsRng;
 
 use sta_rs::{derive_ske_key, share_recover, Client, ClientSharingMaterial, Share};
 
@@ -30,10 +31,11 @@ pub fn create_share(measurement: &[u8], threshold: u32, epoch: &str) -> String {
     // panic::set_hook(Box::new(console_error_panic_hook::hook));
 
     let client = Client::new(measurement, threshold, epoch, None);
-    let ClientSharingMaterial { key, share, tag } = client.share_with_local_randomness();
+    let ClientSharingMaterial { key, share, tag } =
+        client.share_with_local_randomness_rng(&mut OsRng);
 
     let key_b64 = encode(&key);
-    let share_b64 = encode(&share.to_bytes());
+    let share_b64 = encode(share.to_bytes());
     let tag_b64 = encode(&tag);
 
     format!(